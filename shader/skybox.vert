@@ -0,0 +1,21 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(location = 0) out vec3 fragDirection;
+
+void main() {
+    // A single triangle covering the whole screen on the far plane
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    gl_Position = vec4(position, 1.0, 1.0);
+
+    // Only the rotation of the camera matters. The scene has Z as up while cubemaps have Y.
+    vec4 viewPosition = inverse(ubo.proj) * vec4(position, 1.0, 1.0);
+    vec3 direction = transpose(mat3(ubo.view)) * (viewPosition.xyz / viewPosition.w);
+    fragDirection = vec3(direction.x, direction.z, -direction.y);
+}
@@ -0,0 +1,43 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+const uint TONEMAP_REINHARD = 0;
+const uint TONEMAP_ACES = 1;
+
+layout(binding = 0) uniform texture2D hdrImage;
+layout(binding = 1) uniform sampler hdrSampler;
+
+layout(binding = 2) uniform TonemapParams {
+    float exposure;
+    uint tonemapper;
+    uint encodeSrgb;
+} params;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 linearToSrgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, lessThanEqual(color, vec3(0.0031308)));
+}
+
+// Curve fit of the ACES filmic tonemapper by Krzysztof Narkowicz
+vec3 aces(vec3 color) {
+    return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+}
+
+void main() {
+    vec3 color = texture(sampler2D(hdrImage, hdrSampler), fragTexCoord).rgb * params.exposure;
+    if (params.tonemapper == TONEMAP_ACES) {
+        color = aces(color);
+    } else {
+        color = color / (1.0 + color);
+    }
+    color = clamp(color, 0.0, 1.0);
+    if (params.encodeSrgb != 0) {
+        color = linearToSrgb(color);
+    }
+    outColor = vec4(color, 1.0);
+}
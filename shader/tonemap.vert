@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// A single triangle covering the whole screen, positions are already in clip space
+layout(location = 0) in vec3 inPosition;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec2 fragTexCoord;
+
+void main() {
+    fragTexCoord = inTexCoord;
+    gl_Position = vec4(inPosition, 1.0);
+}
@@ -1,9 +1,12 @@
-use winit::{dpi::PhysicalSize, window::Window};
+use crate::streaming::StreamingProgress;
+use winit::{dpi::PhysicalSize, event::VirtualKeyCode, window::Window};
 
 pub trait App {
     fn wait_idle(&self);
     fn update(&mut self);
     fn resized(&mut self, window: &Window, size: PhysicalSize<u32>);
     fn minimized(&mut self, window: &Window);
+    fn key_pressed(&mut self, key: VirtualKeyCode);
+    fn loading_progress(&self) -> StreamingProgress;
     fn draw_frame(&mut self, window: &Window);
 }
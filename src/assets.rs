@@ -0,0 +1,511 @@
+mod watcher;
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use ash::vk;
+
+use crate::{
+    mesh::{Mesh, Primitive},
+    streaming::{AssetStreamer, ModelBuffers, StreamedAsset, StreamingProgress},
+    vulkan::{
+        MipGeneration, SamplerDesc, TextureColorSpace, VkBuffer, VkCommandPool, VkDeletionQueue,
+        VkDevice, VkImage, VkSampler, VkShaderModule, VkTexture,
+    },
+};
+
+use self::watcher::FileWatcher;
+
+// Shown until the streamed assets are resident
+const PLACEHOLDER_SIZE: f32 = 0.5;
+const PLACEHOLDER_COLOR: [u8; 4] = [128, 128, 128, 255];
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+pub type AssetId = usize;
+
+// Typed reference to an asset of the manager. Assets stay loaded while any handle to them is
+// alive, clones share the same asset.
+pub struct Handle<T> {
+    id: AssetId,
+    token: Arc<()>,
+    marker: PhantomData<fn() -> T>,
+}
+
+pub struct Material {
+    pub base_color: Handle<VkTexture>,
+    pub sampler: Arc<VkSampler>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct TextureKey {
+    path: PathBuf,
+    color_space: TextureColorSpace,
+    mip_generation: MipGeneration,
+}
+
+// Models are loaded from files or generated, only files are watched for changes
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum ModelSource {
+    File(PathBuf),
+    Primitive(Primitive),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ModelKey {
+    source: ModelSource,
+    max_lod_levels: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ShaderKey {
+    path: PathBuf,
+    stage: vk::ShaderStageFlags,
+    entry_point: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct MaterialKey {
+    base_color: TextureKey,
+    sampler: SamplerDesc,
+}
+
+// Streamed assets have no value until their upload has finished
+struct Slot<K, T> {
+    key: K,
+    token: Arc<()>,
+    asset: Option<T>,
+}
+
+struct AssetStore<K, T> {
+    slots: HashMap<AssetId, Slot<K, T>>,
+    ids: HashMap<K, AssetId>,
+}
+
+// Loads assets by path and parameters, repeated loads return the already loaded asset. Relative
+// paths are looked up in the asset roots in order. Textures and models are reloaded when their
+// files change.
+pub struct AssetManager {
+    device: Arc<VkDevice>,
+    roots: Vec<PathBuf>,
+    watcher: FileWatcher,
+    streamer: AssetStreamer,
+    materials: AssetStore<MaterialKey, Material>,
+    textures: AssetStore<TextureKey, VkTexture>,
+    models: AssetStore<ModelKey, ModelBuffers>,
+    shaders: AssetStore<ShaderKey, VkShaderModule>,
+    next_id: AssetId,
+    placeholder_texture: VkTexture,
+    placeholder_model: ModelBuffers,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            id: self.id,
+            token: Arc::clone(&self.token),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl ModelKey {
+    fn path(&self) -> Option<&Path> {
+        match &self.source {
+            ModelSource::File(path) => Some(path),
+            ModelSource::Primitive(_) => None,
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash, T> AssetStore<K, T> {
+    fn new() -> AssetStore<K, T> {
+        AssetStore {
+            slots: HashMap::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    fn find(&self, key: &K) -> Option<Handle<T>> {
+        self.ids.get(key).map(|&id| self.handle(id))
+    }
+
+    fn insert(&mut self, id: AssetId, key: K, asset: Option<T>) -> Handle<T> {
+        self.ids.insert(key.clone(), id);
+        self.slots.insert(
+            id,
+            Slot {
+                key,
+                token: Arc::new(()),
+                asset,
+            },
+        );
+        self.handle(id)
+    }
+
+    fn handle(&self, id: AssetId) -> Handle<T> {
+        Handle {
+            id,
+            token: Arc::clone(&self.slots[&id].token),
+            marker: PhantomData,
+        }
+    }
+
+    fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.slots[&handle.id].asset.as_ref()
+    }
+
+    fn slot_mut(&mut self, id: AssetId) -> Option<&mut Slot<K, T>> {
+        self.slots.get_mut(&id)
+    }
+
+    fn matching(&self, predicate: impl Fn(&K) -> bool) -> Vec<(AssetId, K)> {
+        self.slots
+            .iter()
+            .filter(|(_, slot)| predicate(&slot.key))
+            .map(|(&id, slot)| (id, slot.key.clone()))
+            .collect()
+    }
+
+    // Slots are unused when the store holds the only reference to their token
+    fn remove_unused(&mut self) -> Vec<Slot<K, T>> {
+        let unused: Vec<AssetId> = self
+            .slots
+            .iter()
+            .filter(|(_, slot)| Arc::strong_count(&slot.token) == 1)
+            .map(|(&id, _)| id)
+            .collect();
+        unused
+            .iter()
+            .map(|id| {
+                let slot = self.slots.remove(id).unwrap();
+                self.ids.remove(&slot.key);
+                slot
+            })
+            .collect()
+    }
+}
+
+impl AssetManager {
+    pub fn new(
+        device: &Arc<VkDevice>,
+        command_pool: &Arc<VkCommandPool>,
+        roots: Vec<PathBuf>,
+    ) -> AssetManager {
+        log::info!("Using asset roots {:?}", roots);
+
+        let graphics_queue = &device.queues.graphics;
+        let placeholder_texture = VkImage::create_placeholder_texture(
+            device,
+            command_pool,
+            graphics_queue,
+            PLACEHOLDER_COLOR,
+        );
+        let placeholder = Mesh::cube(PLACEHOLDER_SIZE);
+        let bounding_sphere = placeholder.bounding_sphere();
+        let (mesh, lods) = Mesh::merge(&[placeholder]);
+        let placeholder_model = ModelBuffers {
+            vertex_buffer: VkBuffer::new_device_local(
+                device,
+                command_pool,
+                graphics_queue,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                &mesh.vertices,
+            ),
+            index_buffer: VkBuffer::new_device_local(
+                device,
+                command_pool,
+                graphics_queue,
+                vk::BufferUsageFlags::INDEX_BUFFER,
+                &mesh.indices,
+            ),
+            lods,
+            bounding_sphere,
+        };
+
+        AssetManager {
+            device: Arc::clone(device),
+            roots,
+            watcher: FileWatcher::new(WATCH_INTERVAL),
+            streamer: AssetStreamer::new(device, command_pool),
+            materials: AssetStore::new(),
+            textures: AssetStore::new(),
+            models: AssetStore::new(),
+            shaders: AssetStore::new(),
+            next_id: 0,
+            placeholder_texture,
+            placeholder_model,
+        }
+    }
+
+    // Absolute paths are used as they are. Relative paths resolve against the first root that
+    // contains them, or the first root if none does so that loading reports the missing file.
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return path.to_owned();
+        }
+        match self
+            .roots
+            .iter()
+            .map(|root| root.join(path))
+            .find(|path| path.exists())
+        {
+            Some(resolved) => resolved,
+            None => {
+                log::warn!("Asset {} not found in {:?}", path.display(), self.roots);
+                self.roots
+                    .first()
+                    .map(|root| root.join(path))
+                    .unwrap_or_else(|| path.to_owned())
+            }
+        }
+    }
+
+    pub fn progress(&self) -> StreamingProgress {
+        self.streamer.progress()
+    }
+
+    pub fn load_texture(
+        &mut self,
+        path: &str,
+        color_space: TextureColorSpace,
+        mip_generation: MipGeneration,
+    ) -> Handle<VkTexture> {
+        let key = TextureKey {
+            path: self.resolve(path),
+            color_space,
+            mip_generation,
+        };
+        if let Some(handle) = self.textures.find(&key) {
+            return handle;
+        }
+
+        let id = self.allocate_id();
+        self.watcher.watch(&key.path);
+        self.stream_texture(id, &key);
+        self.textures.insert(id, key, None)
+    }
+
+    pub fn load_model(&mut self, path: &str, max_lod_levels: usize) -> Handle<ModelBuffers> {
+        let path = self.resolve(path);
+        self.watcher.watch(&path);
+        self.find_or_stream_model(ModelKey {
+            source: ModelSource::File(path),
+            max_lod_levels,
+        })
+    }
+
+    pub fn load_primitive(
+        &mut self,
+        primitive: Primitive,
+        max_lod_levels: usize,
+    ) -> Handle<ModelBuffers> {
+        self.find_or_stream_model(ModelKey {
+            source: ModelSource::Primitive(primitive),
+            max_lod_levels,
+        })
+    }
+
+    // Shaders are small and needed to build pipelines, so they are loaded right away
+    pub fn load_shader(
+        &mut self,
+        path: &str,
+        stage: vk::ShaderStageFlags,
+        entry_point: &str,
+    ) -> Handle<VkShaderModule> {
+        let key = ShaderKey {
+            path: self.resolve(path),
+            stage,
+            entry_point: entry_point.to_owned(),
+        };
+        if let Some(handle) = self.shaders.find(&key) {
+            return handle;
+        }
+
+        let shader = VkShaderModule::new_from_file(
+            &self.device,
+            stage,
+            &key.path.to_string_lossy(),
+            entry_point,
+        );
+        let id = self.allocate_id();
+        self.shaders.insert(id, key, Some(shader))
+    }
+
+    // Materials reference their texture by handle, keeping it loaded as long as they are
+    pub fn load_material(
+        &mut self,
+        base_color: &str,
+        color_space: TextureColorSpace,
+        mip_generation: MipGeneration,
+        sampler: Arc<VkSampler>,
+    ) -> Handle<Material> {
+        let key = MaterialKey {
+            base_color: TextureKey {
+                path: self.resolve(base_color),
+                color_space,
+                mip_generation,
+            },
+            sampler: sampler.desc,
+        };
+        if let Some(handle) = self.materials.find(&key) {
+            return handle;
+        }
+
+        let material = Material {
+            base_color: self.load_texture(base_color, color_space, mip_generation),
+            sampler,
+        };
+        let id = self.allocate_id();
+        self.materials.insert(id, key, Some(material))
+    }
+
+    pub fn texture(&self, handle: &Handle<VkTexture>) -> &VkTexture {
+        self.textures
+            .get(handle)
+            .unwrap_or(&self.placeholder_texture)
+    }
+
+    pub fn model(&self, handle: &Handle<ModelBuffers>) -> &ModelBuffers {
+        self.models.get(handle).unwrap_or(&self.placeholder_model)
+    }
+
+    pub fn shader(&self, handle: &Handle<VkShaderModule>) -> &VkShaderModule {
+        self.shaders.get(handle).unwrap()
+    }
+
+    pub fn material(&self, handle: &Handle<Material>) -> &Material {
+        self.materials.get(handle).unwrap()
+    }
+
+    // Starts reloads of changed files and moves finished uploads into their slots, returns true
+    // when any asset was replaced. Reload errors are logged and keep the previous asset. Frames
+    // in flight may still use replaced assets, so they are retired to `deletion_queue`.
+    pub fn update(&mut self, deletion_queue: &mut VkDeletionQueue) -> bool {
+        for path in self.watcher.changes() {
+            self.reload(&path);
+        }
+
+        let assets = self.streamer.poll();
+        let mut changed = false;
+        for (id, asset) in assets {
+            // Assets unloaded while they were streaming are dropped right away
+            match asset {
+                StreamedAsset::Texture(texture) => {
+                    if let Some(slot) = self.textures.slot_mut(id) {
+                        if let Some(old) = slot.asset.replace(texture) {
+                            deletion_queue.retire(old);
+                        }
+                        changed = true;
+                    }
+                }
+                StreamedAsset::Model(model) => {
+                    if let Some(slot) = self.models.slot_mut(id) {
+                        if let Some(old) = slot.asset.replace(model) {
+                            deletion_queue.retire(old);
+                        }
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    // Unloads assets without handles. Textures and models are retired to `deletion_queue` as
+    // frames in flight may still use them, materials and shader modules are only used while
+    // recording and dropped right away.
+    pub fn unload_unused(&mut self, deletion_queue: &mut VkDeletionQueue) {
+        // Materials hold texture handles and go first
+        let count = self.materials.remove_unused().len() + self.shaders.remove_unused().len();
+        let textures = self.textures.remove_unused();
+        let models = self.models.remove_unused();
+        let count = count + textures.len() + models.len();
+
+        let mut paths = Vec::new();
+        for slot in textures {
+            paths.push(slot.key.path);
+            if let Some(texture) = slot.asset {
+                deletion_queue.retire(texture);
+            }
+        }
+        for slot in models {
+            if let ModelSource::File(path) = slot.key.source {
+                paths.push(path);
+            }
+            if let Some(model) = slot.asset {
+                deletion_queue.retire(model);
+            }
+        }
+        for path in paths {
+            let in_use = !self.textures.matching(|key| key.path == path).is_empty()
+                || !self
+                    .models
+                    .matching(|key| key.path() == Some(&path))
+                    .is_empty();
+            if !in_use {
+                self.watcher.unwatch(&path);
+            }
+        }
+
+        if count > 0 {
+            log::info!("Unloaded {} unused assets", count);
+        }
+    }
+
+    fn reload(&mut self, path: &Path) {
+        for (id, key) in self.textures.matching(|key| key.path == path) {
+            log::info!("Reloading texture {}", path.display());
+            self.stream_texture(id, &key);
+        }
+        for (id, key) in self.models.matching(|key| key.path() == Some(path)) {
+            log::info!("Reloading model {}", path.display());
+            self.stream_model(id, &key);
+        }
+    }
+
+    fn stream_texture(&mut self, id: AssetId, key: &TextureKey) {
+        self.streamer.load_texture(
+            id,
+            &key.path.to_string_lossy(),
+            key.color_space,
+            key.mip_generation,
+        );
+    }
+
+    fn find_or_stream_model(&mut self, key: ModelKey) -> Handle<ModelBuffers> {
+        if let Some(handle) = self.models.find(&key) {
+            return handle;
+        }
+
+        let id = self.allocate_id();
+        self.stream_model(id, &key);
+        self.models.insert(id, key, None)
+    }
+
+    fn stream_model(&mut self, id: AssetId, key: &ModelKey) {
+        match &key.source {
+            ModelSource::File(path) => {
+                self.streamer
+                    .load_model(id, &path.to_string_lossy(), key.max_lod_levels)
+            }
+            ModelSource::Primitive(primitive) => {
+                self.streamer
+                    .load_primitive(id, *primitive, key.max_lod_levels)
+            }
+        }
+    }
+
+    fn allocate_id(&mut self) -> AssetId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+type WatchedFiles = Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>;
+
+// Polls the modification times of the watched files on a background thread. Files which do
+// not exist yet are reported once they are created.
+pub struct FileWatcher {
+    files: WatchedFiles,
+    changes: Receiver<PathBuf>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> FileWatcher {
+        let files: WatchedFiles = Arc::new(Mutex::new(HashMap::new()));
+        let (change_sender, change_receiver) = mpsc::channel();
+        let (stop_sender, stop_receiver) = mpsc::channel();
+
+        let watched = Arc::clone(&files);
+        let thread = thread::Builder::new()
+            .name("file-watcher".to_owned())
+            .spawn(move || run_watcher(&watched, interval, &stop_receiver, &change_sender))
+            .expect("Unable to start file watcher thread");
+
+        FileWatcher {
+            files,
+            changes: change_receiver,
+            stop: Some(stop_sender),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn watch(&self, path: &Path) {
+        let mut files = self.files.lock().unwrap();
+        if !files.contains_key(path) {
+            files.insert(path.to_owned(), modified_time(path));
+        }
+    }
+
+    pub fn unwatch(&self, path: &Path) {
+        self.files.lock().unwrap().remove(path);
+    }
+
+    // Files changed since the last call, each path is only reported once
+    pub fn changes(&self) -> Vec<PathBuf> {
+        let mut changes: Vec<PathBuf> = self.changes.try_iter().collect();
+        changes.sort();
+        changes.dedup();
+        changes
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_watcher(
+    files: &Mutex<HashMap<PathBuf, Option<SystemTime>>>,
+    interval: Duration,
+    stop: &Receiver<()>,
+    changes: &Sender<PathBuf>,
+) {
+    // Dropping the sender of `stop` wakes the thread up immediately
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        let mut files = files.lock().unwrap();
+        for (path, last_modified) in files.iter_mut() {
+            let modified = modified_time(path);
+            if modified.is_some() && modified != *last_modified {
+                log::debug!("Detected change of {}", path.display());
+                if changes.send(path.clone()).is_err() {
+                    return;
+                }
+            }
+            *last_modified = modified;
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
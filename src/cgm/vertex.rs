@@ -1,4 +1,4 @@
-use super::{vector::Vec2, Vec3};
+use super::{vector::Vec2, Vec3, Vec4};
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -6,4 +6,7 @@ pub struct Vertex {
     pub position: Vec3,
     pub color: Vec3,
     pub tex_coord: Vec2,
+    pub normal: Vec3,
+    // xyz is the tangent direction, w the bitangent sign (handedness)
+    pub tangent: Vec4,
 }
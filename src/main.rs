@@ -1,22 +1,32 @@
 // #![windows_subsystem = "windows"]
 
 mod app;
+mod assets;
 mod cgm;
 mod logger;
+mod mesh;
+mod streaming;
+mod texture;
 mod tutorial;
 mod vulkan;
 
+use std::env;
+
 use app::App;
 use log::LevelFilter;
 use logger::init_logging;
+use streaming::StreamingProgress;
 use tutorial::TutorialApp;
+use vulkan::{VkDeviceReport, VkSettings};
 use winit::{
     dpi::PhysicalSize,
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
 
+const WINDOW_TITLE: &str = "Vulkan Tutorial - Rust";
+
 #[cfg(debug_assertions)]
 const LOG_LEVEL: LevelFilter = LevelFilter::Debug;
 
@@ -24,19 +34,43 @@ const LOG_LEVEL: LevelFilter = LevelFilter::Debug;
 const LOG_LEVEL: LevelFilter = LevelFilter::Error;
 
 fn main() {
-    init_logging(LOG_LEVEL);
+    let device_info = device_info_format(env::args().skip(1));
+    // The log shares stdout with the report, which must stay parseable as JSON
+    if device_info == Some(ReportFormat::Json) {
+        init_logging(LevelFilter::Off);
+    } else {
+        init_logging(LOG_LEVEL);
+    }
 
     let window_size = PhysicalSize::new(800, 600);
-    let (event_loop, window) = create_window(&window_size);
-    let mut app = TutorialApp::new(&window);
+    // The report only needs a surface, the window is never shown
+    let (event_loop, window) = create_window(&window_size, device_info.is_none());
+    let vk_settings = VkSettings::default()
+        .with_env_overrides()
+        .with_args(env::args().skip(1));
+    if let Some(format) = device_info {
+        let report = VkDeviceReport::new(&window, &vk_settings);
+        match format {
+            ReportFormat::Text => print!("{}", report.to_text()),
+            ReportFormat::Json => print!("{}", report.to_json()),
+        }
+        return;
+    }
+
+    let mut app = TutorialApp::new(&window, &vk_settings);
     let mut exit = false;
+    let mut progress = StreamingProgress::default();
 
     log::info!("Starting event loop");
     event_loop.run(move |event, _, control_flow| match event {
         Event::MainEventsCleared => {
-            // app.update();
             if !exit {
+                app.update();
                 app.draw_frame(&window);
+                if app.loading_progress() != progress {
+                    progress = app.loading_progress();
+                    window.set_title(&window_title(&progress));
+                }
             }
             // window.request_redraw();
         }
@@ -53,6 +87,15 @@ fn main() {
                     app.minimized(&window);
                 }
             }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => app.key_pressed(key),
             _ => *control_flow = ControlFlow::Poll,
         },
         // Event::RedrawRequested(_window_id) => {
@@ -62,12 +105,85 @@ fn main() {
     });
 }
 
-fn create_window(size: &PhysicalSize<u32>) -> (EventLoop<()>, Window) {
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+// `--device-info` prints a report of every device instead of running the application,
+// `--device-info=json` or `--device-info json` prints it as JSON
+fn device_info_format(args: impl IntoIterator<Item = String>) -> Option<ReportFormat> {
+    let mut format = None;
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let value = match arg.split_once('=') {
+            Some(("--device-info", value)) => value.to_owned(),
+            Some(_) => continue,
+            None if arg == "--device-info" => {
+                match args.next_if(|next| next == "text" || next == "json") {
+                    Some(value) => value,
+                    None => "text".to_owned(),
+                }
+            }
+            None => continue,
+        };
+        format = match value.as_str() {
+            "text" => Some(ReportFormat::Text),
+            "json" => Some(ReportFormat::Json),
+            _ => {
+                // Parsed before logging is set up, since the format decides the log level
+                eprintln!("Ignoring unknown device info format {}", value);
+                format
+            }
+        };
+    }
+    format
+}
+
+fn create_window(size: &PhysicalSize<u32>, visible: bool) -> (EventLoop<()>, Window) {
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
-        .with_title("Vulkan Tutorial - Rust")
+        .with_title(WINDOW_TITLE)
         .with_inner_size(*size)
+        .with_visible(visible)
         .build(&event_loop)
         .expect("Unable to create application window");
     (event_loop, window)
 }
+
+fn window_title(progress: &StreamingProgress) -> String {
+    if progress.is_complete() {
+        WINDOW_TITLE.to_owned()
+    } else {
+        format!(
+            "{} (loading {}/{})",
+            WINDOW_TITLE,
+            progress.resident + progress.failed,
+            progress.requested
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(args: &[&str]) -> Option<ReportFormat> {
+        device_info_format(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn device_info_formats() {
+        assert_eq!(format(&[]), None);
+        assert_eq!(format(&["--device", "1"]), None);
+        assert_eq!(format(&["--device-info"]), Some(ReportFormat::Text));
+        assert_eq!(format(&["--device-info=json"]), Some(ReportFormat::Json));
+        assert_eq!(format(&["--device-info", "json"]), Some(ReportFormat::Json));
+        assert_eq!(format(&["--device-info", "text"]), Some(ReportFormat::Text));
+        assert_eq!(
+            format(&["--device-info", "--device", "1"]),
+            Some(ReportFormat::Text)
+        );
+    }
+}
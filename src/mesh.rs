@@ -0,0 +1,220 @@
+mod lod;
+mod primitives;
+mod simplify;
+
+use std::{
+    fs::File,
+    io::{Cursor, Read},
+};
+
+use crate::cgm::{Vec2, Vec3, Vec4, Vertex};
+
+pub use lod::{projected_screen_size, LodSelector};
+pub use primitives::Primitive;
+
+#[derive(Clone)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+// Location of one mesh inside vertex and index buffers shared with other meshes
+#[derive(Clone, Copy, Debug)]
+pub struct MeshRange {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_offset: i32,
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Mesh {
+        Mesh { vertices, indices }
+    }
+
+    pub fn load_obj(path: &str) -> Mesh {
+        log::info!("Loading model {}", path);
+
+        let mut buf = Vec::new();
+        let mut file = File::open(path).expect("Unable to open model file");
+        file.read_to_end(&mut buf)
+            .expect("Unable to read model file");
+        let mut cursor = Cursor::new(buf);
+
+        let (models, _) = tobj::load_obj_buf(
+            &mut cursor,
+            &tobj::LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+            |_| unreachable!(),
+        )
+        .expect("Unable to parse model file");
+
+        let mesh = &models[0].mesh;
+        let positions = mesh.positions.as_slice();
+        let coords = mesh.texcoords.as_slice();
+        let normals = mesh.normals.as_slice();
+        let vertex_count = mesh.positions.len() / 3;
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for i in 0..vertex_count {
+            let x = positions[i * 3];
+            let y = positions[i * 3 + 1];
+            let z = positions[i * 3 + 2];
+            let (u, v) = if coords.is_empty() {
+                (0.0, 0.0)
+            } else {
+                (coords[i * 2], coords[i * 2 + 1])
+            };
+            let normal = if normals.is_empty() {
+                Vec3::new(0.0, 0.0, 0.0)
+            } else {
+                Vec3::new(normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2])
+            };
+
+            let vertex = Vertex {
+                position: Vec3::new(x, y, z),
+                color: Vec3::new(1.0, 1.0, 1.0),
+                tex_coord: Vec2::new(u, v),
+                normal,
+                tangent: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            };
+            vertices.push(vertex);
+        }
+
+        let mut result = Mesh::new(vertices, mesh.indices.clone());
+        if normals.is_empty() {
+            result.compute_normals();
+        }
+        result.compute_tangents();
+        result
+    }
+
+    // Appends all meshes into a single one, indices stay relative to the first vertex of their
+    // mesh so draws have to use the vertex offset of the range
+    pub fn merge(meshes: &[Mesh]) -> (Mesh, Vec<MeshRange>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut ranges = Vec::with_capacity(meshes.len());
+
+        for mesh in meshes {
+            ranges.push(MeshRange {
+                first_index: indices.len() as u32,
+                index_count: mesh.index_count(),
+                vertex_offset: vertices.len() as i32,
+            });
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        (Mesh::new(vertices, indices), ranges)
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.indices.len() as u32
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // Area weighted vertex normals, the cross product length is proportional to the face area
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![[0.0f32; 3]; self.vertices.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let p0 = self.vertices[triangle[0] as usize].position;
+            let p1 = self.vertices[triangle[1] as usize].position;
+            let p2 = self.vertices[triangle[2] as usize].position;
+            let face_normal = (p1 - p0).cross(&(p2 - p0));
+
+            for &index in triangle {
+                let normal = &mut normals[index as usize];
+                normal[0] += face_normal.x();
+                normal[1] += face_normal.y();
+                normal[2] += face_normal.z();
+            }
+        }
+
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals.iter()) {
+            let normal = Vec3::new(normal[0], normal[1], normal[2]);
+            vertex.normal = if normal.length() > 0.0 {
+                normal.unit()
+            } else {
+                Vec3::new(0.0, 0.0, 1.0)
+            };
+        }
+    }
+
+    // Per-vertex tangent frames derived from the UV layout (Lengyel's method)
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![[0.0f32; 3]; self.vertices.len()];
+        let mut bitangents = vec![[0.0f32; 3]; self.vertices.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let v0 = &self.vertices[triangle[0] as usize];
+            let v1 = &self.vertices[triangle[1] as usize];
+            let v2 = &self.vertices[triangle[2] as usize];
+
+            let e1 = v1.position - v0.position;
+            let e2 = v2.position - v0.position;
+            let d1 = v1.tex_coord - v0.tex_coord;
+            let d2 = v2.tex_coord - v0.tex_coord;
+
+            let det = d1.x() * d2.y() - d2.x() * d1.y();
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let r = 1.0 / det;
+            let sdir = (e1 * d2.y() - e2 * d1.y()) * r;
+            let tdir = (e2 * d1.x() - e1 * d2.x()) * r;
+
+            for &index in triangle {
+                let tangent = &mut tangents[index as usize];
+                tangent[0] += sdir.x();
+                tangent[1] += sdir.y();
+                tangent[2] += sdir.z();
+                let bitangent = &mut bitangents[index as usize];
+                bitangent[0] += tdir.x();
+                bitangent[1] += tdir.y();
+                bitangent[2] += tdir.z();
+            }
+        }
+
+        for (index, vertex) in self.vertices.iter_mut().enumerate() {
+            let n = vertex.normal;
+            let t = Vec3::new(tangents[index][0], tangents[index][1], tangents[index][2]);
+            let b = Vec3::new(
+                bitangents[index][0],
+                bitangents[index][1],
+                bitangents[index][2],
+            );
+
+            // Gram-Schmidt orthogonalize against the normal
+            let t = t - n * n.dot(&t);
+            let t = if t.length() > f32::EPSILON {
+                t.unit()
+            } else {
+                any_perpendicular(&n)
+            };
+            let w = if n.cross(&t).dot(&b) < 0.0 { -1.0 } else { 1.0 };
+
+            vertex.tangent = Vec4::new(t.x(), t.y(), t.z(), w);
+        }
+    }
+}
+
+fn any_perpendicular(normal: &Vec3) -> Vec3 {
+    let axis = if normal.x().abs() < 0.9 {
+        Vec3::new(1.0, 0.0, 0.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    };
+    let perpendicular = normal.cross(&axis);
+    if perpendicular.length() > f32::EPSILON {
+        perpendicular.unit()
+    } else {
+        axis
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    mem::size_of,
+};
+
+use crate::cgm::{Vec3, Vertex};
+
+use super::Mesh;
+
+const LOD_CACHE_MAGIC: &[u8; 4] = b"LOD1";
+const MIN_LOD_TRIANGLES: usize = 64;
+
+impl Mesh {
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        if self.vertices.is_empty() {
+            return (Vec3::new(0.0, 0.0, 0.0), 0.0);
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in &self.vertices {
+            let position = [
+                vertex.position.x(),
+                vertex.position.y(),
+                vertex.position.z(),
+            ];
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+
+        let center = Vec3::new(
+            (min[0] + max[0]) / 2.0,
+            (min[1] + max[1]) / 2.0,
+            (min[2] + max[2]) / 2.0,
+        );
+        let radius = self
+            .vertices
+            .iter()
+            .map(|vertex| (vertex.position - center).length())
+            .fold(0.0, f32::max);
+
+        (center, radius)
+    }
+
+    // Every level has roughly half the triangles of the previous one, level 0 is the source mesh
+    pub fn build_lod_chain(&self, max_levels: usize) -> Vec<Mesh> {
+        let mut levels = vec![self.clone()];
+
+        while levels.len() < max_levels {
+            let previous = &levels[levels.len() - 1];
+            let target = previous.triangle_count() / 2;
+            if target < MIN_LOD_TRIANGLES {
+                break;
+            }
+
+            let level = previous.simplify(target);
+            log::info!(
+                "Generated LOD {} with {} triangles",
+                levels.len(),
+                level.triangle_count()
+            );
+            if level.triangle_count() * 10 > previous.triangle_count() * 9 {
+                // Simplification got stuck on locked vertices, further levels would not help
+                break;
+            }
+            levels.push(level);
+        }
+
+        levels
+    }
+
+    // The chain is cached next to the model as `<path>.lod` and rebuilt when the model changes
+    pub fn load_obj_lod_chain(path: &str, max_levels: usize) -> Vec<Mesh> {
+        let cache_path = format!("{}.lod", path);
+        if is_cache_fresh(path, &cache_path) {
+            match read_lod_cache(&cache_path, max_levels) {
+                Ok(levels) => {
+                    log::info!("Loaded {} LOD levels from {}", levels.len(), cache_path);
+                    return levels;
+                }
+                Err(error) => log::warn!("Ignoring LOD cache {}: {}", cache_path, error),
+            }
+        }
+
+        let levels = Mesh::load_obj(path).build_lod_chain(max_levels);
+        if let Err(error) = write_lod_cache(&cache_path, max_levels, &levels) {
+            log::warn!("Unable to write LOD cache {}: {}", cache_path, error);
+        }
+        levels
+    }
+}
+
+// Picks a LOD level from the projected size of an object. Switching to a coarser level only
+// happens once the size drops below the threshold by the hysteresis band (and vice versa) so
+// objects sitting right at a threshold do not flicker between two levels.
+pub struct LodSelector {
+    thresholds: Vec<f32>,
+    hysteresis: f32,
+    current: usize,
+}
+
+impl LodSelector {
+    // Level 0 is used down to `full_detail_size` pixels, every further level halves the size
+    pub fn new(level_count: usize, full_detail_size: f32, hysteresis: f32) -> LodSelector {
+        let thresholds = (0..level_count.saturating_sub(1))
+            .map(|level| full_detail_size / (1 << level) as f32)
+            .collect();
+
+        LodSelector {
+            thresholds,
+            hysteresis,
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn select(&mut self, screen_size: f32) -> usize {
+        while self.current > 0
+            && screen_size > self.thresholds[self.current - 1] * (1.0 + self.hysteresis)
+        {
+            self.current -= 1;
+        }
+        while self.current < self.thresholds.len()
+            && screen_size < self.thresholds[self.current] * (1.0 - self.hysteresis)
+        {
+            self.current += 1;
+        }
+        self.current
+    }
+}
+
+// Projected diameter in pixels of a bounding sphere `distance` units in front of a perspective
+// camera with the vertical field of view `fov` (same parameter as `Mat4::perspective`)
+pub fn projected_screen_size(radius: f32, distance: f32, fov: f32, viewport_height: f32) -> f32 {
+    if distance <= radius {
+        return f32::MAX;
+    }
+    radius / (distance * (fov / 2.0).tan()) * viewport_height
+}
+
+fn is_cache_fresh(path: &str, cache_path: &str) -> bool {
+    let modified = |path: &str| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(path), modified(cache_path)) {
+        (Ok(source), Ok(cache)) => cache >= source,
+        _ => false,
+    }
+}
+
+fn read_lod_cache(cache_path: &str, max_levels: usize) -> io::Result<Vec<Mesh>> {
+    let mut data = Vec::new();
+    File::open(cache_path)?.read_to_end(&mut data)?;
+    let mut reader = CacheReader {
+        data: &data,
+        offset: 0,
+    };
+
+    if reader.take(4)? != LOD_CACHE_MAGIC {
+        return Err(invalid_cache("unknown file format"));
+    }
+    if reader.read_u32()? as usize != size_of::<Vertex>() {
+        return Err(invalid_cache("vertex layout changed"));
+    }
+    if reader.read_u32()? as usize != max_levels {
+        return Err(invalid_cache("different LOD level count requested"));
+    }
+
+    let level_count = reader.read_u32()? as usize;
+    let mut levels = Vec::with_capacity(level_count);
+    for _ in 0..level_count {
+        let vertex_count = reader.read_u32()? as usize;
+        let index_count = reader.read_u32()? as usize;
+
+        let vertex_bytes = reader.take(vertex_count * size_of::<Vertex>())?;
+        let mut vertices = Vec::<Vertex>::with_capacity(vertex_count);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                vertex_bytes.as_ptr(),
+                vertices.as_mut_ptr() as *mut u8,
+                vertex_bytes.len(),
+            );
+            vertices.set_len(vertex_count);
+        }
+
+        let indices = (0..index_count)
+            .map(|_| reader.read_u32())
+            .collect::<io::Result<Vec<_>>>()?;
+        if indices.iter().any(|&index| index as usize >= vertex_count) {
+            return Err(invalid_cache("index out of range"));
+        }
+
+        levels.push(Mesh::new(vertices, indices));
+    }
+
+    if levels.is_empty() {
+        return Err(invalid_cache("no LOD levels"));
+    }
+    Ok(levels)
+}
+
+fn write_lod_cache(cache_path: &str, max_levels: usize, levels: &[Mesh]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(cache_path)?);
+    file.write_all(LOD_CACHE_MAGIC)?;
+    file.write_all(&(size_of::<Vertex>() as u32).to_le_bytes())?;
+    file.write_all(&(max_levels as u32).to_le_bytes())?;
+    file.write_all(&(levels.len() as u32).to_le_bytes())?;
+
+    for level in levels {
+        file.write_all(&(level.vertices.len() as u32).to_le_bytes())?;
+        file.write_all(&(level.indices.len() as u32).to_le_bytes())?;
+
+        let vertex_bytes = unsafe {
+            std::slice::from_raw_parts(
+                level.vertices.as_ptr() as *const u8,
+                level.vertices.len() * size_of::<Vertex>(),
+            )
+        };
+        file.write_all(vertex_bytes)?;
+        for index in &level.indices {
+            file.write_all(&index.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+struct CacheReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> CacheReader<'a> {
+    fn take(&mut self, size: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + size)
+            .ok_or_else(|| invalid_cache("truncated file"))?;
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+fn invalid_cache(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn lod_chain_halves_triangles() {
+        let sphere = Mesh::icosphere(1.0, 4);
+        let levels = sphere.build_lod_chain(4);
+
+        assert_eq!(levels.len(), 4);
+        assert_eq!(levels[0].triangle_count(), sphere.triangle_count());
+        for pair in levels.windows(2) {
+            assert!(pair[1].triangle_count() * 10 <= pair[0].triangle_count() * 9);
+            assert!(pair[1].triangle_count() * 3 >= pair[0].triangle_count());
+        }
+    }
+
+    #[test]
+    fn small_meshes_have_one_level() {
+        let levels = Mesh::cube(1.0).build_lod_chain(4);
+        assert_eq!(levels.len(), 1);
+    }
+
+    #[test]
+    fn selector_applies_hysteresis() {
+        // Thresholds at 600, 300 and 150 pixels
+        let mut selector = LodSelector::new(4, 600.0, 0.1);
+        assert_eq!(selector.select(1000.0), 0);
+        assert_eq!(selector.select(560.0), 0);
+        assert_eq!(selector.select(530.0), 1);
+        assert_eq!(selector.select(640.0), 1);
+        assert_eq!(selector.select(670.0), 0);
+        // Several levels are skipped at once
+        assert_eq!(selector.select(10.0), 3);
+        assert_eq!(selector.select(0.0), 3);
+        assert_eq!(selector.select(f32::MAX), 0);
+    }
+
+    #[test]
+    fn selector_with_single_level() {
+        let mut selector = LodSelector::new(1, 600.0, 0.1);
+        assert_eq!(selector.select(0.0), 0);
+        assert_eq!(selector.select(1000.0), 0);
+    }
+
+    #[test]
+    fn screen_size_of_sphere() {
+        // At a 90 degree field of view the view spans 2 units at a distance of 1
+        let size = projected_screen_size(1.0, 10.0, FRAC_PI_2, 1000.0);
+        assert!((size - 100.0).abs() < 1e-3);
+        assert_eq!(projected_screen_size(1.0, 0.5, FRAC_PI_2, 1000.0), f32::MAX);
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let path = std::env::temp_dir().join(format!("lod-cache-test-{}.lod", std::process::id()));
+        let cache_path = path.to_string_lossy();
+        let levels = Mesh::icosphere(1.0, 3).build_lod_chain(3);
+        write_lod_cache(&cache_path, 3, &levels).unwrap();
+
+        let read = read_lod_cache(&cache_path, 3).unwrap();
+        assert_eq!(read.len(), levels.len());
+        for (read, level) in read.iter().zip(&levels) {
+            assert_eq!(read.indices, level.indices);
+            assert_eq!(read.vertices.len(), level.vertices.len());
+        }
+        // Caches built for a different level count are rebuilt
+        assert!(read_lod_cache(&cache_path, 4).is_err());
+
+        fs::write(&path, b"LOD1").unwrap();
+        assert!(read_lod_cache(&cache_path, 3).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,596 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::cgm::{Vec2, Vec3, Vec4, Vertex};
+
+use super::Mesh;
+
+// Generated meshes which can be shown in place of a model, sized to fit the tutorial scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    Cube,
+    Plane,
+    UvSphere,
+    Icosphere,
+    Cylinder,
+    Cone,
+    Torus,
+}
+
+impl Primitive {
+    pub const ALL: [Primitive; 7] = [
+        Primitive::Cube,
+        Primitive::Plane,
+        Primitive::UvSphere,
+        Primitive::Icosphere,
+        Primitive::Cylinder,
+        Primitive::Cone,
+        Primitive::Torus,
+    ];
+
+    pub fn mesh(self) -> Mesh {
+        match self {
+            Primitive::Cube => Mesh::cube(0.8),
+            Primitive::Plane => Mesh::plane(1.6, 1.6, 16, 16),
+            Primitive::UvSphere => Mesh::uv_sphere(0.6, 48, 24),
+            Primitive::Icosphere => Mesh::icosphere(0.6, 3),
+            Primitive::Cylinder => Mesh::cylinder(0.4, 1.0, 48),
+            Primitive::Cone => Mesh::cone(0.5, 1.0, 48),
+            Primitive::Torus => Mesh::torus(0.5, 0.2, 48, 24),
+        }
+    }
+}
+
+// All primitives are centered at the origin, use +Z as up (same as the tutorial scene)
+// and have counter-clockwise front faces.
+impl Mesh {
+    pub fn cube(size: f32) -> Mesh {
+        let half = size / 2.0;
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+
+        // (normal, u axis, v axis), u x v = normal
+        let faces = [
+            (x, y, z),
+            (-x, -y, z),
+            (y, -x, z),
+            (-y, x, z),
+            (z, x, y),
+            (-z, x, -y),
+        ];
+
+        let mut mesh = Mesh::new(Vec::with_capacity(24), Vec::with_capacity(36));
+        for (normal, u_axis, v_axis) in faces.iter() {
+            push_grid(
+                &mut mesh,
+                &(normal * half),
+                &(u_axis * size),
+                &(v_axis * size),
+                normal,
+                1,
+                1,
+            );
+        }
+        mesh
+    }
+
+    pub fn plane(width: f32, height: f32, x_segments: u32, y_segments: u32) -> Mesh {
+        let x_segments = x_segments.max(1);
+        let y_segments = y_segments.max(1);
+        let vertex_count = ((x_segments + 1) * (y_segments + 1)) as usize;
+        let index_count = (x_segments * y_segments * 6) as usize;
+
+        let mut mesh = Mesh::new(
+            Vec::with_capacity(vertex_count),
+            Vec::with_capacity(index_count),
+        );
+        push_grid(
+            &mut mesh,
+            &Vec3::new(0.0, 0.0, 0.0),
+            &Vec3::new(width, 0.0, 0.0),
+            &Vec3::new(0.0, height, 0.0),
+            &Vec3::new(0.0, 0.0, 1.0),
+            x_segments,
+            y_segments,
+        );
+        mesh
+    }
+
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+        let segments = segments.max(3);
+        let rings = rings.max(2);
+
+        let mut mesh = Mesh::new(Vec::new(), Vec::new());
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let theta = PI * v;
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let phi = 2.0 * PI * u;
+                let normal = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                let tangent = Vec4::new(-phi.sin(), phi.cos(), 0.0, 1.0);
+                mesh.vertices.push(vertex(
+                    normal * radius,
+                    normal,
+                    tangent,
+                    Vec2::new(u, 1.0 - v),
+                ));
+            }
+        }
+
+        let stride = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * stride + segment;
+                let b = a + stride;
+                // Skip the degenerate triangles touching the poles
+                if ring != rings - 1 {
+                    mesh.indices.extend_from_slice(&[a, b, b + 1]);
+                }
+                if ring != 0 {
+                    mesh.indices.extend_from_slice(&[a, b + 1, a + 1]);
+                }
+            }
+        }
+        mesh
+    }
+
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut positions = [
+            Vec3::new(-1.0, t, 0.0),
+            Vec3::new(1.0, t, 0.0),
+            Vec3::new(-1.0, -t, 0.0),
+            Vec3::new(1.0, -t, 0.0),
+            Vec3::new(0.0, -1.0, t),
+            Vec3::new(0.0, 1.0, t),
+            Vec3::new(0.0, -1.0, -t),
+            Vec3::new(0.0, 1.0, -t),
+            Vec3::new(t, 0.0, -1.0),
+            Vec3::new(t, 0.0, 1.0),
+            Vec3::new(-t, 0.0, -1.0),
+            Vec3::new(-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|position| position.unit())
+        .collect::<Vec<_>>();
+
+        #[rustfmt::skip]
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| -> u32 {
+                let key = (a.min(b), a.max(b));
+                *midpoints.entry(key).or_insert_with(|| {
+                    let position = (positions[a as usize] + positions[b as usize]).unit();
+                    positions.push(position);
+                    positions.len() as u32 - 1
+                })
+            };
+
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let ab = midpoint(a, b);
+                    let bc = midpoint(b, c);
+                    let ca = midpoint(c, a);
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut mesh = Mesh::new(Vec::with_capacity(positions.len()), Vec::new());
+        for normal in &positions {
+            let phi = normal.y().atan2(normal.x());
+            let u = 0.5 + phi / (2.0 * PI);
+            let v = 0.5 + normal.z().clamp(-1.0, 1.0).asin() / PI;
+            let tangent = Vec4::new(-phi.sin(), phi.cos(), 0.0, 1.0);
+            mesh.vertices
+                .push(vertex(normal * radius, *normal, tangent, Vec2::new(u, v)));
+        }
+
+        // Triangles crossing the texture seam get duplicated vertices with u shifted by one
+        let mut seam_duplicates = HashMap::new();
+        for triangle in &triangles {
+            let us = triangle.map(|index| mesh.vertices[index as usize].tex_coord.x());
+            let max_u = us[0].max(us[1]).max(us[2]);
+            let min_u = us[0].min(us[1]).min(us[2]);
+
+            for &index in triangle {
+                let mut index = index;
+                if max_u - min_u > 0.5 && mesh.vertices[index as usize].tex_coord.x() < 0.5 {
+                    index = *seam_duplicates.entry(index).or_insert_with(|| {
+                        let mut duplicate = mesh.vertices[index as usize];
+                        duplicate.tex_coord =
+                            Vec2::new(duplicate.tex_coord.x() + 1.0, duplicate.tex_coord.y());
+                        mesh.vertices.push(duplicate);
+                        mesh.vertices.len() as u32 - 1
+                    });
+                }
+                mesh.indices.push(index);
+            }
+        }
+        mesh
+    }
+
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+        let segments = segments.max(3);
+        let half = height / 2.0;
+
+        let mut mesh = Mesh::new(Vec::new(), Vec::new());
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let phi = 2.0 * PI * u;
+            let normal = Vec3::new(phi.cos(), phi.sin(), 0.0);
+            let tangent = Vec4::new(-phi.sin(), phi.cos(), 0.0, 1.0);
+            let bottom = Vec3::new(radius * phi.cos(), radius * phi.sin(), -half);
+            let top = Vec3::new(radius * phi.cos(), radius * phi.sin(), half);
+            mesh.vertices
+                .push(vertex(bottom, normal, tangent, Vec2::new(u, 0.0)));
+            mesh.vertices
+                .push(vertex(top, normal, tangent, Vec2::new(u, 1.0)));
+        }
+
+        for segment in 0..segments {
+            let bottom = segment * 2;
+            let top = bottom + 1;
+            let next_bottom = bottom + 2;
+            let next_top = bottom + 3;
+            mesh.indices
+                .extend_from_slice(&[bottom, next_bottom, next_top, bottom, next_top, top]);
+        }
+
+        push_disk(&mut mesh, half, radius, segments, true);
+        push_disk(&mut mesh, -half, radius, segments, false);
+        mesh
+    }
+
+    pub fn cone(radius: f32, height: f32, segments: u32) -> Mesh {
+        let segments = segments.max(3);
+        let half = height / 2.0;
+        let slope = (radius * radius + height * height).sqrt();
+
+        let side_normal = |phi: f32| {
+            Vec3::new(
+                height * phi.cos() / slope,
+                height * phi.sin() / slope,
+                radius / slope,
+            )
+        };
+
+        let mut mesh = Mesh::new(Vec::new(), Vec::new());
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let phi = 2.0 * PI * u;
+            let tangent = Vec4::new(-phi.sin(), phi.cos(), 0.0, 1.0);
+            let bottom = Vec3::new(radius * phi.cos(), radius * phi.sin(), -half);
+            mesh.vertices
+                .push(vertex(bottom, side_normal(phi), tangent, Vec2::new(u, 0.0)));
+
+            // One apex vertex per segment so that the apex gets a smooth normal
+            let apex_u = (segment as f32 + 0.5) / segments as f32;
+            let apex_phi = 2.0 * PI * apex_u;
+            let apex_tangent = Vec4::new(-apex_phi.sin(), apex_phi.cos(), 0.0, 1.0);
+            mesh.vertices.push(vertex(
+                Vec3::new(0.0, 0.0, half),
+                side_normal(apex_phi),
+                apex_tangent,
+                Vec2::new(apex_u, 1.0),
+            ));
+        }
+
+        for segment in 0..segments {
+            let bottom = segment * 2;
+            let apex = bottom + 1;
+            let next_bottom = bottom + 2;
+            mesh.indices.extend_from_slice(&[bottom, next_bottom, apex]);
+        }
+
+        push_disk(&mut mesh, -half, radius, segments, false);
+        mesh
+    }
+
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Mesh {
+        let major_segments = major_segments.max(3);
+        let minor_segments = minor_segments.max(3);
+
+        let mut mesh = Mesh::new(Vec::new(), Vec::new());
+        for major in 0..=major_segments {
+            let u = major as f32 / major_segments as f32;
+            let phi = 2.0 * PI * u;
+            let center = Vec3::new(major_radius * phi.cos(), major_radius * phi.sin(), 0.0);
+            let tangent = Vec4::new(-phi.sin(), phi.cos(), 0.0, 1.0);
+
+            for minor in 0..=minor_segments {
+                let v = minor as f32 / minor_segments as f32;
+                let theta = 2.0 * PI * v;
+                let normal = Vec3::new(
+                    theta.cos() * phi.cos(),
+                    theta.cos() * phi.sin(),
+                    theta.sin(),
+                );
+                mesh.vertices.push(vertex(
+                    center + normal * minor_radius,
+                    normal,
+                    tangent,
+                    Vec2::new(u, v),
+                ));
+            }
+        }
+
+        let stride = minor_segments + 1;
+        for major in 0..major_segments {
+            for minor in 0..minor_segments {
+                let a = major * stride + minor;
+                let b = a + stride;
+                let c = b + 1;
+                let d = a + 1;
+                mesh.indices.extend_from_slice(&[a, b, c, a, c, d]);
+            }
+        }
+        mesh
+    }
+
+    // A single triangle covering the whole viewport, positions are already in clip space
+    pub fn fullscreen_triangle() -> Mesh {
+        let normal = Vec3::new(0.0, 0.0, -1.0);
+        let tangent = Vec4::new(1.0, 0.0, 0.0, -1.0);
+        let vertices = vec![
+            vertex(
+                Vec3::new(-1.0, -1.0, 0.0),
+                normal,
+                tangent,
+                Vec2::new(0.0, 0.0),
+            ),
+            vertex(
+                Vec3::new(-1.0, 3.0, 0.0),
+                normal,
+                tangent,
+                Vec2::new(0.0, 2.0),
+            ),
+            vertex(
+                Vec3::new(3.0, -1.0, 0.0),
+                normal,
+                tangent,
+                Vec2::new(2.0, 0.0),
+            ),
+        ];
+        Mesh::new(vertices, vec![0, 1, 2])
+    }
+}
+
+fn vertex(position: Vec3, normal: Vec3, tangent: Vec4, tex_coord: Vec2) -> Vertex {
+    Vertex {
+        position,
+        color: Vec3::new(1.0, 1.0, 1.0),
+        tex_coord,
+        normal,
+        tangent,
+    }
+}
+
+// Subdivided quad centered at `center` spanning `u_axis` x `v_axis`, u x v must point along normal
+fn push_grid(
+    mesh: &mut Mesh,
+    center: &Vec3,
+    u_axis: &Vec3,
+    v_axis: &Vec3,
+    normal: &Vec3,
+    u_segments: u32,
+    v_segments: u32,
+) {
+    let base = mesh.vertices.len() as u32;
+    let u_dir = u_axis.unit();
+    let tangent = Vec4::new(u_dir.x(), u_dir.y(), u_dir.z(), 1.0);
+
+    for j in 0..=v_segments {
+        let v = j as f32 / v_segments as f32;
+        for i in 0..=u_segments {
+            let u = i as f32 / u_segments as f32;
+            let position = center + u_axis * (u - 0.5) + v_axis * (v - 0.5);
+            mesh.vertices
+                .push(vertex(position, *normal, tangent, Vec2::new(u, v)));
+        }
+    }
+
+    let stride = u_segments + 1;
+    for j in 0..v_segments {
+        for i in 0..u_segments {
+            let a = base + j * stride + i;
+            let b = a + 1;
+            let c = a + stride + 1;
+            let d = a + stride;
+            mesh.indices.extend_from_slice(&[a, b, c, a, c, d]);
+        }
+    }
+}
+
+fn push_disk(mesh: &mut Mesh, z: f32, radius: f32, segments: u32, facing_up: bool) {
+    let base = mesh.vertices.len() as u32;
+    let (normal, v_sign) = if facing_up {
+        (Vec3::new(0.0, 0.0, 1.0), 1.0)
+    } else {
+        (Vec3::new(0.0, 0.0, -1.0), -1.0)
+    };
+    let tangent = Vec4::new(1.0, 0.0, 0.0, 1.0);
+
+    mesh.vertices.push(vertex(
+        Vec3::new(0.0, 0.0, z),
+        normal,
+        tangent,
+        Vec2::new(0.5, 0.5),
+    ));
+    for segment in 0..=segments {
+        let phi = 2.0 * PI * segment as f32 / segments as f32;
+        let uv = Vec2::new(0.5 + 0.5 * phi.cos(), 0.5 + 0.5 * v_sign * phi.sin());
+        let position = Vec3::new(radius * phi.cos(), radius * phi.sin(), z);
+        mesh.vertices.push(vertex(position, normal, tangent, uv));
+    }
+
+    for segment in 0..segments {
+        let current = base + 1 + segment;
+        let next = current + 1;
+        if facing_up {
+            mesh.indices.extend_from_slice(&[base, current, next]);
+        } else {
+            mesh.indices.extend_from_slice(&[base, next, current]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn check_mesh(mesh: &Mesh, vertex_count: usize, index_count: usize) {
+        assert_eq!(mesh.vertices.len(), vertex_count);
+        assert_eq!(mesh.indices.len(), index_count);
+        assert!(mesh
+            .indices
+            .iter()
+            .all(|&index| (index as usize) < mesh.vertices.len()));
+
+        for vertex in &mesh.vertices {
+            let normal = vertex.normal;
+            let tangent = vertex.tangent;
+            let tangent_dir = Vec3::new(tangent.x(), tangent.y(), tangent.z());
+            assert!((normal.length() - 1.0).abs() < EPSILON);
+            assert!((tangent_dir.length() - 1.0).abs() < EPSILON);
+            assert!(normal.dot(&tangent_dir).abs() < EPSILON);
+            assert_eq!(tangent.w().abs(), 1.0);
+        }
+
+        // Counter-clockwise triangles face the same way as their vertex normals
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [v0, v1, v2] = [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]);
+            let face_normal = (v1.position - v0.position).cross(&(v2.position - v0.position));
+            if face_normal.length() > EPSILON {
+                assert!(face_normal.dot(&(v0.normal + v1.normal + v2.normal)) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn cube() {
+        let mesh = Mesh::cube(2.0);
+        check_mesh(&mesh, 24, 36);
+        assert!(mesh.vertices.iter().all(|vertex| {
+            let position = vertex.position;
+            let extent = position
+                .x()
+                .abs()
+                .max(position.y().abs())
+                .max(position.z().abs());
+            (extent - 1.0).abs() < EPSILON
+        }));
+    }
+
+    #[test]
+    fn plane() {
+        check_mesh(&Mesh::plane(2.0, 1.0, 4, 3), 5 * 4, 4 * 3 * 6);
+        check_mesh(&Mesh::plane(2.0, 1.0, 0, 0), 4, 6);
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let (segments, rings) = (16, 8);
+        let mesh = Mesh::uv_sphere(2.0, segments, rings);
+        let triangles = segments * (rings - 1) * 2;
+        check_mesh(
+            &mesh,
+            ((segments + 1) * (rings + 1)) as usize,
+            (triangles * 3) as usize,
+        );
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| (vertex.position.length() - 2.0).abs() < EPSILON));
+    }
+
+    #[test]
+    fn icosphere() {
+        let mesh = Mesh::icosphere(1.0, 2);
+        // Vertices on the texture seam are duplicated
+        let positions = 10 * 4usize.pow(2) + 2;
+        assert!(mesh.vertices.len() >= positions);
+        check_mesh(&mesh, mesh.vertices.len(), 20 * 4usize.pow(2) * 3);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| (vertex.position.length() - 1.0).abs() < EPSILON));
+    }
+
+    #[test]
+    fn cylinder() {
+        let segments = 12;
+        let mesh = Mesh::cylinder(1.0, 2.0, segments);
+        check_mesh(
+            &mesh,
+            (2 * (segments + 1) + 2 * (segments + 2)) as usize,
+            (12 * segments) as usize,
+        );
+    }
+
+    #[test]
+    fn cone() {
+        let segments = 12;
+        let mesh = Mesh::cone(1.0, 2.0, segments);
+        check_mesh(
+            &mesh,
+            (2 * (segments + 1) + segments + 2) as usize,
+            (6 * segments) as usize,
+        );
+    }
+
+    #[test]
+    fn torus() {
+        let (major, minor) = (12, 6);
+        let mesh = Mesh::torus(1.0, 0.25, major, minor);
+        check_mesh(
+            &mesh,
+            ((major + 1) * (minor + 1)) as usize,
+            (major * minor * 6) as usize,
+        );
+    }
+
+    #[test]
+    fn fullscreen_triangle() {
+        let mesh = Mesh::fullscreen_triangle();
+        check_mesh(&mesh, 3, 3);
+        // The viewport corners are inside the triangle
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.position.x() >= -1.0 && vertex.position.y() >= -1.0));
+        assert!(mesh
+            .vertices
+            .iter()
+            .map(|vertex| vertex.position.x() + vertex.position.y())
+            .all(|sum| sum <= 2.0));
+    }
+
+    #[test]
+    fn all_primitives() {
+        for primitive in Primitive::ALL {
+            let mesh = primitive.mesh();
+            check_mesh(&mesh, mesh.vertices.len(), mesh.indices.len());
+        }
+    }
+}
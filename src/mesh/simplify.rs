@@ -0,0 +1,408 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    ops,
+};
+
+use crate::cgm::Vec3;
+
+use super::Mesh;
+
+// Symmetric 4x4 error quadric stored as its upper triangle:
+// a2 ab ac ad b2 bc bd c2 cd d2
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+    data: [f64; 10],
+}
+
+impl Quadric {
+    fn from_plane(a: f64, b: f64, c: f64, d: f64, weight: f64) -> Quadric {
+        Quadric {
+            data: [
+                a * a * weight,
+                a * b * weight,
+                a * c * weight,
+                a * d * weight,
+                b * b * weight,
+                b * c * weight,
+                b * d * weight,
+                c * c * weight,
+                c * d * weight,
+                d * d * weight,
+            ],
+        }
+    }
+
+    fn error(&self, position: &Vec3) -> f64 {
+        let q = &self.data;
+        let x = position.x() as f64;
+        let y = position.y() as f64;
+        let z = position.z() as f64;
+
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+impl ops::AddAssign<Quadric> for Quadric {
+    fn add_assign(&mut self, rhs: Quadric) {
+        for (value, other) in self.data.iter_mut().zip(rhs.data.iter()) {
+            *value += other;
+        }
+    }
+}
+
+impl ops::Add<Quadric> for Quadric {
+    type Output = Quadric;
+
+    fn add(mut self, rhs: Quadric) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+// Candidate half-edge collapse moving `from` onto `to`
+struct Collapse {
+    error: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // Reversed so that the BinaryHeap pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.error.total_cmp(&self.error)
+    }
+}
+
+impl Mesh {
+    // Quadric error metric simplification (Garland & Heckbert) using half-edge collapses.
+    // Collapsing onto an existing vertex keeps all vertex attributes valid, vertices on
+    // UV/normal seams and open borders are never moved so the silhouette and texturing
+    // stay intact.
+    pub fn simplify(&self, target_triangle_count: usize) -> Mesh {
+        let positions = self
+            .vertices
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+        let (position_ids, position_count) = weld_positions(&positions);
+
+        let mut triangles = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<_>>();
+        let mut removed = vec![false; triangles.len()];
+        let mut vertex_triangles = vec![Vec::new(); self.vertices.len()];
+        let mut quadrics = vec![Quadric::default(); position_count];
+        let mut edge_uses = HashMap::new();
+        let mut live_triangles = 0;
+
+        for (index, triangle) in triangles.iter().enumerate() {
+            let p0 = positions[triangle[0] as usize];
+            let p1 = positions[triangle[1] as usize];
+            let p2 = positions[triangle[2] as usize];
+            let normal = (p1 - p0).cross(&(p2 - p0));
+            let double_area = normal.length();
+            if double_area <= f32::EPSILON {
+                removed[index] = true;
+                continue;
+            }
+
+            let n = normal / double_area;
+            let quadric = Quadric::from_plane(
+                n.x() as f64,
+                n.y() as f64,
+                n.z() as f64,
+                -n.dot(&p0) as f64,
+                double_area as f64 * 0.5,
+            );
+
+            for corner in 0..3 {
+                let vertex = triangle[corner];
+                vertex_triangles[vertex as usize].push(index);
+                quadrics[position_ids[vertex as usize] as usize] += quadric;
+
+                let a = position_ids[vertex as usize];
+                let b = position_ids[triangle[(corner + 1) % 3] as usize];
+                *edge_uses.entry((a.min(b), a.max(b))).or_insert(0u32) += 1;
+            }
+            live_triangles += 1;
+        }
+
+        // Seam vertices share their position with other vertices, border edges have one triangle
+        let mut locked = vec![false; position_count];
+        let mut wedge_counts = vec![0u32; position_count];
+        for &id in &position_ids {
+            wedge_counts[id as usize] += 1;
+            if wedge_counts[id as usize] > 1 {
+                locked[id as usize] = true;
+            }
+        }
+        for (&(a, b), &uses) in &edge_uses {
+            if uses == 1 {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+            }
+        }
+        let is_locked = |vertex: u32| locked[position_ids[vertex as usize] as usize];
+
+        let mut versions = vec![0u32; self.vertices.len()];
+        let mut collapsed = vec![false; self.vertices.len()];
+        let mut heap = BinaryHeap::new();
+
+        let candidate = |from: u32, to: u32, versions: &[u32], quadrics: &[Quadric]| {
+            let quadric = quadrics[position_ids[from as usize] as usize]
+                + quadrics[position_ids[to as usize] as usize];
+            Collapse {
+                error: quadric.error(&positions[to as usize]),
+                from,
+                to,
+                from_version: versions[from as usize],
+                to_version: versions[to as usize],
+            }
+        };
+
+        for (index, triangle) in triangles.iter().enumerate() {
+            if removed[index] {
+                continue;
+            }
+            for corner in 0..3 {
+                let a = triangle[corner];
+                let b = triangle[(corner + 1) % 3];
+                if !is_locked(a) {
+                    heap.push(candidate(a, b, &versions, &quadrics));
+                }
+                if !is_locked(b) {
+                    heap.push(candidate(b, a, &versions, &quadrics));
+                }
+            }
+        }
+
+        while live_triangles > target_triangle_count {
+            let collapse = match heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let from = collapse.from;
+            let to = collapse.to;
+            if collapsed[from as usize]
+                || collapsed[to as usize]
+                || versions[from as usize] != collapse.from_version
+                || versions[to as usize] != collapse.to_version
+            {
+                continue;
+            }
+
+            let from_triangles = &vertex_triangles[from as usize];
+            let adjacent = from_triangles
+                .iter()
+                .any(|&index| !removed[index] && triangles[index].contains(&to));
+            if !adjacent {
+                continue;
+            }
+
+            // Reject collapses which would flip a remaining triangle
+            let target = positions[to as usize];
+            let flips = from_triangles.iter().any(|&index| {
+                if removed[index] || triangles[index].contains(&to) {
+                    return false;
+                }
+                let corners = triangles[index].map(|vertex| positions[vertex as usize]);
+                let moved = triangles[index].map(|vertex| {
+                    if vertex == from {
+                        target
+                    } else {
+                        positions[vertex as usize]
+                    }
+                });
+                let old_normal = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+                let new_normal = (moved[1] - moved[0]).cross(&(moved[2] - moved[0]));
+                new_normal.length() <= f32::EPSILON || old_normal.dot(&new_normal) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            collapsed[from as usize] = true;
+            let from_triangles = std::mem::take(&mut vertex_triangles[from as usize]);
+            for index in from_triangles {
+                if removed[index] {
+                    continue;
+                }
+                if triangles[index].contains(&to) {
+                    removed[index] = true;
+                    live_triangles -= 1;
+                } else {
+                    for vertex in triangles[index].iter_mut() {
+                        if *vertex == from {
+                            *vertex = to;
+                        }
+                    }
+                    vertex_triangles[to as usize].push(index);
+                }
+            }
+            vertex_triangles[to as usize].retain(|&index| !removed[index]);
+
+            let from_quadric = quadrics[position_ids[from as usize] as usize];
+            quadrics[position_ids[to as usize] as usize] += from_quadric;
+            versions[to as usize] += 1;
+
+            let mut neighbours = vertex_triangles[to as usize]
+                .iter()
+                .flat_map(|&index| triangles[index])
+                .filter(|&vertex| vertex != to)
+                .collect::<Vec<_>>();
+            neighbours.sort_unstable();
+            neighbours.dedup();
+            for neighbour in neighbours {
+                if !is_locked(neighbour) {
+                    heap.push(candidate(neighbour, to, &versions, &quadrics));
+                }
+                if !is_locked(to) {
+                    heap.push(candidate(to, neighbour, &versions, &quadrics));
+                }
+            }
+        }
+
+        // Compact the remaining triangles into a new vertex buffer
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::with_capacity(live_triangles * 3);
+        for (index, triangle) in triangles.iter().enumerate() {
+            if removed[index] {
+                continue;
+            }
+            for &vertex in triangle {
+                if remap[vertex as usize] == u32::MAX {
+                    remap[vertex as usize] = vertices.len() as u32;
+                    vertices.push(self.vertices[vertex as usize]);
+                }
+                indices.push(remap[vertex as usize]);
+            }
+        }
+
+        Mesh::new(vertices, indices)
+    }
+}
+
+fn weld_positions(positions: &[Vec3]) -> (Vec<u32>, usize) {
+    let mut unique = HashMap::new();
+    let ids = positions
+        .iter()
+        .map(|position| {
+            let key = [
+                position.x().to_bits(),
+                position.y().to_bits(),
+                position.z().to_bits(),
+            ];
+            let next = unique.len() as u32;
+            *unique.entry(key).or_insert(next)
+        })
+        .collect();
+    (ids, unique.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains_vertex(mesh: &Mesh, position: &Vec3) -> bool {
+        mesh.vertices
+            .iter()
+            .any(|vertex| (vertex.position - position).length() == 0.0)
+    }
+
+    #[test]
+    fn quadric_error_is_squared_plane_distance() {
+        // z = 1 with twice the weight
+        let quadric = Quadric::from_plane(0.0, 0.0, 1.0, -1.0, 2.0);
+        assert_eq!(quadric.error(&Vec3::new(5.0, -3.0, 1.0)), 0.0);
+        assert_eq!(quadric.error(&Vec3::new(0.0, 0.0, 4.0)), 18.0);
+
+        // Sum of the squared distances to x = 0 and y = 0
+        let corner = Quadric::from_plane(1.0, 0.0, 0.0, 0.0, 1.0)
+            + Quadric::from_plane(0.0, 1.0, 0.0, 0.0, 1.0);
+        assert_eq!(corner.error(&Vec3::new(3.0, 4.0, 7.0)), 25.0);
+    }
+
+    #[test]
+    fn flat_grid_keeps_its_border_and_orientation() {
+        let grid = Mesh::plane(2.0, 2.0, 8, 8);
+        let simplified = grid.simplify(32);
+
+        assert!(simplified.triangle_count() < grid.triangle_count());
+        assert!(simplified.triangle_count() >= 32);
+        assert!(simplified.vertices.iter().all(|vertex| {
+            let position = vertex.position;
+            contains_vertex(&grid, &position)
+        }));
+        // Open borders are locked, so the corners and the outline stay where they are
+        for corner in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            assert!(contains_vertex(
+                &simplified,
+                &Vec3::new(corner.0, corner.1, 0.0)
+            ));
+        }
+        for triangle in simplified.indices.chunks_exact(3) {
+            let [p0, p1, p2] = [0, 1, 2].map(|corner| {
+                assert!((triangle[corner] as usize) < simplified.vertices.len());
+                simplified.vertices[triangle[corner] as usize].position
+            });
+            assert!((p1 - p0).cross(&(p2 - p0)).z() > 0.0);
+        }
+    }
+
+    #[test]
+    fn closed_mesh_reaches_target() {
+        let sphere = Mesh::icosphere(1.0, 3);
+        let target = sphere.triangle_count() / 4;
+        let simplified = sphere.simplify(target);
+
+        // Seam vertices are locked, the rest of the sphere collapses freely
+        assert!(simplified.triangle_count() <= target + target / 10);
+        assert!(simplified
+            .indices
+            .iter()
+            .all(|&index| (index as usize) < simplified.vertices.len()));
+    }
+
+    #[test]
+    fn welds_identical_positions() {
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(-0.0, 0.0, 0.0),
+        ];
+        let (ids, count) = weld_positions(&positions);
+        // Welding compares bits, so -0.0 is a separate position
+        assert_eq!(ids, vec![0, 1, 0, 2]);
+        assert_eq!(count, 3);
+    }
+}
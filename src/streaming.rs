@@ -0,0 +1,365 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use ash::vk;
+
+use crate::{
+    assets::AssetId,
+    cgm::Vec3,
+    mesh::{Mesh, MeshRange, Primitive},
+    vulkan::{
+        MipGeneration, TextureColorSpace, TexturePixels, VkBuffer, VkCommandPool, VkDevice,
+        VkImage, VkPendingUpload, VkQueue, VkTexture,
+    },
+};
+
+const MAX_WORKERS: usize = 4;
+
+// Number of requested assets in each state, failed assets are logged and never become resident
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamingProgress {
+    pub requested: usize,
+    pub decoded: usize,
+    pub resident: usize,
+    pub failed: usize,
+}
+
+// Vertex and index buffers holding all LOD levels of a model
+pub struct ModelBuffers {
+    pub vertex_buffer: VkBuffer,
+    pub index_buffer: VkBuffer,
+    pub lods: Vec<MeshRange>,
+    pub bounding_sphere: (Vec3, f32),
+}
+
+pub enum StreamedAsset {
+    Texture(VkTexture),
+    Model(ModelBuffers),
+}
+
+enum DecodedAsset {
+    Texture(TexturePixels),
+    Model {
+        mesh: Mesh,
+        lods: Vec<MeshRange>,
+        bounding_sphere: (Vec3, f32),
+    },
+}
+
+enum PendingAsset {
+    Texture(VkPendingUpload<VkTexture>),
+    Model {
+        vertex_buffer: VkPendingUpload<VkBuffer>,
+        index_buffer: VkPendingUpload<VkBuffer>,
+        lods: Vec<MeshRange>,
+        bounding_sphere: (Vec3, f32),
+    },
+}
+
+struct Job {
+    id: AssetId,
+    stream: u64,
+    name: String,
+    decode: Box<dyn FnOnce() -> DecodedAsset + Send>,
+}
+
+type JobResult = (AssetId, u64, String, thread::Result<DecodedAsset>);
+
+// Decodes textures and models on a pool of worker threads. Uploads are submitted from the
+// thread calling `poll` (so that the command pools stay on one thread) and handed out once the
+// GPU has finished copying, rendering never waits for them. Buffers are copied on the transfer
+// queue. Textures still go through the graphics queue: their mip levels may be blitted, which
+// the transfer queue cannot do, and copying there would need a queue ownership transfer.
+pub struct AssetStreamer {
+    device: Arc<VkDevice>,
+    command_pool: Arc<VkCommandPool>,
+    transfer_pool: Arc<VkCommandPool>,
+    transfer_queue: Arc<VkQueue>,
+    jobs: Option<Sender<Job>>,
+    results: Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+    uploads: Vec<(AssetId, u64, String, PendingAsset)>,
+    // Latest stream of each asset, results of earlier streams are dropped
+    latest_streams: HashMap<AssetId, u64>,
+    next_stream: u64,
+    progress: StreamingProgress,
+}
+
+impl StreamingProgress {
+    pub fn is_complete(&self) -> bool {
+        self.resident + self.failed == self.requested
+    }
+}
+
+impl DecodedAsset {
+    fn model(lod_chain: Vec<Mesh>) -> DecodedAsset {
+        let bounding_sphere = lod_chain[0].bounding_sphere();
+        let (mesh, lods) = Mesh::merge(&lod_chain);
+        DecodedAsset::Model {
+            mesh,
+            lods,
+            bounding_sphere,
+        }
+    }
+}
+
+impl PendingAsset {
+    fn is_complete(&self) -> bool {
+        match self {
+            PendingAsset::Texture(texture) => texture.is_complete(),
+            PendingAsset::Model {
+                vertex_buffer,
+                index_buffer,
+                ..
+            } => vertex_buffer.is_complete() && index_buffer.is_complete(),
+        }
+    }
+
+    fn finish(self) -> StreamedAsset {
+        match self {
+            PendingAsset::Texture(texture) => StreamedAsset::Texture(texture.wait()),
+            PendingAsset::Model {
+                vertex_buffer,
+                index_buffer,
+                lods,
+                bounding_sphere,
+            } => StreamedAsset::Model(ModelBuffers {
+                vertex_buffer: vertex_buffer.wait(),
+                index_buffer: index_buffer.wait(),
+                lods,
+                bounding_sphere,
+            }),
+        }
+    }
+}
+
+impl AssetStreamer {
+    // `command_pool` has to belong to the graphics queue family
+    pub fn new(device: &Arc<VkDevice>, command_pool: &Arc<VkCommandPool>) -> AssetStreamer {
+        // One core is left to the render loop
+        let worker_count = thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(2)
+            .saturating_sub(1)
+            .clamp(1, MAX_WORKERS);
+        log::info!("Starting {} asset streaming threads", worker_count);
+
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..worker_count)
+            .map(|index| {
+                let jobs = Arc::clone(&job_receiver);
+                let results = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("asset-streaming-{}", index))
+                    .spawn(move || run_worker(&jobs, &results))
+                    .expect("Unable to start asset streaming thread")
+            })
+            .collect();
+
+        let transfer_queue = Arc::clone(&device.queues.transfer);
+        let transfer_pool = Arc::new(VkCommandPool::new(device, transfer_queue.family));
+        transfer_pool.set_name("asset transfer command pool");
+
+        AssetStreamer {
+            device: Arc::clone(device),
+            command_pool: Arc::clone(command_pool),
+            transfer_pool,
+            transfer_queue,
+            jobs: Some(job_sender),
+            results: result_receiver,
+            workers,
+            uploads: Vec::new(),
+            latest_streams: HashMap::new(),
+            next_stream: 0,
+            progress: StreamingProgress::default(),
+        }
+    }
+
+    pub fn progress(&self) -> StreamingProgress {
+        self.progress
+    }
+
+    // Streaming an asset again supersedes its earlier streams
+    pub fn load_texture(
+        &mut self,
+        id: AssetId,
+        path: &str,
+        color_space: TextureColorSpace,
+        mip_generation: MipGeneration,
+    ) {
+        let device = Arc::clone(&self.device);
+        let path_owned = path.to_owned();
+        self.submit(id, path, move || {
+            let pixels = VkImage::decode_texture(&device, &path_owned, color_space, mip_generation);
+            DecodedAsset::Texture(pixels)
+        })
+    }
+
+    pub fn load_model(&mut self, id: AssetId, path: &str, max_lod_levels: usize) {
+        let path_owned = path.to_owned();
+        self.submit(id, path, move || {
+            DecodedAsset::model(Mesh::load_obj_lod_chain(&path_owned, max_lod_levels))
+        })
+    }
+
+    // Generated meshes are not cached, their LOD chains are quick to build
+    pub fn load_primitive(&mut self, id: AssetId, primitive: Primitive, max_lod_levels: usize) {
+        let name = format!("{:?} primitive", primitive);
+        self.submit(id, &name, move || {
+            DecodedAsset::model(primitive.mesh().build_lod_chain(max_lod_levels))
+        })
+    }
+
+    // Starts the uploads of newly decoded assets and returns the assets whose uploads have
+    // finished since the last call
+    pub fn poll(&mut self) -> Vec<(AssetId, StreamedAsset)> {
+        while let Ok((id, stream, name, result)) = self.results.try_recv() {
+            match result {
+                Ok(asset) => {
+                    self.progress.decoded += 1;
+                    let upload = self.upload(&name, asset);
+                    self.uploads.push((id, stream, name, upload));
+                }
+                Err(error) => {
+                    self.progress.failed += 1;
+                    self.finish_stream(id, stream);
+                    log::error!("Unable to load {}: {}", name, panic_message(&*error));
+                }
+            }
+        }
+
+        let (complete, pending): (Vec<_>, Vec<_>) = self
+            .uploads
+            .drain(..)
+            .partition(|(_, _, _, upload)| upload.is_complete());
+        self.uploads = pending;
+
+        let mut assets = Vec::new();
+        for (id, stream, name, upload) in complete {
+            log::info!("Streamed in {}", name);
+            self.progress.resident += 1;
+            let asset = upload.finish();
+            if self.finish_stream(id, stream) {
+                assets.push((id, asset));
+            }
+        }
+        assets
+    }
+
+    // Returns false for streams superseded by a later one
+    fn finish_stream(&mut self, id: AssetId, stream: u64) -> bool {
+        let latest = self.latest_streams.get(&id) == Some(&stream);
+        if latest {
+            self.latest_streams.remove(&id);
+        }
+        latest
+    }
+
+    fn submit(
+        &mut self,
+        id: AssetId,
+        name: &str,
+        decode: impl FnOnce() -> DecodedAsset + Send + 'static,
+    ) {
+        let stream = self.next_stream;
+        self.next_stream += 1;
+        self.latest_streams.insert(id, stream);
+        self.progress.requested += 1;
+
+        log::info!("Queueing {} for streaming", name);
+        let job = Job {
+            id,
+            stream,
+            name: name.to_owned(),
+            decode: Box::new(decode),
+        };
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(job)
+            .expect("Asset streaming threads have stopped");
+    }
+
+    fn upload(&self, name: &str, asset: DecodedAsset) -> PendingAsset {
+        match asset {
+            DecodedAsset::Texture(pixels) => PendingAsset::Texture(VkImage::upload_texture(
+                &self.device,
+                &self.command_pool,
+                &self.device.queues.graphics,
+                name,
+                &pixels,
+            )),
+            DecodedAsset::Model {
+                mesh,
+                lods,
+                bounding_sphere,
+            } => PendingAsset::Model {
+                vertex_buffer: VkBuffer::upload_device_local(
+                    &self.device,
+                    &self.transfer_pool,
+                    &self.transfer_queue,
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    &mesh.vertices,
+                ),
+                index_buffer: VkBuffer::upload_device_local(
+                    &self.device,
+                    &self.transfer_pool,
+                    &self.transfer_queue,
+                    vk::BufferUsageFlags::INDEX_BUFFER,
+                    &mesh.indices,
+                ),
+                lods,
+                bounding_sphere,
+            },
+        }
+    }
+}
+
+impl Drop for AssetStreamer {
+    fn drop(&mut self) {
+        log::debug!("Stopping asset streaming threads");
+        // Closing the channel stops the workers once their current job is done
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+// Loaders report errors by panicking, the panic is caught so that the worker keeps running
+fn run_worker(jobs: &Mutex<Receiver<Job>>, results: &Sender<JobResult>) {
+    loop {
+        let job = jobs.lock().unwrap().recv();
+        let job = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(job.decode));
+        if results
+            .send((job.id, job.stream, job.name, result))
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+fn panic_message(error: &(dyn Any + Send)) -> &str {
+    if let Some(message) = error.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = error.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown error"
+    }
+}
@@ -0,0 +1,453 @@
+mod astc;
+mod bc;
+mod cubemap;
+mod dds;
+mod etc;
+mod exr;
+mod ktx2;
+mod mipmap;
+
+pub use self::cubemap::{cross_faces, equirectangular_to_cube, face_size};
+pub use self::mipmap::{generate_mip_chain, mip_level_count, MipFilter, MipOptions};
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use ash::vk;
+use image::codecs::hdr::HdrDecoder;
+
+use crate::vulkan::{ImageDimensions, TextureColorSpace};
+
+// Texture data as stored in a KTX2 or DDS container, the levels are tightly packed rows of
+// texel blocks ordered from the full resolution image down to the smallest mip level. Every
+// level holds all array layers (or cube faces) one after another, each with all depth slices.
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub format: vk::Format,
+    pub dimensions: ImageDimensions,
+    pub levels: Vec<Vec<u8>>,
+}
+
+// Linear RGBA values of a Radiance HDR or OpenEXR image, `format` is the float format which
+// keeps the precision of the file
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: vk::Format,
+    pub pixels: Vec<f32>,
+}
+
+#[derive(Clone, Copy)]
+pub struct BlockLayout {
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u32,
+}
+
+impl TextureData {
+    pub fn is_container_file(path: &str) -> bool {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        matches!(extension.as_deref(), Some("ktx2") | Some("dds"))
+    }
+
+    // Legacy DDS files do not store whether the data is sRGB encoded, `color_space` is used
+    // for them. KTX2 and DX10 DDS files always specify the exact format.
+    pub fn load(path: &str, color_space: TextureColorSpace) -> io::Result<TextureData> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        if data.starts_with(&ktx2::IDENTIFIER) {
+            ktx2::parse(&data)
+        } else if data.starts_with(dds::MAGIC) {
+            dds::parse(&data, color_space)
+        } else {
+            Err(invalid_data("unknown texture container"))
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        block_layout(self.format)
+            .map(|layout| layout.width > 1)
+            .unwrap_or(false)
+    }
+
+    // Decodes block compressed data into the closest uncompressed format, returns None for
+    // formats which are not compressed
+    pub fn decompress(&self) -> Option<TextureData> {
+        use vk::Format as F;
+
+        let layout = block_layout(self.format)?;
+        let (format, pixel_size): (vk::Format, usize) = match self.format {
+            F::BC1_RGB_UNORM_BLOCK
+            | F::BC1_RGBA_UNORM_BLOCK
+            | F::BC2_UNORM_BLOCK
+            | F::BC3_UNORM_BLOCK
+            | F::BC7_UNORM_BLOCK
+            | F::ETC2_R8G8B8_UNORM_BLOCK
+            | F::ETC2_R8G8B8A1_UNORM_BLOCK
+            | F::ETC2_R8G8B8A8_UNORM_BLOCK => (F::R8G8B8A8_UNORM, 4),
+            F::BC1_RGB_SRGB_BLOCK
+            | F::BC1_RGBA_SRGB_BLOCK
+            | F::BC2_SRGB_BLOCK
+            | F::BC3_SRGB_BLOCK
+            | F::BC7_SRGB_BLOCK
+            | F::ETC2_R8G8B8_SRGB_BLOCK
+            | F::ETC2_R8G8B8A1_SRGB_BLOCK
+            | F::ETC2_R8G8B8A8_SRGB_BLOCK => (F::R8G8B8A8_SRGB, 4),
+            F::BC4_UNORM_BLOCK => (F::R8_UNORM, 1),
+            F::BC4_SNORM_BLOCK => (F::R8_SNORM, 1),
+            F::BC5_UNORM_BLOCK => (F::R8G8_UNORM, 2),
+            F::BC5_SNORM_BLOCK => (F::R8G8_SNORM, 2),
+            F::BC6H_UFLOAT_BLOCK | F::BC6H_SFLOAT_BLOCK => (F::R16G16B16A16_SFLOAT, 8),
+            F::EAC_R11_UNORM_BLOCK => (F::R16_UNORM, 2),
+            F::EAC_R11_SNORM_BLOCK => (F::R16_SNORM, 2),
+            F::EAC_R11G11_UNORM_BLOCK => (F::R16G16_UNORM, 4),
+            F::EAC_R11G11_SNORM_BLOCK => (F::R16G16_SNORM, 4),
+            format if is_astc(format) && is_srgb(format) => (F::R8G8B8A8_SRGB, 4),
+            format if is_astc(format) => (F::R8G8B8A8_UNORM, 4),
+            _ => return None,
+        };
+
+        let source_format = self.format;
+        let srgb = is_srgb(source_format);
+        let decode = |block: &[u8], pixels: &mut [u8]| match source_format {
+            F::BC1_RGB_UNORM_BLOCK | F::BC1_RGB_SRGB_BLOCK => bc::decode_bc1(block, pixels, false),
+            F::BC1_RGBA_UNORM_BLOCK | F::BC1_RGBA_SRGB_BLOCK => bc::decode_bc1(block, pixels, true),
+            F::BC2_UNORM_BLOCK | F::BC2_SRGB_BLOCK => bc::decode_bc2(block, pixels),
+            F::BC3_UNORM_BLOCK | F::BC3_SRGB_BLOCK => bc::decode_bc3(block, pixels),
+            F::BC4_UNORM_BLOCK => bc::decode_bc4(block, pixels, false),
+            F::BC4_SNORM_BLOCK => bc::decode_bc4(block, pixels, true),
+            F::BC5_UNORM_BLOCK => bc::decode_bc5(block, pixels, false),
+            F::BC5_SNORM_BLOCK => bc::decode_bc5(block, pixels, true),
+            F::BC6H_UFLOAT_BLOCK => bc::decode_bc6h(block, pixels, false),
+            F::BC6H_SFLOAT_BLOCK => bc::decode_bc6h(block, pixels, true),
+            F::BC7_UNORM_BLOCK | F::BC7_SRGB_BLOCK => bc::decode_bc7(block, pixels),
+            F::ETC2_R8G8B8_UNORM_BLOCK | F::ETC2_R8G8B8_SRGB_BLOCK => {
+                etc::decode_etc2_rgb(block, pixels, false)
+            }
+            F::ETC2_R8G8B8A1_UNORM_BLOCK | F::ETC2_R8G8B8A1_SRGB_BLOCK => {
+                etc::decode_etc2_rgb(block, pixels, true)
+            }
+            F::ETC2_R8G8B8A8_UNORM_BLOCK | F::ETC2_R8G8B8A8_SRGB_BLOCK => {
+                etc::decode_etc2_rgba(block, pixels)
+            }
+            F::EAC_R11_UNORM_BLOCK => etc::decode_eac_r11(block, pixels, false),
+            F::EAC_R11_SNORM_BLOCK => etc::decode_eac_r11(block, pixels, true),
+            F::EAC_R11G11_UNORM_BLOCK => etc::decode_eac_rg11(block, pixels, false),
+            F::EAC_R11G11_SNORM_BLOCK => etc::decode_eac_rg11(block, pixels, true),
+            _ => astc::decode_block(block, pixels, layout.width, layout.height, srgb),
+        };
+
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let width = (self.width >> level).max(1);
+                let height = (self.height >> level).max(1);
+                data.chunks_exact(level_size(layout, width, height))
+                    .flat_map(|image| {
+                        decode_level(image, width, height, layout, pixel_size, &decode)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Some(TextureData {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            format,
+            dimensions: self.dimensions,
+            levels,
+        })
+    }
+}
+
+impl HdrImage {
+    pub fn is_hdr_file(path: &str) -> bool {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        matches!(extension.as_deref(), Some("hdr") | Some("exr"))
+    }
+
+    // RGBE pixels only have 8 bits of mantissa and fit into half floats, OpenEXR images stay
+    // 32-bit when they are stored that way
+    pub fn load(path: &str) -> io::Result<HdrImage> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        if data.starts_with(&exr::MAGIC) {
+            let image = exr::parse(&data)?;
+            let format = if image.full_precision {
+                vk::Format::R32G32B32A32_SFLOAT
+            } else {
+                vk::Format::R16G16B16A16_SFLOAT
+            };
+            return Ok(HdrImage {
+                width: image.width,
+                height: image.height,
+                format,
+                pixels: image.pixels,
+            });
+        }
+
+        let image_error = |error: image::ImageError| invalid_data(&error.to_string());
+        let decoder = HdrDecoder::new(&data[..]).map_err(image_error)?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()
+            .map_err(image_error)?
+            .iter()
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0])
+            .collect();
+        Ok(HdrImage {
+            width: metadata.width,
+            height: metadata.height,
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            pixels,
+        })
+    }
+
+    pub fn flip_vertically(&mut self) {
+        let row_size = self.width as usize * 4;
+        let rows: Vec<&[f32]> = self.pixels.chunks_exact(row_size).rev().collect();
+        self.pixels = rows.concat();
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.format == vk::Format::R32G32B32A32_SFLOAT {
+            self.pixels
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect()
+        } else {
+            self.pixels
+                .iter()
+                .flat_map(|&value| f32_to_f16(value).to_le_bytes())
+                .collect()
+        }
+    }
+}
+
+pub fn block_layout(format: vk::Format) -> Option<BlockLayout> {
+    use vk::Format as F;
+
+    let block = |width, height, bytes| {
+        Some(BlockLayout {
+            width,
+            height,
+            bytes,
+        })
+    };
+    match format {
+        F::R8_UNORM | F::R8_SNORM | F::R8_SRGB => block(1, 1, 1),
+        F::R8G8_UNORM
+        | F::R8G8_SNORM
+        | F::R8G8_SRGB
+        | F::R16_UNORM
+        | F::R16_SNORM
+        | F::R16_SFLOAT => block(1, 1, 2),
+        F::R8G8B8A8_UNORM
+        | F::R8G8B8A8_SNORM
+        | F::R8G8B8A8_SRGB
+        | F::B8G8R8A8_UNORM
+        | F::B8G8R8A8_SRGB
+        | F::R16G16_UNORM
+        | F::R16G16_SNORM
+        | F::R16G16_SFLOAT
+        | F::R32_SFLOAT => block(1, 1, 4),
+        F::R16G16B16A16_UNORM | F::R16G16B16A16_SFLOAT | F::R32G32_SFLOAT => block(1, 1, 8),
+        F::R32G32B32A32_SFLOAT => block(1, 1, 16),
+        F::BC1_RGB_UNORM_BLOCK
+        | F::BC1_RGB_SRGB_BLOCK
+        | F::BC1_RGBA_UNORM_BLOCK
+        | F::BC1_RGBA_SRGB_BLOCK
+        | F::BC4_UNORM_BLOCK
+        | F::BC4_SNORM_BLOCK
+        | F::ETC2_R8G8B8_UNORM_BLOCK
+        | F::ETC2_R8G8B8_SRGB_BLOCK
+        | F::ETC2_R8G8B8A1_UNORM_BLOCK
+        | F::ETC2_R8G8B8A1_SRGB_BLOCK
+        | F::EAC_R11_UNORM_BLOCK
+        | F::EAC_R11_SNORM_BLOCK => block(4, 4, 8),
+        F::BC2_UNORM_BLOCK
+        | F::BC2_SRGB_BLOCK
+        | F::BC3_UNORM_BLOCK
+        | F::BC3_SRGB_BLOCK
+        | F::BC5_UNORM_BLOCK
+        | F::BC5_SNORM_BLOCK
+        | F::BC6H_UFLOAT_BLOCK
+        | F::BC6H_SFLOAT_BLOCK
+        | F::BC7_UNORM_BLOCK
+        | F::BC7_SRGB_BLOCK
+        | F::ETC2_R8G8B8A8_UNORM_BLOCK
+        | F::ETC2_R8G8B8A8_SRGB_BLOCK
+        | F::EAC_R11G11_UNORM_BLOCK
+        | F::EAC_R11G11_SNORM_BLOCK => block(4, 4, 16),
+        F::ASTC_4X4_UNORM_BLOCK | F::ASTC_4X4_SRGB_BLOCK => block(4, 4, 16),
+        F::ASTC_5X4_UNORM_BLOCK | F::ASTC_5X4_SRGB_BLOCK => block(5, 4, 16),
+        F::ASTC_5X5_UNORM_BLOCK | F::ASTC_5X5_SRGB_BLOCK => block(5, 5, 16),
+        F::ASTC_6X5_UNORM_BLOCK | F::ASTC_6X5_SRGB_BLOCK => block(6, 5, 16),
+        F::ASTC_6X6_UNORM_BLOCK | F::ASTC_6X6_SRGB_BLOCK => block(6, 6, 16),
+        F::ASTC_8X5_UNORM_BLOCK | F::ASTC_8X5_SRGB_BLOCK => block(8, 5, 16),
+        F::ASTC_8X6_UNORM_BLOCK | F::ASTC_8X6_SRGB_BLOCK => block(8, 6, 16),
+        F::ASTC_8X8_UNORM_BLOCK | F::ASTC_8X8_SRGB_BLOCK => block(8, 8, 16),
+        F::ASTC_10X5_UNORM_BLOCK | F::ASTC_10X5_SRGB_BLOCK => block(10, 5, 16),
+        F::ASTC_10X6_UNORM_BLOCK | F::ASTC_10X6_SRGB_BLOCK => block(10, 6, 16),
+        F::ASTC_10X8_UNORM_BLOCK | F::ASTC_10X8_SRGB_BLOCK => block(10, 8, 16),
+        F::ASTC_10X10_UNORM_BLOCK | F::ASTC_10X10_SRGB_BLOCK => block(10, 10, 16),
+        F::ASTC_12X10_UNORM_BLOCK | F::ASTC_12X10_SRGB_BLOCK => block(12, 10, 16),
+        F::ASTC_12X12_UNORM_BLOCK | F::ASTC_12X12_SRGB_BLOCK => block(12, 12, 16),
+        _ => None,
+    }
+}
+
+// Saturates instead of overflowing for the sizes of malformed headers, which then fail the
+// length checks of the containers
+pub fn level_size(layout: BlockLayout, width: u32, height: u32) -> usize {
+    let blocks_x = width.div_ceil(layout.width) as usize;
+    let blocks_y = height.div_ceil(layout.height) as usize;
+    blocks_x
+        .saturating_mul(blocks_y)
+        .saturating_mul(layout.bytes as usize)
+}
+
+pub fn is_srgb(format: vk::Format) -> bool {
+    use vk::Format as F;
+
+    matches!(
+        format,
+        F::R8_SRGB
+            | F::R8G8_SRGB
+            | F::R8G8B8A8_SRGB
+            | F::B8G8R8A8_SRGB
+            | F::BC1_RGB_SRGB_BLOCK
+            | F::BC1_RGBA_SRGB_BLOCK
+            | F::BC2_SRGB_BLOCK
+            | F::BC3_SRGB_BLOCK
+            | F::BC7_SRGB_BLOCK
+            | F::ETC2_R8G8B8_SRGB_BLOCK
+            | F::ETC2_R8G8B8A1_SRGB_BLOCK
+            | F::ETC2_R8G8B8A8_SRGB_BLOCK
+    ) || (is_astc(format) && (format.as_raw() - vk::Format::ASTC_4X4_UNORM_BLOCK.as_raw()) % 2 == 1)
+}
+
+fn is_astc(format: vk::Format) -> bool {
+    (vk::Format::ASTC_4X4_UNORM_BLOCK.as_raw()..=vk::Format::ASTC_12X12_SRGB_BLOCK.as_raw())
+        .contains(&format.as_raw())
+}
+
+fn decode_level(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    layout: BlockLayout,
+    pixel_size: usize,
+    decode: &dyn Fn(&[u8], &mut [u8]),
+) -> Vec<u8> {
+    let blocks_x = width.div_ceil(layout.width);
+    let blocks_y = height.div_ceil(layout.height);
+    let block_width = layout.width as usize;
+    let row_size = width as usize * pixel_size;
+
+    let mut output = vec![0u8; row_size * height as usize];
+    let mut block_pixels = vec![0u8; block_width * layout.height as usize * pixel_size];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let offset = ((block_y * blocks_x + block_x) * layout.bytes) as usize;
+            decode(
+                &data[offset..offset + layout.bytes as usize],
+                &mut block_pixels,
+            );
+
+            // Blocks on the right and bottom edge may extend past the image
+            let x = block_x * layout.width;
+            let columns = layout.width.min(width - x) as usize;
+            for row in 0..layout.height.min(height - block_y * layout.height) {
+                let y = (block_y * layout.height + row) as usize;
+                let source = row as usize * block_width * pixel_size;
+                let target = y * row_size + x as usize * pixel_size;
+                output[target..target + columns * pixel_size]
+                    .copy_from_slice(&block_pixels[source..source + columns * pixel_size]);
+            }
+        }
+    }
+    output
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    read_u32(data, offset) as u64 | (read_u32(data, offset + 4) as u64) << 32
+}
+
+// Rounds to nearest, values beyond the half float range become infinity
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exponent == 0xFF {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        sign | 0x7C00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal, the implicit leading bit becomes part of the mantissa
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let rounded = (mantissa + (1 << (shift - 1))) >> shift;
+        sign | rounded as u16
+    } else {
+        let rounded = ((exponent as u32) << 10 | mantissa >> 13) + ((mantissa >> 12) & 1);
+        sign | rounded as u16
+    }
+}
+
+pub fn f16_to_f32(value: u16) -> f32 {
+    let sign = ((value & 0x8000) as u32) << 16;
+    let exponent = ((value >> 10) & 0x1F) as u32;
+    let mantissa = (value & 0x3FF) as u32;
+
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        // Subnormal values are normalized for the wider exponent range
+        0 => {
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3FF;
+            sign | ((127 - 15 + 1 - shift) << 23) | (mantissa << 13)
+        }
+        0x1F => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
@@ -0,0 +1,658 @@
+// Decoder for ASTC blocks with LDR endpoints. Blocks which use HDR endpoint modes or an invalid
+// encoding decode to the error color, as on hardware without HDR support.
+// https://registry.khronos.org/DataFormat/specs/1.3/dataformat.1.3.html#ASTC
+
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+const WEIGHT_RANGES: [u32; 12] = [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32];
+const COLOR_RANGES: [u32; 21] = [
+    2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256,
+];
+
+struct BlockMode {
+    weights_x: u32,
+    weights_y: u32,
+    weight_range: u32,
+    dual_plane: bool,
+}
+
+// RGBA8 output of `block_width` x `block_height` pixels
+pub fn decode_block(
+    block: &[u8],
+    pixels: &mut [u8],
+    block_width: u32,
+    block_height: u32,
+    srgb: bool,
+) {
+    let data = u128::from_le_bytes(block[0..16].try_into().unwrap());
+    if decode(data, pixels, block_width, block_height, srgb).is_none() {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&ERROR_COLOR);
+        }
+    }
+}
+
+fn decode(
+    data: u128,
+    pixels: &mut [u8],
+    block_width: u32,
+    block_height: u32,
+    srgb: bool,
+) -> Option<()> {
+    // Void extent blocks store a single constant color
+    if bits(data, 0, 9) == 0x1FC {
+        if bits(data, 9, 1) == 1 {
+            return None;
+        }
+        let color = [0, 1, 2, 3].map(|channel| (bits(data, 64 + channel * 16, 16) >> 8) as u8);
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+        return Some(());
+    }
+
+    let mode = block_mode(bits(data, 0, 11))?;
+    let partitions = bits(data, 11, 2) + 1;
+    let planes = if mode.dual_plane { 2 } else { 1 };
+    let weight_count = mode.weights_x * mode.weights_y * planes;
+    if mode.weights_x > block_width
+        || mode.weights_y > block_height
+        || weight_count > 64
+        || (mode.dual_plane && partitions == 4)
+    {
+        return None;
+    }
+    let weight_bits = ise_bit_count(weight_count, mode.weight_range);
+    if !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+
+    let mut color_end = 128 - weight_bits;
+    let mut endpoint_modes = [0u32; 4];
+    let color_start = if partitions == 1 {
+        endpoint_modes[0] = bits(data, 13, 4);
+        17
+    } else {
+        let field = bits(data, 23, 6);
+        if field & 3 == 0 {
+            endpoint_modes = [field >> 2; 4];
+        } else {
+            let extra_bits = 3 * partitions - 4;
+            color_end -= extra_bits;
+            let encoded = field | (bits(data, color_end, extra_bits) << 6);
+            let base_class = (encoded & 3) - 1;
+            for partition in 0..partitions {
+                let class = base_class + ((encoded >> (2 + partition)) & 1);
+                let mode = (encoded >> (2 + partitions + 2 * partition)) & 3;
+                endpoint_modes[partition as usize] = (class << 2) | mode;
+            }
+        }
+        29
+    };
+    let plane_component = if mode.dual_plane {
+        color_end -= 2;
+        bits(data, color_end, 2) as usize
+    } else {
+        4
+    };
+
+    let value_count: u32 = endpoint_modes[..partitions as usize]
+        .iter()
+        .map(|mode| 2 * ((mode >> 2) + 1))
+        .sum();
+    if value_count > 18 || color_end < color_start {
+        return None;
+    }
+    let available = color_end - color_start;
+    let color_range = (4..COLOR_RANGES.len())
+        .rev()
+        .map(|index| COLOR_RANGES[index])
+        .find(|&range| ise_bit_count(value_count, range) <= available)?;
+
+    let values: Vec<i32> = decode_ise(data >> color_start, value_count, color_range)
+        .into_iter()
+        .map(|value| unquantize_color(value, color_range))
+        .collect();
+    let mut endpoints = [[[0i32; 4]; 2]; 4];
+    let mut offset = 0;
+    for partition in 0..partitions as usize {
+        let endpoint_mode = endpoint_modes[partition];
+        let count = 2 * ((endpoint_mode >> 2) + 1) as usize;
+        endpoints[partition] = decode_endpoints(endpoint_mode, &values[offset..offset + count])?;
+        offset += count;
+    }
+
+    // Weights are stored from the top of the block downwards with reversed bit order
+    let weights: Vec<i32> = decode_ise(data.reverse_bits(), weight_count, mode.weight_range)
+        .into_iter()
+        .map(|weight| unquantize_weight(weight, mode.weight_range))
+        .collect();
+
+    let partition_index = bits(data, 13, 10);
+    let small_block = block_width * block_height < 31;
+    let scale_x = (1024 + block_width / 2) / (block_width - 1);
+    let scale_y = (1024 + block_height / 2) / (block_height - 1);
+    for y in 0..block_height {
+        for x in 0..block_width {
+            let grid_x = (scale_x * x * (mode.weights_x - 1) + 32) >> 6;
+            let grid_y = (scale_y * y * (mode.weights_y - 1) + 32) >> 6;
+            let plane_weights = [0, 1].map(|plane| {
+                infill_weight(
+                    &weights,
+                    &mode,
+                    grid_x,
+                    grid_y,
+                    plane as usize,
+                    planes as usize,
+                )
+            });
+
+            let partition = if partitions > 1 {
+                select_partition(partition_index, x, y, partitions, small_block)
+            } else {
+                0
+            };
+            let [e0, e1] = endpoints[partition as usize];
+
+            let offset = ((y * block_width + x) * 4) as usize;
+            for channel in 0..4 {
+                let weight = plane_weights[(channel == plane_component) as usize];
+                let (c0, c1) = if srgb && channel < 3 {
+                    ((e0[channel] << 8) | 0x80, (e1[channel] << 8) | 0x80)
+                } else {
+                    (e0[channel] * 257, e1[channel] * 257)
+                };
+                let color = (c0 * (64 - weight) + c1 * weight + 32) >> 6;
+                pixels[offset + channel] = (color >> 8) as u8;
+            }
+        }
+    }
+    Some(())
+}
+
+fn block_mode(mode: u32) -> Option<BlockMode> {
+    let mut dual_plane = (mode >> 10) & 1 == 1;
+    let mut high_precision = (mode >> 9) & 1 == 1;
+    let a = (mode >> 5) & 3;
+
+    let (range, weights_x, weights_y) = if mode & 3 != 0 {
+        let range = ((mode & 3) << 1) | ((mode >> 4) & 1);
+        let b = (mode >> 7) & 3;
+        let (x, y) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 == 0 => (a + 2, (b & 1) + 6),
+            _ => ((b & 1) + 2, a + 2),
+        };
+        (range, x, y)
+    } else {
+        let range = ((mode >> 1) & 6) | ((mode >> 4) & 1);
+        let b = (mode >> 9) & 3;
+        let (x, y) = match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                dual_plane = false;
+                high_precision = false;
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+        (range, x, y)
+    };
+    if range < 2 {
+        return None;
+    }
+
+    Some(BlockMode {
+        weights_x,
+        weights_y,
+        weight_range: WEIGHT_RANGES[(range - 2 + 6 * high_precision as u32) as usize],
+        dual_plane,
+    })
+}
+
+fn bits(data: u128, start: u32, count: u32) -> u32 {
+    if start >= 128 {
+        return 0;
+    }
+    ((data >> start) & ((1u128 << count) - 1)) as u32
+}
+
+// Splits a range into the number of plain bits and whether a trit or quint is used
+fn range_encoding(range: u32) -> (u32, u32) {
+    let (divisor, levels) = if range.is_multiple_of(3) {
+        (3, 3)
+    } else if range.is_multiple_of(5) {
+        (5, 5)
+    } else {
+        (1, 1)
+    };
+    ((range / divisor).trailing_zeros(), levels)
+}
+
+fn ise_bit_count(count: u32, range: u32) -> u32 {
+    let (bits, levels) = range_encoding(range);
+    count * bits
+        + match levels {
+            3 => (8 * count).div_ceil(5),
+            5 => (7 * count).div_ceil(3),
+            _ => 0,
+        }
+}
+
+// Integer sequence decoding of `count` values, the trits and quints are packed in groups of
+// five and three values interleaved with the plain bits
+fn decode_ise(data: u128, count: u32, range: u32) -> Vec<u32> {
+    let (bit_count, levels) = range_encoding(range);
+    let length = ise_bit_count(count, range);
+    let data = if length >= 128 {
+        data
+    } else {
+        data & ((1u128 << length) - 1)
+    };
+
+    let mut values = Vec::with_capacity(count as usize);
+    let mut position = 0;
+    let mut read = |count: u32| {
+        let value = bits(data, position, count);
+        position += count;
+        value
+    };
+    while values.len() < count as usize {
+        match levels {
+            3 => {
+                let mut m = [0u32; 5];
+                let mut t = 0;
+                for (index, shift, size) in [(0, 0, 2), (1, 2, 2), (2, 4, 1), (3, 5, 2), (4, 7, 1)]
+                {
+                    m[index] = read(bit_count);
+                    t |= read(size) << shift;
+                }
+                for (trit, low) in decode_trits(t).iter().zip(m) {
+                    values.push((trit << bit_count) | low);
+                }
+            }
+            5 => {
+                let mut m = [0u32; 3];
+                let mut q = 0;
+                for (index, shift, size) in [(0, 0, 3), (1, 3, 2), (2, 5, 2)] {
+                    m[index] = read(bit_count);
+                    q |= read(size) << shift;
+                }
+                for (quint, low) in decode_quints(q).iter().zip(m) {
+                    values.push((quint << bit_count) | low);
+                }
+            }
+            _ => values.push(read(bit_count)),
+        }
+    }
+    values.truncate(count as usize);
+    values
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |index: u32| (t >> index) & 1;
+    let (c, t4, t3);
+    if (t >> 2) & 7 == 7 {
+        c = (((t >> 5) & 7) << 2) | (t & 3);
+        t4 = 2;
+        t3 = 2;
+    } else {
+        c = t & 0x1F;
+        if (t >> 5) & 3 == 3 {
+            t4 = 2;
+            t3 = bit(7);
+        } else {
+            t4 = bit(7);
+            t3 = (t >> 5) & 3;
+        }
+    }
+
+    let (t0, t1, t2);
+    if c & 3 == 3 {
+        t2 = 2;
+        t1 = (c >> 4) & 1;
+        t0 = (((c >> 3) & 1) << 1) | (((c >> 2) & 1) & !((c >> 3) & 1) & 1);
+    } else if (c >> 2) & 3 == 3 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 3;
+    } else {
+        t2 = (c >> 4) & 1;
+        t1 = (c >> 2) & 3;
+        t0 = (c & 2) | ((c & 1) & !((c >> 1) & 1) & 1);
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |index: u32| (q >> index) & 1;
+    let (q0, q1, q2);
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        q2 = (bit(0) << 2) | ((bit(4) & (bit(0) ^ 1)) << 1) | (bit(3) & (bit(0) ^ 1));
+        q1 = 4;
+        q0 = 4;
+    } else {
+        let c;
+        if (q >> 1) & 3 == 3 {
+            q2 = 4;
+            c = (((q >> 3) & 3) << 3) | ((!(q >> 5) & 3) << 1) | bit(0);
+        } else {
+            q2 = (q >> 5) & 3;
+            c = q & 0x1F;
+        }
+        if c & 7 == 5 {
+            q1 = 4;
+            q0 = (c >> 3) & 3;
+        } else {
+            q1 = (c >> 3) & 3;
+            q0 = c & 7;
+        }
+    }
+    [q0, q1, q2]
+}
+
+fn unquantize_color(value: u32, range: u32) -> i32 {
+    let (bit_count, levels) = range_encoding(range);
+    if levels == 1 {
+        return replicate(value, bit_count, 8) as i32;
+    }
+
+    let low = value & ((1 << bit_count) - 1);
+    let digit = value >> bit_count;
+    let bit = |index: u32| (low >> index) & 1;
+    let (b, c, d, e, f) = (bit(1), bit(2), bit(3), bit(4), bit(5));
+    let (scale, offset) = match (levels, bit_count) {
+        (3, 1) => (204, 0),
+        (3, 2) => (93, b * 0x116),
+        (3, 3) => (44, c * 0x10A + b * 0x85),
+        (3, 4) => (22, d * 0x104 + c * 0x82 + b * 0x41),
+        (3, 5) => (11, e * 0x102 + d * 0x81 + c * 0x40 + b * 0x20),
+        (3, _) => (5, f * 0x101 + e * 0x80 + d * 0x40 + c * 0x20 + b * 0x10),
+        (_, 1) => (113, 0),
+        (_, 2) => (54, b * 0x10C),
+        (_, 3) => (26, c * 0x105 + b * 0x82),
+        (_, 4) => (13, d * 0x102 + c * 0x81 + b * 0x40),
+        (_, _) => (6, e * 0x101 + d * 0x80 + c * 0x40 + b * 0x20),
+    };
+    let mask = if bit(0) == 1 { 0x1FF } else { 0 };
+    let t = (digit * scale + offset) ^ mask;
+    ((mask & 0x80) | (t >> 2)) as i32
+}
+
+fn unquantize_weight(value: u32, range: u32) -> i32 {
+    let (bit_count, levels) = range_encoding(range);
+    let weight = match (levels, bit_count) {
+        (1, _) => replicate(value, bit_count, 6),
+        (3, 0) => return [0, 32, 64][value as usize],
+        (5, 0) => return [0, 16, 32, 48, 64][value as usize],
+        _ => {
+            let low = value & ((1 << bit_count) - 1);
+            let digit = value >> bit_count;
+            let (b, c) = ((low >> 1) & 1, (low >> 2) & 1);
+            let (scale, offset) = match (levels, bit_count) {
+                (3, 1) => (50, 0),
+                (3, 2) => (23, b * 0x45),
+                (3, _) => (11, c * 0x42 + b * 0x21),
+                (_, 1) => (28, 0),
+                (_, _) => (13, b * 0x42),
+            };
+            let mask = if low & 1 == 1 { 0x7F } else { 0 };
+            let t = (digit * scale + offset) ^ mask;
+            return adjust_weight((mask & 0x20) | (t >> 2));
+        }
+    };
+    adjust_weight(weight)
+}
+
+fn adjust_weight(weight: u32) -> i32 {
+    if weight > 32 {
+        weight as i32 + 1
+    } else {
+        weight as i32
+    }
+}
+
+fn replicate(value: u32, from: u32, to: u32) -> u32 {
+    let mut result = 0;
+    let mut remaining = to as i32;
+    while remaining > 0 {
+        result |= if remaining >= from as i32 {
+            value << (remaining - from as i32)
+        } else {
+            value >> (from as i32 - remaining)
+        };
+        remaining -= from as i32;
+    }
+    result
+}
+
+fn infill_weight(
+    weights: &[i32],
+    mode: &BlockMode,
+    grid_x: u32,
+    grid_y: u32,
+    plane: usize,
+    planes: usize,
+) -> i32 {
+    let (x, fraction_x) = ((grid_x >> 4) as usize, (grid_x & 15) as i32);
+    let (y, fraction_y) = ((grid_y >> 4) as usize, (grid_y & 15) as i32);
+    let width = mode.weights_x as usize;
+    let weight = |x: usize, y: usize| {
+        weights
+            .get((y * width + x) * planes + plane)
+            .copied()
+            .unwrap_or(0)
+    };
+
+    let w11 = (fraction_x * fraction_y + 8) >> 4;
+    let w10 = fraction_y - w11;
+    let w01 = fraction_x - w11;
+    let w00 = 16 - fraction_x - fraction_y + w11;
+    (w00 * weight(x, y)
+        + w01 * weight(x + 1, y)
+        + w10 * weight(x, y + 1)
+        + w11 * weight(x + 1, y + 1)
+        + 8)
+        >> 4
+}
+
+fn decode_endpoints(mode: u32, v: &[i32]) -> Option<[[i32; 4]; 2]> {
+    let endpoints = match mode {
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (b0, a0) = bit_transfer_signed(v[1], v[0]);
+            let (b2, a2) = bit_transfer_signed(v[3], v[2]);
+            let l1 = a0 + b0;
+            [[a0, a0, a0, a2], [l1, l1, l1, a2 + b2]]
+        }
+        6 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                255,
+            ],
+            [v[0], v[1], v[2], 255],
+        ],
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract(v[1], v[3], v[5], a1),
+                    blue_contract(v[0], v[2], v[4], a0),
+                ]
+            }
+        }
+        9 | 13 => {
+            let (b0, a0) = bit_transfer_signed(v[1], v[0]);
+            let (b2, a2) = bit_transfer_signed(v[3], v[2]);
+            let (b4, a4) = bit_transfer_signed(v[5], v[4]);
+            let (b6, a6) = if mode == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 255)
+            };
+            if b0 + b2 + b4 >= 0 {
+                [[a0, a2, a4, a6], [a0 + b0, a2 + b2, a4 + b4, a6 + b6]]
+            } else {
+                [
+                    blue_contract(a0 + b0, a2 + b2, a4 + b4, a6 + b6),
+                    blue_contract(a0, a2, a4, a6),
+                ]
+            }
+        }
+        10 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ],
+        // HDR endpoint modes
+        _ => return None,
+    };
+    Some(endpoints.map(|endpoint| endpoint.map(|value| value.clamp(0, 255))))
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let mut a = (a >> 1) & 0x3F;
+    if a & 0x20 != 0 {
+        a -= 0x40;
+    }
+    (a, b)
+}
+
+fn blue_contract(r: i32, g: i32, b: i32, a: i32) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> u32 {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partitions - 1) * 1024;
+    let random = hash52(seed);
+
+    let mut seeds = [0u32; 8];
+    for (index, value) in seeds.iter_mut().enumerate() {
+        *value = (random >> (index * 4)) & 0xF;
+        *value *= *value;
+    }
+    let (sh1, sh2) = if seed & 1 == 1 {
+        (
+            if seed & 2 == 2 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 == 2 { 4 } else { 5 },
+        )
+    };
+    for (index, value) in seeds.iter_mut().enumerate() {
+        *value >>= if index % 2 == 0 { sh1 } else { sh2 };
+    }
+
+    let component = |index: usize, shift: u32| {
+        (seeds[index * 2] * x + seeds[index * 2 + 1] * y + (random >> shift)) & 0x3F
+    };
+    let a = component(0, 14);
+    let b = component(1, 10);
+    let c = if partitions < 3 { 0 } else { component(2, 6) };
+    let d = if partitions < 4 { 0 } else { component(3, 2) };
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+fn hash52(seed: u32) -> u32 {
+    let mut p = seed;
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Void extent header with LDR color and no extent coordinates
+    const VOID_EXTENT: u128 = 0xFFFF_FFFF_FFFF_FDFC;
+
+    fn decode(data: u128, block_width: u32, block_height: u32, srgb: bool) -> Vec<u8> {
+        let mut pixels = vec![0; (block_width * block_height * 4) as usize];
+        decode_block(
+            &data.to_le_bytes(),
+            &mut pixels,
+            block_width,
+            block_height,
+            srgb,
+        );
+        pixels
+    }
+
+    #[test]
+    fn void_extent_block() {
+        let color = 0xFF00 << 64 | 0x8000 << 80 | 0xFFFF << 112;
+        for (width, height) in [(4, 4), (6, 5), (12, 12)] {
+            let pixels = decode(VOID_EXTENT | color, width, height, false);
+            assert!(pixels.chunks(4).all(|pixel| pixel == [255, 128, 0, 255]));
+        }
+    }
+
+    #[test]
+    fn hdr_void_extent_block() {
+        let pixels = decode(VOID_EXTENT | 1 << 9, 4, 4, false);
+        assert!(pixels.chunks(4).all(|pixel| pixel == ERROR_COLOR));
+    }
+
+    #[test]
+    fn reserved_block_mode() {
+        let pixels = decode(0, 4, 4, true);
+        assert!(pixels.chunks(4).all(|pixel| pixel == ERROR_COLOR));
+    }
+
+    #[test]
+    fn weight_grid_larger_than_block() {
+        // A 12x2 weight grid does not fit into a 4x4 block
+        let mode = block_mode(0b100).unwrap();
+        assert_eq!((mode.weights_x, mode.weights_y), (12, 2));
+        let pixels = decode(0b100, 4, 4, false);
+        assert!(pixels.chunks(4).all(|pixel| pixel == ERROR_COLOR));
+    }
+}
@@ -0,0 +1,1061 @@
+// Block decoders for the BC1-7 formats, every function decodes one 4x4 block into row major
+// pixels. https://learn.microsoft.com/en-us/windows/win32/direct3d11/texture-block-compression-in-direct3d-11
+
+const WEIGHTS_2: [i32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [i32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// RGBA8 output, `punch_through` enables the transparent black color of BC1 blocks with c0 <= c1
+pub fn decode_bc1(block: &[u8], pixels: &mut [u8], punch_through: bool) {
+    decode_color(block, pixels, punch_through, false);
+}
+
+pub fn decode_bc2(block: &[u8], pixels: &mut [u8]) {
+    decode_color(&block[8..16], pixels, false, true);
+
+    let alpha = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    for (index, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        pixel[3] = ((alpha >> (index * 4)) & 0xF) as u8 * 17;
+    }
+}
+
+pub fn decode_bc3(block: &[u8], pixels: &mut [u8]) {
+    decode_color(&block[8..16], pixels, false, true);
+    decode_channel(&block[0..8], pixels, 4, 3, false);
+}
+
+// R8 output, signed data is written as two's complement bytes for the SNORM formats
+pub fn decode_bc4(block: &[u8], pixels: &mut [u8], signed: bool) {
+    decode_channel(block, pixels, 1, 0, signed);
+}
+
+// RG8 output
+pub fn decode_bc5(block: &[u8], pixels: &mut [u8], signed: bool) {
+    decode_channel(&block[0..8], pixels, 2, 0, signed);
+    decode_channel(&block[8..16], pixels, 2, 1, signed);
+}
+
+fn decode_color(block: &[u8], pixels: &mut [u8], punch_through: bool, four_colors: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let e0 = rgb565(c0);
+    let e1 = rgb565(c1);
+
+    let mut palette = [[0, 0, 0, 255]; 4];
+    for channel in 0..3 {
+        let (a, b) = (e0[channel] as u32, e1[channel] as u32);
+        palette[0][channel] = a as u8;
+        palette[1][channel] = b as u8;
+        if c0 > c1 || four_colors {
+            palette[2][channel] = ((2 * a + b + 1) / 3) as u8;
+            palette[3][channel] = ((a + 2 * b + 1) / 3) as u8;
+        } else {
+            palette[2][channel] = (a + b).div_ceil(2) as u8;
+        }
+    }
+    if c0 <= c1 && !four_colors && punch_through {
+        palette[3][3] = 0;
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (index, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        let color = (indices >> (index * 2)) & 3;
+        pixel.copy_from_slice(&palette[color as usize]);
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 0x1F) as u8;
+    let g = ((color >> 5) & 0x3F) as u8;
+    let b = (color & 0x1F) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+// Single channel block shared by BC3 alpha, BC4 and BC5
+fn decode_channel(block: &[u8], pixels: &mut [u8], stride: usize, offset: usize, signed: bool) {
+    let (e0, e1) = if signed {
+        let e0 = (block[0] as i8).max(-127) as i32;
+        let e1 = (block[1] as i8).max(-127) as i32;
+        (e0, e1)
+    } else {
+        (block[0] as i32, block[1] as i32)
+    };
+    let (min, max) = if signed { (-127, 127) } else { (0, 255) };
+
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 0];
+    if e0 > e1 {
+        for step in 1..7 {
+            palette[step + 1] = ((7 - step as i32) * e0 + step as i32 * e1 + 3) / 7;
+        }
+    } else {
+        for step in 1..5 {
+            palette[step + 1] = ((5 - step as i32) * e0 + step as i32 * e1 + 2) / 5;
+        }
+        palette[6] = min;
+        palette[7] = max;
+    }
+
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    for index in 0..16 {
+        let value = palette[((indices >> (index * 3)) & 7) as usize];
+        pixels[index * stride + offset] = value as i8 as u8;
+    }
+}
+
+struct BitReader {
+    data: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> BitReader {
+        BitReader {
+            data: u128::from_le_bytes(block[0..16].try_into().unwrap()),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let value = (self.data >> self.position) as u32 & ((1u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+// RGBA8 output
+pub fn decode_bc7(block: &[u8], pixels: &mut [u8]) {
+    let mode_index = match (0..8).find(|bit| block[0] & (1 << bit) != 0) {
+        Some(mode_index) => mode_index,
+        None => {
+            // Reserved mode, decodes to transparent black
+            pixels.fill(0);
+            return;
+        }
+    };
+    let mode = &BC7_MODES[mode_index];
+
+    let mut bits = BitReader::new(block);
+    bits.read(mode_index as u32 + 1);
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[3] = bits.read(mode.alpha_bits);
+        }
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for pbit in pbits.iter_mut().take(endpoint_count) {
+                *pbit = bits.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let pbit = bits.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+        for (endpoint, pbit) in endpoints.iter_mut().zip(pbits.iter()) {
+            for value in endpoint.iter_mut() {
+                *value = (*value << 1) | pbit;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut().take(3) {
+            *value = expand_bits(*value, color_bits);
+        }
+        endpoint[3] = if alpha_bits > 0 {
+            expand_bits(endpoint[3], alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let subset_of = |pixel: usize| match mode.subsets {
+        2 => ((BC7_PARTITIONS_2[partition] >> pixel) & 1) as usize,
+        3 => BC7_PARTITIONS_3[partition][pixel] as usize,
+        _ => 0,
+    };
+    let is_anchor = |pixel: usize| {
+        pixel == 0
+            || match mode.subsets {
+                2 => pixel == BC7_ANCHORS_2[partition] as usize,
+                3 => {
+                    pixel == BC7_ANCHORS_3_SECOND[partition] as usize
+                        || pixel == BC7_ANCHORS_3_THIRD[partition] as usize
+                }
+                _ => false,
+            }
+    };
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let anchor_bit = is_anchor(pixel) as u32;
+        *index = bits.read(mode.index_bits - anchor_bit);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            let anchor_bit = (pixel == 0) as u32;
+            *index = bits.read(mode.secondary_index_bits - anchor_bit);
+        }
+    }
+
+    for (pixel, output) in pixels.chunks_exact_mut(4).enumerate() {
+        let subset = subset_of(pixel);
+        let e0 = &endpoints[subset * 2];
+        let e1 = &endpoints[subset * 2 + 1];
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = weights(mode.index_bits)[indices[pixel] as usize];
+            (weight, weight)
+        } else {
+            let primary = weights(mode.index_bits)[indices[pixel] as usize];
+            let secondary = weights(mode.secondary_index_bits)[secondary_indices[pixel] as usize];
+            if index_selection == 0 {
+                (primary, secondary)
+            } else {
+                (secondary, primary)
+            }
+        };
+
+        let mut color = [0u8; 4];
+        for channel in 0..4 {
+            let weight = if channel == 3 {
+                alpha_weight
+            } else {
+                color_weight
+            };
+            color[channel] = interpolate(e0[channel] as i32, e1[channel] as i32, weight) as u8;
+        }
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+        output.copy_from_slice(&color);
+    }
+}
+
+fn expand_bits(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | (value >> bits)
+}
+
+fn weights(bits: u32) -> &'static [i32] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn interpolate(e0: i32, e1: i32, weight: i32) -> i32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+// BC6H endpoint fields, w/x are the endpoints of the first subset and y/z of the second
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+const D: usize = 12;
+
+struct Bc6hMode {
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    two_subsets: bool,
+    transformed: bool,
+    // Fields in stream order as (field, high bit, low bit), a high bit below the low bit
+    // marks a bit range which is stored in reversed order
+    layout: &'static [(usize, u32, u32)],
+}
+
+const BC6H_MODE_1: &[(usize, u32, u32)] = &[
+    (GY, 4, 4),
+    (BY, 4, 4),
+    (BZ, 4, 4),
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 4, 0),
+    (GZ, 4, 4),
+    (GY, 3, 0),
+    (GX, 4, 0),
+    (BZ, 0, 0),
+    (GZ, 3, 0),
+    (BX, 4, 0),
+    (BZ, 1, 1),
+    (BY, 3, 0),
+    (RY, 4, 0),
+    (BZ, 2, 2),
+    (RZ, 4, 0),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_2: &[(usize, u32, u32)] = &[
+    (GY, 5, 5),
+    (GZ, 4, 4),
+    (GZ, 5, 5),
+    (RW, 6, 0),
+    (BZ, 0, 0),
+    (BZ, 1, 1),
+    (BY, 4, 4),
+    (GW, 6, 0),
+    (BY, 5, 5),
+    (BZ, 2, 2),
+    (GY, 4, 4),
+    (BW, 6, 0),
+    (BZ, 3, 3),
+    (BZ, 5, 5),
+    (BZ, 4, 4),
+    (RX, 5, 0),
+    (GY, 3, 0),
+    (GX, 5, 0),
+    (GZ, 3, 0),
+    (BX, 5, 0),
+    (BY, 3, 0),
+    (RY, 5, 0),
+    (RZ, 5, 0),
+    (D, 4, 0),
+];
+const BC6H_MODE_3: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 4, 0),
+    (RW, 10, 10),
+    (GY, 3, 0),
+    (GX, 3, 0),
+    (GW, 10, 10),
+    (BZ, 0, 0),
+    (GZ, 3, 0),
+    (BX, 3, 0),
+    (BW, 10, 10),
+    (BZ, 1, 1),
+    (BY, 3, 0),
+    (RY, 4, 0),
+    (BZ, 2, 2),
+    (RZ, 4, 0),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_4: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 3, 0),
+    (RW, 10, 10),
+    (GZ, 4, 4),
+    (GY, 3, 0),
+    (GX, 4, 0),
+    (GW, 10, 10),
+    (GZ, 3, 0),
+    (BX, 3, 0),
+    (BW, 10, 10),
+    (BZ, 1, 1),
+    (BY, 3, 0),
+    (RY, 3, 0),
+    (BZ, 0, 0),
+    (BZ, 2, 2),
+    (RZ, 3, 0),
+    (GY, 4, 4),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_5: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 3, 0),
+    (RW, 10, 10),
+    (BY, 4, 4),
+    (GY, 3, 0),
+    (GX, 3, 0),
+    (GW, 10, 10),
+    (BZ, 0, 0),
+    (GZ, 3, 0),
+    (BX, 4, 0),
+    (BW, 10, 10),
+    (BY, 3, 0),
+    (RY, 3, 0),
+    (BZ, 1, 1),
+    (BZ, 2, 2),
+    (RZ, 3, 0),
+    (BZ, 4, 4),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_6: &[(usize, u32, u32)] = &[
+    (RW, 8, 0),
+    (BY, 4, 4),
+    (GW, 8, 0),
+    (GY, 4, 4),
+    (BW, 8, 0),
+    (BZ, 4, 4),
+    (RX, 4, 0),
+    (GZ, 4, 4),
+    (GY, 3, 0),
+    (GX, 4, 0),
+    (BZ, 0, 0),
+    (GZ, 3, 0),
+    (BX, 4, 0),
+    (BZ, 1, 1),
+    (BY, 3, 0),
+    (RY, 4, 0),
+    (BZ, 2, 2),
+    (RZ, 4, 0),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_7: &[(usize, u32, u32)] = &[
+    (RW, 7, 0),
+    (GZ, 4, 4),
+    (BY, 4, 4),
+    (GW, 7, 0),
+    (BZ, 2, 2),
+    (GY, 4, 4),
+    (BW, 7, 0),
+    (BZ, 3, 3),
+    (BZ, 4, 4),
+    (RX, 5, 0),
+    (GY, 3, 0),
+    (GX, 4, 0),
+    (BZ, 0, 0),
+    (GZ, 3, 0),
+    (BX, 4, 0),
+    (BZ, 1, 1),
+    (BY, 3, 0),
+    (RY, 5, 0),
+    (RZ, 5, 0),
+    (D, 4, 0),
+];
+const BC6H_MODE_8: &[(usize, u32, u32)] = &[
+    (RW, 7, 0),
+    (BZ, 0, 0),
+    (BY, 4, 4),
+    (GW, 7, 0),
+    (GY, 5, 5),
+    (GY, 4, 4),
+    (BW, 7, 0),
+    (GZ, 5, 5),
+    (BZ, 4, 4),
+    (RX, 4, 0),
+    (GZ, 4, 4),
+    (GY, 3, 0),
+    (GX, 5, 0),
+    (GZ, 3, 0),
+    (BX, 4, 0),
+    (BZ, 1, 1),
+    (BY, 3, 0),
+    (RY, 4, 0),
+    (BZ, 2, 2),
+    (RZ, 4, 0),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_9: &[(usize, u32, u32)] = &[
+    (RW, 7, 0),
+    (BZ, 1, 1),
+    (BY, 4, 4),
+    (GW, 7, 0),
+    (BY, 5, 5),
+    (GY, 4, 4),
+    (BW, 7, 0),
+    (BZ, 5, 5),
+    (BZ, 4, 4),
+    (RX, 4, 0),
+    (GZ, 4, 4),
+    (GY, 3, 0),
+    (GX, 4, 0),
+    (BZ, 0, 0),
+    (GZ, 3, 0),
+    (BX, 5, 0),
+    (BY, 3, 0),
+    (RY, 4, 0),
+    (BZ, 2, 2),
+    (RZ, 4, 0),
+    (BZ, 3, 3),
+    (D, 4, 0),
+];
+const BC6H_MODE_10: &[(usize, u32, u32)] = &[
+    (RW, 5, 0),
+    (GZ, 4, 4),
+    (BZ, 0, 0),
+    (BZ, 1, 1),
+    (BY, 4, 4),
+    (GW, 5, 0),
+    (GY, 5, 5),
+    (BY, 5, 5),
+    (BZ, 2, 2),
+    (GY, 4, 4),
+    (BW, 5, 0),
+    (GZ, 5, 5),
+    (BZ, 3, 3),
+    (BZ, 5, 5),
+    (BZ, 4, 4),
+    (RX, 5, 0),
+    (GY, 3, 0),
+    (GX, 5, 0),
+    (GZ, 3, 0),
+    (BX, 5, 0),
+    (BY, 3, 0),
+    (RY, 5, 0),
+    (RZ, 5, 0),
+    (D, 4, 0),
+];
+const BC6H_MODE_11: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 9, 0),
+    (GX, 9, 0),
+    (BX, 9, 0),
+];
+const BC6H_MODE_12: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 8, 0),
+    (RW, 10, 10),
+    (GX, 8, 0),
+    (GW, 10, 10),
+    (BX, 8, 0),
+    (BW, 10, 10),
+];
+const BC6H_MODE_13: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 7, 0),
+    (RW, 10, 11),
+    (GX, 7, 0),
+    (GW, 10, 11),
+    (BX, 7, 0),
+    (BW, 10, 11),
+];
+const BC6H_MODE_14: &[(usize, u32, u32)] = &[
+    (RW, 9, 0),
+    (GW, 9, 0),
+    (BW, 9, 0),
+    (RX, 3, 0),
+    (RW, 10, 15),
+    (GX, 3, 0),
+    (GW, 10, 15),
+    (BX, 3, 0),
+    (BW, 10, 15),
+];
+
+fn bc6h_mode(mode: u32) -> Option<Bc6hMode> {
+    let two = |endpoint_bits, delta_bits, layout| Bc6hMode {
+        endpoint_bits,
+        delta_bits,
+        two_subsets: true,
+        transformed: true,
+        layout,
+    };
+    let one = |endpoint_bits, delta_bits, layout| Bc6hMode {
+        endpoint_bits,
+        delta_bits,
+        two_subsets: false,
+        transformed: true,
+        layout,
+    };
+
+    let mode = match mode {
+        0b00 => two(10, [5, 5, 5], BC6H_MODE_1),
+        0b01 => two(7, [6, 6, 6], BC6H_MODE_2),
+        0b00010 => two(11, [5, 4, 4], BC6H_MODE_3),
+        0b00110 => two(11, [4, 5, 4], BC6H_MODE_4),
+        0b01010 => two(11, [4, 4, 5], BC6H_MODE_5),
+        0b01110 => two(9, [5, 5, 5], BC6H_MODE_6),
+        0b10010 => two(8, [6, 5, 5], BC6H_MODE_7),
+        0b10110 => two(8, [5, 6, 5], BC6H_MODE_8),
+        0b11010 => two(8, [5, 5, 6], BC6H_MODE_9),
+        0b11110 => Bc6hMode {
+            transformed: false,
+            ..two(6, [6, 6, 6], BC6H_MODE_10)
+        },
+        0b00011 => Bc6hMode {
+            transformed: false,
+            ..one(10, [10, 10, 10], BC6H_MODE_11)
+        },
+        0b00111 => one(11, [9, 9, 9], BC6H_MODE_12),
+        0b01011 => one(12, [8, 8, 8], BC6H_MODE_13),
+        0b01111 => one(16, [4, 4, 4], BC6H_MODE_14),
+        _ => return None,
+    };
+    Some(mode)
+}
+
+// RGBA16F output with an alpha of one
+pub fn decode_bc6h(block: &[u8], pixels: &mut [u8], signed: bool) {
+    let mut bits = BitReader::new(block);
+    let mut mode_bits = bits.read(2);
+    if mode_bits > 1 {
+        mode_bits |= bits.read(3) << 2;
+    }
+    let mode = match bc6h_mode(mode_bits) {
+        Some(mode) => mode,
+        None => {
+            // Reserved mode, decodes to black
+            pixels.fill(0);
+            return;
+        }
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, high, low) in mode.layout {
+        if high >= low {
+            fields[field] |= (bits.read(high - low + 1) << low) as i32;
+        } else {
+            for bit in (high..=low).rev() {
+                fields[field] |= (bits.read(1) << bit) as i32;
+            }
+        }
+    }
+
+    let endpoint_count = if mode.two_subsets { 4 } else { 2 };
+    let epb = mode.endpoint_bits;
+    let mut endpoints = [[0i32; 3]; 4];
+    for (endpoint, values) in endpoints.iter_mut().enumerate().take(endpoint_count) {
+        values.copy_from_slice(&fields[endpoint * 3..endpoint * 3 + 3]);
+    }
+
+    if signed {
+        endpoints[0] = endpoints[0].map(|value| sign_extend(value, epb));
+    }
+    let base = endpoints[0];
+    for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+        for channel in 0..3 {
+            let mut value = endpoint[channel];
+            if mode.transformed {
+                let delta = sign_extend(value, mode.delta_bits[channel]);
+                value = (base[channel] + delta) & ((1 << epb) - 1);
+            }
+            endpoint[channel] = if signed {
+                sign_extend(value, epb)
+            } else {
+                value
+            };
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = unquantize_bc6h(*value, epb, signed);
+        }
+    }
+
+    let partition = fields[D] as usize;
+    let index_bits = if mode.two_subsets { 3 } else { 4 };
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let anchor = pixel == 0 || (mode.two_subsets && pixel == BC7_ANCHORS_2[partition] as usize);
+        *index = bits.read(index_bits - anchor as u32);
+    }
+
+    for (pixel, output) in pixels.chunks_exact_mut(8).enumerate() {
+        let subset = if mode.two_subsets {
+            ((BC7_PARTITIONS_2[partition] >> pixel) & 1) as usize
+        } else {
+            0
+        };
+        let weight = weights(index_bits)[indices[pixel] as usize];
+        let e0 = &endpoints[subset * 2];
+        let e1 = &endpoints[subset * 2 + 1];
+
+        for channel in 0..3 {
+            let value = interpolate(e0[channel], e1[channel], weight);
+            let half = finish_unquantize_bc6h(value, signed);
+            output[channel * 2..channel * 2 + 2].copy_from_slice(&half.to_le_bytes());
+        }
+        output[6..8].copy_from_slice(&0x3C00u16.to_le_bytes());
+    }
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn unquantize_bc6h(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 {
+            value
+        } else if value == 0 {
+            0
+        } else if value == (1 << bits) - 1 {
+            0xFFFF
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 {
+        value
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+// Scales the interpolated value into the bit pattern of a half float
+fn finish_unquantize_bc6h(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | (((-value) * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+// Subset of every pixel for the two subset partitions, one bit per pixel
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// Anchor pixels store their index with one bit less, the first pixel is always an anchor
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BC7_ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+const BC7_ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(decoder: impl Fn(&[u8], &mut [u8]), block: &[u8], pixel_size: usize) -> Vec<u8> {
+        let mut pixels = vec![0; 16 * pixel_size];
+        decoder(block, &mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn bc1_four_color_block() {
+        // Red and blue endpoints, the first four pixels use each palette entry once
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0x00, 0x00, 0x00];
+        let pixels = decode(|b, p| decode_bc1(b, p, true), &block, 4);
+        assert_eq!(pixels[0..4], [255, 0, 0, 255]);
+        assert_eq!(pixels[4..8], [0, 0, 255, 255]);
+        assert_eq!(pixels[8..12], [170, 0, 85, 255]);
+        assert_eq!(pixels[12..16], [85, 0, 170, 255]);
+        assert!(pixels[16..]
+            .chunks(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn bc1_three_color_block() {
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0xE4, 0x00, 0x00, 0x00];
+        let pixels = decode(|b, p| decode_bc1(b, p, true), &block, 4);
+        assert_eq!(pixels[8..12], [128, 0, 128, 255]);
+        assert_eq!(pixels[12..16], [0, 0, 0, 0]);
+
+        // Without punch-through alpha the fourth color is opaque black
+        let pixels = decode(|b, p| decode_bc1(b, p, false), &block, 4);
+        assert_eq!(pixels[12..16], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc2_explicit_alpha() {
+        let mut block = [0u8; 16];
+        block[0] = 0xF0;
+        block[8..10].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        let pixels = decode(decode_bc2, &block, 4);
+        assert_eq!(pixels[0..4], [255, 255, 255, 0]);
+        assert_eq!(pixels[4..8], [255, 255, 255, 255]);
+        assert_eq!(pixels[8..12], [255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn bc4_interpolated_values() {
+        // Endpoints 255 and 0 with 8 values, the first three pixels use indices 0, 1 and 2
+        let block = [255, 0, 0x88, 0, 0, 0, 0, 0];
+        let pixels = decode(|b, p| decode_bc4(b, p, false), &block, 1);
+        assert_eq!(pixels[0..3], [255, 0, 219]);
+        assert!(pixels[3..].iter().all(|&value| value == 255));
+
+        // With e0 <= e1 indices 6 and 7 are the extremes of the range
+        let block = [10, 20, 0b110 | 0b111 << 3, 0, 0, 0, 0, 0];
+        let pixels = decode(|b, p| decode_bc4(b, p, false), &block, 1);
+        assert_eq!(pixels[0..3], [0, 255, 10]);
+
+        let block = [0x80, 0x7F, 0, 0, 0, 0, 0, 0];
+        let pixels = decode(|b, p| decode_bc4(b, p, true), &block, 1);
+        assert!(pixels.iter().all(|&value| value as i8 == -127));
+    }
+
+    #[test]
+    fn bc5_two_channels() {
+        let mut block = [0u8; 16];
+        block[0] = 200;
+        block[8] = 50;
+        let pixels = decode(|b, p| decode_bc5(b, p, false), &block, 2);
+        assert!(pixels.chunks(2).all(|pixel| pixel == [200, 50]));
+    }
+
+    #[test]
+    fn bc7_mode_6_endpoints() {
+        // Mode 6 with all endpoint and p-bits set is opaque white, with all of them cleared it
+        // is transparent black
+        let mut block = [0xFF; 16];
+        block[0] = 0xC0;
+        let pixels = decode(decode_bc7, &block, 4);
+        assert!(pixels.iter().all(|&value| value == 255));
+
+        let mut block = [0; 16];
+        block[0] = 0x40;
+        let pixels = decode(decode_bc7, &block, 4);
+        assert!(pixels.iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn bc7_reserved_mode() {
+        let pixels = decode(decode_bc7, &[0; 16], 4);
+        assert!(pixels.iter().all(|&value| value == 0));
+    }
+}
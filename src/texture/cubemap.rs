@@ -0,0 +1,207 @@
+use std::f32::consts::PI;
+
+use image::{DynamicImage, GenericImageView};
+
+use super::f32_to_f16;
+
+// Faces in the Vulkan layer order +X, -X, +Y, -Y, +Z, -Z, given as column and row of the face
+// in the cross
+const HORIZONTAL_CROSS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+const VERTICAL_CROSS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)];
+
+// Splits a horizontal (4x3 faces) or vertical (3x4 faces) cross into the six cube faces,
+// returns None for images with other proportions
+pub fn cross_faces(image: &DynamicImage) -> Option<Vec<DynamicImage>> {
+    let (width, height) = (image.width(), image.height());
+    let (face_size, positions, vertical) = if width * 3 == height * 4 {
+        (width / 4, HORIZONTAL_CROSS, false)
+    } else if width * 4 == height * 3 {
+        (width / 3, VERTICAL_CROSS, true)
+    } else {
+        return None;
+    };
+
+    let faces = positions
+        .iter()
+        .enumerate()
+        .map(|(face, &(column, row))| {
+            let image = image.crop_imm(column * face_size, row * face_size, face_size, face_size);
+            // The -Z face hangs below +Y in the vertical cross and is upside down
+            if vertical && face == 5 {
+                image.rotate180()
+            } else {
+                image
+            }
+        })
+        .collect();
+    Some(faces)
+}
+
+// Size of the six faces of a cubemap, None unless all of them are square and of the same size
+pub fn face_size(faces: &[DynamicImage]) -> Option<u32> {
+    let size = faces.first()?.width();
+    let square = |face: &DynamicImage| face.width() == size && face.height() == size;
+    (faces.len() == 6 && faces.iter().all(square)).then_some(size)
+}
+
+// Projects an equirectangular RGBA image onto the six faces of a cube with +Y as up, the
+// result is R16G16B16A16_SFLOAT data with the faces in layer order
+pub fn equirectangular_to_cube(pixels: &[f32], width: u32, height: u32, face_size: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((face_size * face_size) as usize * 6 * 8);
+    for face in 0..6 {
+        for y in 0..face_size {
+            for x in 0..face_size {
+                let u = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                let color = sample(pixels, width, height, face_direction(face, u, v));
+                data.extend(
+                    color
+                        .iter()
+                        .flat_map(|&value| f32_to_f16(value).to_le_bytes()),
+                );
+            }
+        }
+    }
+    data
+}
+
+// Direction through the texel at (u, v) in [-1, 1] of a face, as defined by the cube map
+// face selection of the Vulkan specification
+fn face_direction(face: usize, u: f32, v: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    }
+}
+
+// Bilinear lookup, wrapping around horizontally and clamped at the poles
+fn sample(pixels: &[f32], width: u32, height: u32, direction: [f32; 3]) -> [f32; 4] {
+    let [x, y, z] = direction;
+    let length = (x * x + y * y + z * z).sqrt();
+    let s = 0.5 + x.atan2(z) / (2.0 * PI);
+    let t = 0.5 - (y / length).asin() / PI;
+
+    let px = s * width as f32 - 0.5;
+    let py = (t * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+    let (x0, y0) = (px.floor(), py.floor());
+    let (fx, fy) = (px - x0, py - y0);
+
+    let texel = |x: i64, y: i64| {
+        let x = x.rem_euclid(width as i64) as usize;
+        let y = y.clamp(0, height as i64 - 1) as usize;
+        let offset = (y * width as usize + x) * 4;
+        &pixels[offset..offset + 4]
+    };
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let (a, b, c, d) = (
+        texel(x0, y0),
+        texel(x0 + 1, y0),
+        texel(x0, y0 + 1),
+        texel(x0 + 1, y0 + 1),
+    );
+
+    let mut color = [0.0; 4];
+    for channel in 0..4 {
+        let top = a[channel] + (b[channel] - a[channel]) * fx;
+        let bottom = c[channel] + (d[channel] - c[channel]) * fx;
+        color[channel] = top + (bottom - top) * fy;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::texture::f16_to_f32;
+
+    // Cross with every cell filled with its column and row index
+    fn cross(columns: u32, rows: u32, face_size: u32) -> DynamicImage {
+        let image = RgbaImage::from_fn(columns * face_size, rows * face_size, |x, y| {
+            Rgba([(x / face_size) as u8, (y / face_size) as u8, 0, 255])
+        });
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn cell(face: &DynamicImage) -> (u8, u8) {
+        let pixel = face.get_pixel(0, 0);
+        (pixel[0], pixel[1])
+    }
+
+    // Red channel of the center texel of every face of a 1x1 cubemap
+    fn face_centers(pixels: &[f32], width: u32, height: u32) -> Vec<f32> {
+        equirectangular_to_cube(pixels, width, height, 1)
+            .chunks_exact(8)
+            .map(|texel| f16_to_f32(u16::from_le_bytes([texel[0], texel[1]])))
+            .collect()
+    }
+
+    #[test]
+    fn horizontal_cross() {
+        let faces = cross_faces(&cross(4, 3, 2)).unwrap();
+        let cells: Vec<_> = faces.iter().map(cell).collect();
+        assert_eq!(cells, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)]);
+        assert_eq!(face_size(&faces), Some(2));
+    }
+
+    #[test]
+    fn vertical_cross() {
+        let image = cross(3, 4, 2);
+        let faces = cross_faces(&image).unwrap();
+        let cells: Vec<_> = faces.iter().map(cell).collect();
+        assert_eq!(cells, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)]);
+
+        // -Z is stored upside down below -Y
+        let mut image = image.into_rgba8();
+        image.put_pixel(2, 6, Rgba([9, 9, 9, 255]));
+        let faces = cross_faces(&DynamicImage::ImageRgba8(image)).unwrap();
+        assert_eq!(faces[5].get_pixel(1, 1), Rgba([9, 9, 9, 255]));
+    }
+
+    #[test]
+    fn invalid_cross() {
+        assert!(cross_faces(&cross(4, 4, 2)).is_none());
+        assert!(cross_faces(&cross(6, 1, 2)).is_none());
+    }
+
+    #[test]
+    fn face_sizes() {
+        let face = |width, height| DynamicImage::new_rgba8(width, height);
+        assert_eq!(face_size(&vec![face(4, 4); 6]), Some(4));
+        assert_eq!(face_size(&vec![face(4, 4); 5]), None);
+        assert_eq!(face_size(&vec![face(4, 2); 6]), None);
+
+        let mut faces = vec![face(4, 4); 6];
+        faces[3] = face(8, 8);
+        assert_eq!(face_size(&faces), None);
+    }
+
+    #[test]
+    fn equirectangular_latitude() {
+        // Rows from the north to the south pole with the values 0, 1 and 2
+        let pixels: Vec<f32> = (0..3)
+            .flat_map(|row| [row as f32, 0.0, 0.0, 1.0].repeat(4))
+            .collect();
+        let centers = face_centers(&pixels, 4, 3);
+        assert_eq!(centers, [1.0, 1.0, 0.0, 2.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn equirectangular_longitude() {
+        // Columns with the values 0 to 3, +Z looks at the center of the image and the seam is
+        // behind it, so -Z blends the first and last column
+        let pixels: Vec<f32> = (0..4)
+            .flat_map(|column| [column as f32, 0.0, 0.0, 1.0])
+            .collect();
+        let centers = face_centers(&pixels, 4, 1);
+        assert_eq!(
+            [centers[0], centers[1], centers[4], centers[5]],
+            [2.5, 0.5, 1.5, 1.5]
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use std::io;
+
+use ash::vk;
+
+use crate::vulkan::{ImageDimensions, TextureColorSpace};
+
+use super::{block_layout, invalid_data, level_size, mip_level_count, read_u32, TextureData};
+
+pub const MAGIC: &[u8; 4] = b"DDS ";
+
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DIMENSION_TEXTURE2D: u32 = 3;
+const DIMENSION_TEXTURE3D: u32 = 4;
+const MISC_TEXTURECUBE: u32 = 0x4;
+
+// https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dds-header
+pub fn parse(data: &[u8], color_space: TextureColorSpace) -> io::Result<TextureData> {
+    if data.len() < HEADER_SIZE {
+        return Err(invalid_data("truncated DDS header"));
+    }
+
+    let flags = read_u32(data, 8);
+    let height = read_u32(data, 12);
+    let width = read_u32(data, 16);
+    let depth = read_u32(data, 24);
+    let mip_map_count = read_u32(data, 28);
+    let pixel_flags = read_u32(data, 80);
+    let four_cc = &data[84..88];
+    let caps2 = read_u32(data, 112);
+
+    let legacy_dimensions = if caps2 & DDSCAPS2_CUBEMAP != 0 {
+        if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+            return Err(invalid_data(
+                "DDS cubemaps without all six faces are not supported",
+            ));
+        }
+        ImageDimensions::Cube
+    } else if caps2 & DDSCAPS2_VOLUME != 0 {
+        ImageDimensions::D3
+    } else {
+        ImageDimensions::D2
+    };
+
+    let srgb = color_space == TextureColorSpace::Srgb;
+    let (format, dimensions, mut offset) = if pixel_flags & DDPF_FOURCC != 0 && four_cc == b"DX10" {
+        if data.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+            return Err(invalid_data("truncated DDS DX10 header"));
+        }
+        let dimension = read_u32(data, HEADER_SIZE + 4);
+        let misc_flags = read_u32(data, HEADER_SIZE + 8);
+        let array_size = read_u32(data, HEADER_SIZE + 12);
+        let cube = misc_flags & MISC_TEXTURECUBE != 0;
+        let dimensions = match (dimension, cube, array_size.max(1)) {
+            (DIMENSION_TEXTURE2D, false, 1) => ImageDimensions::D2,
+            (DIMENSION_TEXTURE2D, false, layers) => ImageDimensions::D2Array(layers),
+            (DIMENSION_TEXTURE2D, true, 1) => ImageDimensions::Cube,
+            (DIMENSION_TEXTURE3D, false, 1) => ImageDimensions::D3,
+            _ => return Err(invalid_data("unsupported DDS texture type")),
+        };
+        let format = dxgi_format(read_u32(data, HEADER_SIZE))
+            .ok_or_else(|| invalid_data("unsupported DXGI format"))?;
+        (format, dimensions, HEADER_SIZE + DX10_HEADER_SIZE)
+    } else if pixel_flags & DDPF_FOURCC != 0 {
+        let format =
+            four_cc_format(four_cc, srgb).ok_or_else(|| invalid_data("unsupported FourCC"))?;
+        (format, legacy_dimensions, HEADER_SIZE)
+    } else if pixel_flags & DDPF_RGB != 0 {
+        let format = rgb_format(data, pixel_flags, srgb)
+            .ok_or_else(|| invalid_data("unsupported DDS pixel format"))?;
+        (format, legacy_dimensions, HEADER_SIZE)
+    } else {
+        return Err(invalid_data("unsupported DDS pixel format"));
+    };
+
+    let layout = block_layout(format).ok_or_else(|| invalid_data("unsupported DDS format"))?;
+    let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        mip_map_count.max(1)
+    } else {
+        1
+    };
+
+    if width == 0 || height == 0 {
+        return Err(invalid_data("DDS texture has no pixels"));
+    }
+    if dimensions == ImageDimensions::Cube && width != height {
+        return Err(invalid_data("DDS cubemap faces are not square"));
+    }
+    let depth = if dimensions == ImageDimensions::D3 {
+        depth.max(1)
+    } else {
+        1
+    };
+    if level_count > mip_level_count(width, height.max(depth)) {
+        return Err(invalid_data("too many DDS mip levels"));
+    }
+
+    // DDS files store the complete mip chain of every layer or face one after another, the
+    // levels are regrouped so that each one holds all layers
+    let mut levels = vec![Vec::new(); level_count as usize];
+    for _ in 0..dimensions.layer_count() {
+        for (level, level_data) in levels.iter_mut().enumerate() {
+            let level_width = (width >> level).max(1);
+            let level_height = (height >> level).max(1);
+            let level_depth = (depth >> level).max(1) as usize;
+            let length = level_size(layout, level_width, level_height).saturating_mul(level_depth);
+            let end = offset
+                .checked_add(length)
+                .ok_or_else(|| invalid_data("truncated DDS level data"))?;
+            let bytes = data
+                .get(offset..end)
+                .ok_or_else(|| invalid_data("truncated DDS level data"))?;
+            level_data.extend_from_slice(bytes);
+            offset = end;
+        }
+    }
+
+    Ok(TextureData {
+        width,
+        height,
+        depth,
+        format,
+        dimensions,
+        levels,
+    })
+}
+
+fn four_cc_format(four_cc: &[u8], srgb: bool) -> Option<vk::Format> {
+    use vk::Format as F;
+
+    let (linear, encoded) = match four_cc {
+        b"DXT1" => (F::BC1_RGBA_UNORM_BLOCK, F::BC1_RGBA_SRGB_BLOCK),
+        b"DXT2" | b"DXT3" => (F::BC2_UNORM_BLOCK, F::BC2_SRGB_BLOCK),
+        b"DXT4" | b"DXT5" => (F::BC3_UNORM_BLOCK, F::BC3_SRGB_BLOCK),
+        b"ATI1" | b"BC4U" => (F::BC4_UNORM_BLOCK, F::BC4_UNORM_BLOCK),
+        b"BC4S" => (F::BC4_SNORM_BLOCK, F::BC4_SNORM_BLOCK),
+        b"ATI2" | b"BC5U" => (F::BC5_UNORM_BLOCK, F::BC5_UNORM_BLOCK),
+        b"BC5S" => (F::BC5_SNORM_BLOCK, F::BC5_SNORM_BLOCK),
+        _ => return None,
+    };
+    Some(if srgb { encoded } else { linear })
+}
+
+// Uncompressed legacy files are only supported in the common 32-bit layouts
+fn rgb_format(data: &[u8], pixel_flags: u32, srgb: bool) -> Option<vk::Format> {
+    let bit_count = read_u32(data, 88);
+    let red_mask = read_u32(data, 92);
+    let alpha_mask = if pixel_flags & DDPF_ALPHAPIXELS != 0 {
+        read_u32(data, 104)
+    } else {
+        0
+    };
+    if bit_count != 32 || (alpha_mask != 0 && alpha_mask != 0xFF000000) {
+        return None;
+    }
+
+    match (red_mask, srgb) {
+        (0x000000FF, false) => Some(vk::Format::R8G8B8A8_UNORM),
+        (0x000000FF, true) => Some(vk::Format::R8G8B8A8_SRGB),
+        (0x00FF0000, false) => Some(vk::Format::B8G8R8A8_UNORM),
+        (0x00FF0000, true) => Some(vk::Format::B8G8R8A8_SRGB),
+        _ => None,
+    }
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/api/dxgiformat/ne-dxgiformat-dxgi_format
+fn dxgi_format(format: u32) -> Option<vk::Format> {
+    use vk::Format as F;
+
+    let format = match format {
+        2 => F::R32G32B32A32_SFLOAT,
+        10 => F::R16G16B16A16_SFLOAT,
+        11 => F::R16G16B16A16_UNORM,
+        16 => F::R32G32_SFLOAT,
+        28 => F::R8G8B8A8_UNORM,
+        29 => F::R8G8B8A8_SRGB,
+        31 => F::R8G8B8A8_SNORM,
+        34 => F::R16G16_SFLOAT,
+        35 => F::R16G16_UNORM,
+        37 => F::R16G16_SNORM,
+        41 => F::R32_SFLOAT,
+        49 => F::R8G8_UNORM,
+        51 => F::R8G8_SNORM,
+        54 => F::R16_SFLOAT,
+        56 => F::R16_UNORM,
+        58 => F::R16_SNORM,
+        61 => F::R8_UNORM,
+        63 => F::R8_SNORM,
+        71 => F::BC1_RGBA_UNORM_BLOCK,
+        72 => F::BC1_RGBA_SRGB_BLOCK,
+        74 => F::BC2_UNORM_BLOCK,
+        75 => F::BC2_SRGB_BLOCK,
+        77 => F::BC3_UNORM_BLOCK,
+        78 => F::BC3_SRGB_BLOCK,
+        80 => F::BC4_UNORM_BLOCK,
+        81 => F::BC4_SNORM_BLOCK,
+        83 => F::BC5_UNORM_BLOCK,
+        84 => F::BC5_SNORM_BLOCK,
+        87 => F::B8G8R8A8_UNORM,
+        91 => F::B8G8R8A8_SRGB,
+        95 => F::BC6H_UFLOAT_BLOCK,
+        96 => F::BC6H_SFLOAT_BLOCK,
+        98 => F::BC7_UNORM_BLOCK,
+        99 => F::BC7_SRGB_BLOCK,
+        _ => return None,
+    };
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DDSD_CAPS_HEIGHT_WIDTH_PIXELFORMAT: u32 = 0x1007;
+
+    // A DXT1 texture with the complete mip chain
+    fn texture(width: u32, height: u32, mip_map_count: u32) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        data[..4].copy_from_slice(MAGIC);
+        let flags = DDSD_CAPS_HEIGHT_WIDTH_PIXELFORMAT | DDSD_MIPMAPCOUNT;
+        data[8..12].copy_from_slice(&flags.to_le_bytes());
+        data[12..16].copy_from_slice(&height.to_le_bytes());
+        data[16..20].copy_from_slice(&width.to_le_bytes());
+        data[28..32].copy_from_slice(&mip_map_count.to_le_bytes());
+        data[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        data[84..88].copy_from_slice(b"DXT1");
+
+        let layout = block_layout(vk::Format::BC1_RGBA_UNORM_BLOCK).unwrap();
+        for level in 0..mip_level_count(width, height).min(mip_map_count) {
+            let size = level_size(layout, (width >> level).max(1), (height >> level).max(1));
+            data.resize(data.len() + size, 0x55);
+        }
+        data
+    }
+
+    #[test]
+    fn parses_valid_texture() {
+        let texture = parse(&texture(8, 4, 4), TextureColorSpace::Srgb).unwrap();
+        assert_eq!((texture.width, texture.height), (8, 4));
+        assert_eq!(texture.format, vk::Format::BC1_RGBA_SRGB_BLOCK);
+        let sizes: Vec<usize> = texture.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [16, 8, 8, 8]);
+    }
+
+    #[test]
+    fn rejects_too_many_levels() {
+        // An 8x4 texture has 4 levels
+        assert!(parse(&texture(8, 4, 5), TextureColorSpace::Linear).is_err());
+        assert!(parse(&texture(8, 4, 33), TextureColorSpace::Linear).is_err());
+        assert!(parse(&texture(8, 4, u32::MAX), TextureColorSpace::Linear).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let valid = texture(8, 4, 4);
+        for length in 0..valid.len() {
+            assert!(parse(&valid[..length], TextureColorSpace::Linear).is_err());
+        }
+        assert!(parse(&texture(0, 4, 1), TextureColorSpace::Linear).is_err());
+
+        let mut data = valid.clone();
+        data[12..20].fill(0xFF);
+        assert!(parse(&data, TextureColorSpace::Linear).is_err());
+
+        // Every header field set to extreme values must fail cleanly
+        for offset in (4..HEADER_SIZE).step_by(4) {
+            for value in [0, 1, 6, 33, 0x8000_0000, u32::MAX] {
+                let mut data = valid.clone();
+                data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+                let _ = parse(&data, TextureColorSpace::Linear);
+            }
+        }
+    }
+}
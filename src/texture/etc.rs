@@ -0,0 +1,329 @@
+// Block decoders for the ETC2 and EAC formats, every function decodes one 4x4 block into row
+// major pixels. https://registry.khronos.org/DataFormat/specs/1.3/dataformat.1.3.html#ETC2
+
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// RGBA8 output, `punch_through` decodes the RGB8A1 variant with its transparent pixels
+pub fn decode_etc2_rgb(block: &[u8], pixels: &mut [u8], punch_through: bool) {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let diff = (bits >> 33) & 1 == 1;
+    // The diff bit marks opaque blocks in the punch-through format, which has no individual mode
+    let transparent = punch_through && !diff;
+
+    if !punch_through && !diff {
+        let c1 = [field(bits, 60, 4), field(bits, 52, 4), field(bits, 44, 4)];
+        let c2 = [field(bits, 56, 4), field(bits, 48, 4), field(bits, 40, 4)];
+        decode_subblocks(bits, [c1.map(extend4), c2.map(extend4)], false, pixels);
+        return;
+    }
+
+    let base = [field(bits, 59, 5), field(bits, 51, 5), field(bits, 43, 5)];
+    let delta =
+        [field(bits, 56, 3), field(bits, 48, 3), field(bits, 40, 3)].map(|d| (d << 29) >> 29);
+    let overflow = |channel: usize| !(0..32).contains(&(base[channel] + delta[channel]));
+
+    if overflow(0) {
+        decode_t_mode(bits, transparent, pixels);
+    } else if overflow(1) {
+        decode_h_mode(bits, transparent, pixels);
+    } else if overflow(2) {
+        decode_planar(bits, pixels);
+    } else {
+        let c1 = base.map(extend5);
+        let c2 = [0, 1, 2].map(|channel| extend5(base[channel] + delta[channel]));
+        decode_subblocks(bits, [c1, c2], transparent, pixels);
+    }
+}
+
+// RGBA8 output
+pub fn decode_etc2_rgba(block: &[u8], pixels: &mut [u8]) {
+    decode_etc2_rgb(&block[8..16], pixels, false);
+
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let base = field(bits, 56, 8);
+    let multiplier = field(bits, 52, 4);
+    let modifiers = &EAC_MODIFIERS[field(bits, 48, 4) as usize];
+    for_each_pixel(|x, y, index| {
+        let modifier = modifiers[field(bits, 45 - 3 * index, 3) as usize];
+        pixels[(y * 4 + x) * 4 + 3] = (base + modifier * multiplier).clamp(0, 255) as u8;
+    });
+}
+
+// R16 output, signed data is written as two's complement for the SNORM formats
+pub fn decode_eac_r11(block: &[u8], pixels: &mut [u8], signed: bool) {
+    decode_eac_channel(block, pixels, 2, 0, signed);
+}
+
+// RG16 output
+pub fn decode_eac_rg11(block: &[u8], pixels: &mut [u8], signed: bool) {
+    decode_eac_channel(&block[0..8], pixels, 4, 0, signed);
+    decode_eac_channel(&block[8..16], pixels, 4, 2, signed);
+}
+
+fn decode_eac_channel(block: &[u8], pixels: &mut [u8], stride: usize, offset: usize, signed: bool) {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let multiplier = field(bits, 52, 4);
+    let modifiers = &EAC_MODIFIERS[field(bits, 48, 4) as usize];
+    let base = if signed {
+        (block[0] as i8).max(-127) as i32 * 8
+    } else {
+        block[0] as i32 * 8 + 4
+    };
+
+    for_each_pixel(|x, y, index| {
+        let modifier = modifiers[field(bits, 45 - 3 * index, 3) as usize];
+        let scaled = if multiplier == 0 {
+            modifier
+        } else {
+            modifier * multiplier * 8
+        };
+        let value = if signed {
+            let value = (base + scaled).clamp(-1023, 1023);
+            let magnitude = value.abs();
+            let extended = (magnitude << 5) | (magnitude >> 5);
+            (if value < 0 { -extended } else { extended }) as i16 as u16
+        } else {
+            let value = (base + scaled).clamp(0, 2047);
+            ((value << 5) | (value >> 6)) as u16
+        };
+        let target = (y * 4 + x) * stride + offset;
+        pixels[target..target + 2].copy_from_slice(&value.to_le_bytes());
+    });
+}
+
+fn decode_subblocks(bits: u64, colors: [[i32; 3]; 2], transparent: bool, pixels: &mut [u8]) {
+    let tables = [field(bits, 37, 3), field(bits, 34, 3)];
+    let flip = (bits >> 32) & 1 == 1;
+
+    for_each_pixel(|x, y, index| {
+        let subblock = if flip { y >= 2 } else { x >= 2 } as usize;
+        let [small, large] = MODIFIERS[tables[subblock] as usize];
+        let modifier = match pixel_index(bits, index) {
+            0 if transparent => 0,
+            0 => small,
+            1 => large,
+            2 if transparent => {
+                write_pixel(pixels, x, y, [0, 0, 0, 0]);
+                return;
+            }
+            2 => -small,
+            _ => -large,
+        };
+        let color = colors[subblock].map(|channel| (channel + modifier).clamp(0, 255));
+        write_pixel(pixels, x, y, [color[0], color[1], color[2], 255]);
+    });
+}
+
+fn decode_t_mode(bits: u64, transparent: bool, pixels: &mut [u8]) {
+    let c1 = [
+        (field(bits, 59, 2) << 2) | field(bits, 56, 2),
+        field(bits, 52, 4),
+        field(bits, 48, 4),
+    ]
+    .map(extend4);
+    let c2 = [field(bits, 44, 4), field(bits, 40, 4), field(bits, 36, 4)].map(extend4);
+    let distance = DISTANCES[((field(bits, 34, 2) << 1) | field(bits, 32, 1)) as usize];
+
+    let paint = [c1, c2.map(|c| c + distance), c2, c2.map(|c| c - distance)];
+    decode_paint_colors(bits, paint, transparent, pixels);
+}
+
+fn decode_h_mode(bits: u64, transparent: bool, pixels: &mut [u8]) {
+    let c1 = [
+        field(bits, 59, 4),
+        (field(bits, 56, 3) << 1) | field(bits, 52, 1),
+        (field(bits, 51, 1) << 3) | field(bits, 47, 3),
+    ];
+    let c2 = [field(bits, 43, 4), field(bits, 39, 4), field(bits, 35, 4)];
+    let value = |c: [i32; 3]| (c[0] << 8) | (c[1] << 4) | c[2];
+    let distance_index =
+        (field(bits, 34, 1) << 2) | (field(bits, 32, 1) << 1) | (value(c1) >= value(c2)) as i32;
+    let distance = DISTANCES[distance_index as usize];
+
+    let (c1, c2) = (c1.map(extend4), c2.map(extend4));
+    let paint = [
+        c1.map(|c| c + distance),
+        c1.map(|c| c - distance),
+        c2.map(|c| c + distance),
+        c2.map(|c| c - distance),
+    ];
+    decode_paint_colors(bits, paint, transparent, pixels);
+}
+
+fn decode_paint_colors(bits: u64, paint: [[i32; 3]; 4], transparent: bool, pixels: &mut [u8]) {
+    for_each_pixel(|x, y, index| {
+        let color_index = pixel_index(bits, index);
+        if transparent && color_index == 2 {
+            write_pixel(pixels, x, y, [0, 0, 0, 0]);
+        } else {
+            let color = paint[color_index as usize].map(|channel| channel.clamp(0, 255));
+            write_pixel(pixels, x, y, [color[0], color[1], color[2], 255]);
+        }
+    });
+}
+
+// Planar blocks are always opaque, also in the punch-through format
+fn decode_planar(bits: u64, pixels: &mut [u8]) {
+    let origin = [
+        extend6(field(bits, 57, 6)),
+        extend7((field(bits, 56, 1) << 6) | field(bits, 49, 6)),
+        extend6((field(bits, 48, 1) << 5) | (field(bits, 43, 2) << 3) | field(bits, 39, 3)),
+    ];
+    let horizontal = [
+        extend6((field(bits, 34, 5) << 1) | field(bits, 32, 1)),
+        extend7(field(bits, 25, 7)),
+        extend6(field(bits, 19, 6)),
+    ];
+    let vertical = [
+        extend6(field(bits, 13, 6)),
+        extend7(field(bits, 6, 7)),
+        extend6(field(bits, 0, 6)),
+    ];
+
+    for_each_pixel(|x, y, _| {
+        let color = [0, 1, 2].map(|channel| {
+            let o = origin[channel];
+            let value = x as i32 * (horizontal[channel] - o)
+                + y as i32 * (vertical[channel] - o)
+                + 4 * o
+                + 2;
+            (value >> 2).clamp(0, 255)
+        });
+        write_pixel(pixels, x, y, [color[0], color[1], color[2], 255]);
+    });
+}
+
+// The pixel indices of ETC blocks are stored in column major order
+fn for_each_pixel(mut f: impl FnMut(usize, usize, u32)) {
+    for x in 0..4 {
+        for y in 0..4 {
+            f(x, y, (x * 4 + y) as u32);
+        }
+    }
+}
+
+fn pixel_index(bits: u64, index: u32) -> i32 {
+    (field(bits, 16 + index, 1) << 1) | field(bits, index, 1)
+}
+
+fn write_pixel(pixels: &mut [u8], x: usize, y: usize, color: [i32; 4]) {
+    let offset = (y * 4 + x) * 4;
+    for (target, value) in pixels[offset..offset + 4].iter_mut().zip(color) {
+        *target = value as u8;
+    }
+}
+
+fn field(bits: u64, low: u32, count: u32) -> i32 {
+    ((bits >> low) & ((1 << count) - 1)) as i32
+}
+
+fn extend4(value: i32) -> i32 {
+    (value << 4) | value
+}
+
+fn extend5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn extend6(value: i32) -> i32 {
+    (value << 2) | (value >> 4)
+}
+
+fn extend7(value: i32) -> i32 {
+    (value << 1) | (value >> 6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(decoder: impl Fn(&[u8], &mut [u8]), bits: u64, pixel_size: usize) -> Vec<u8> {
+        let mut pixels = vec![0; 16 * pixel_size];
+        decoder(&bits.to_be_bytes(), &mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn etc2_individual_block() {
+        // Both subblocks have the color 0x88 with the first modifier table, the first pixel
+        // has index 2 (-2), the pixel below it index 1 (+8)
+        let bits = 0x8888_8800_0000_0000 | 1 << 16 | 1 << 1;
+        let pixels = decode(|b, p| decode_etc2_rgb(b, p, false), bits, 4);
+        assert_eq!(pixels[0..4], [134, 134, 134, 255]);
+        assert_eq!(pixels[16..20], [144, 144, 144, 255]);
+        assert_eq!(pixels[4..8], [138, 138, 138, 255]);
+    }
+
+    #[test]
+    fn etc2_differential_block() {
+        // Base 16 and delta -1 in every channel, the right subblock is darker
+        let bits = 0x8787_8702_0000_0000;
+        let pixels = decode(|b, p| decode_etc2_rgb(b, p, false), bits, 4);
+        assert_eq!(pixels[0..4], [134, 134, 134, 255]);
+        assert_eq!(pixels[12..16], [125, 125, 125, 255]);
+    }
+
+    #[test]
+    fn etc2_punch_through_transparency() {
+        // Without the opaque bit index 2 is transparent and index 0 has no modifier
+        let bits = 0x8787_8700_0000_0000 | 1 << 16;
+        let pixels = decode(|b, p| decode_etc2_rgb(b, p, true), bits, 4);
+        assert_eq!(pixels[0..4], [0, 0, 0, 0]);
+        assert_eq!(pixels[4..8], [132, 132, 132, 255]);
+    }
+
+    #[test]
+    fn eac_r11_values() {
+        // Base 128 with multiplier 1 and the last modifier table, the first pixel uses +2 and
+        // all others -3
+        let bits = 0x801F_0000_0000_0000 | 0b100 << 45;
+        let pixels = decode(|b, p| decode_eac_r11(b, p, false), bits, 2);
+        let value = (128 * 8 + 4 + 2 * 8) << 5 | (128 * 8 + 4 + 2 * 8) >> 6;
+        assert_eq!(u16::from_le_bytes([pixels[0], pixels[1]]), value);
+        assert_eq!(
+            u16::from_le_bytes([pixels[2], pixels[3]]),
+            (128 * 8 + 4 - 3 * 8) << 5 | (128 * 8 + 4 - 3 * 8) >> 6
+        );
+    }
+
+    #[test]
+    fn etc2_alpha_channel() {
+        let mut block = [0u8; 16];
+        block[0] = 200;
+        block[1] = 0x1F;
+        block[8..16].copy_from_slice(&0x8888_8800_0000_0000u64.to_be_bytes());
+        let mut pixels = vec![0; 64];
+        decode_etc2_rgba(&block, &mut pixels);
+        assert!(pixels.chunks(4).all(|pixel| pixel == [138, 138, 138, 197]));
+    }
+}
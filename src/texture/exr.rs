@@ -0,0 +1,294 @@
+use std::io;
+
+use super::{f16_to_f32, invalid_data, read_u32, read_u64};
+
+pub const MAGIC: [u8; 4] = [0x76, 0x2F, 0x31, 0x01];
+
+const TILED_FLAG: u32 = 0x200;
+const NON_IMAGE_FLAG: u32 = 0x800;
+const MULTIPART_FLAG: u32 = 0x1000;
+
+const PIXEL_TYPE_UINT: u32 = 0;
+const PIXEL_TYPE_HALF: u32 = 1;
+const PIXEL_TYPE_FLOAT: u32 = 2;
+
+const COMPRESSION_NONE: u8 = 0;
+const COMPRESSION_RLE: u8 = 1;
+const COMPRESSION_ZIPS: u8 = 2;
+const COMPRESSION_ZIP: u8 = 3;
+
+struct Channel {
+    name: String,
+    pixel_type: u32,
+}
+
+// RGBA values of a single part scanline image, `full_precision` is set when any of the color
+// channels is stored as 32-bit float. Channels other than R, G, B, A and Y are ignored.
+pub struct ExrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<f32>,
+    pub full_precision: bool,
+}
+
+// https://openexr.com/en/latest/OpenEXRFileLayout.html, only the lossless NONE, RLE, ZIPS and
+// ZIP compressions are supported
+pub fn parse(data: &[u8]) -> io::Result<ExrImage> {
+    if data.len() < 8 || !data.starts_with(&MAGIC) {
+        return Err(invalid_data("not an OpenEXR file"));
+    }
+    let version = read_u32(data, 4);
+    if version & 0xFF != 2 {
+        return Err(invalid_data("unsupported OpenEXR version"));
+    }
+    if version & (TILED_FLAG | NON_IMAGE_FLAG | MULTIPART_FLAG) != 0 {
+        return Err(invalid_data(
+            "only single part scanline OpenEXR images are supported",
+        ));
+    }
+
+    let mut offset = 8;
+    let mut channels = None;
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = read_string(data, &mut offset)?;
+        if name.is_empty() {
+            break;
+        }
+        let _type_name = read_string(data, &mut offset)?;
+        let size = read_i32(data, offset)? as usize;
+        offset += 4;
+        let value = data
+            .get(offset..offset + size)
+            .ok_or_else(|| invalid_data("truncated OpenEXR header"))?;
+        offset += size;
+
+        match name.as_str() {
+            "channels" => channels = Some(parse_channels(value)?),
+            "compression" => compression = value.first().copied(),
+            "dataWindow" => {
+                let mut window = [0; 4];
+                for (index, bound) in window.iter_mut().enumerate() {
+                    *bound = read_i32(value, index * 4)?;
+                }
+                data_window = Some(window);
+            }
+            _ => (),
+        }
+    }
+
+    let channels = channels.ok_or_else(|| invalid_data("OpenEXR image without channels"))?;
+    let compression = compression.unwrap_or(COMPRESSION_NONE);
+    let [x_min, y_min, x_max, y_max] =
+        data_window.ok_or_else(|| invalid_data("OpenEXR image without data window"))?;
+    if x_max < x_min || y_max < y_min {
+        return Err(invalid_data("empty OpenEXR data window"));
+    }
+    let width = (x_max - x_min + 1) as usize;
+    let height = (y_max - y_min + 1) as usize;
+
+    let lines_per_block = match compression {
+        COMPRESSION_NONE | COMPRESSION_RLE | COMPRESSION_ZIPS => 1,
+        COMPRESSION_ZIP => 16,
+        _ => return Err(invalid_data("unsupported OpenEXR compression")),
+    };
+    let line_size: usize = channels
+        .iter()
+        .map(|channel| sample_size(channel.pixel_type) * width)
+        .sum();
+
+    // Gray images only have a luminance channel, missing channels read as 0 and alpha as 1
+    let gray = !channels.iter().any(|channel| channel.name == "R")
+        && channels.iter().any(|channel| channel.name == "Y");
+    let target_channel = |name: &str| match name {
+        "R" => Some(0),
+        "G" => Some(1),
+        "B" => Some(2),
+        "A" => Some(3),
+        "Y" if gray => Some(0),
+        _ => None,
+    };
+
+    let mut pixels = vec![0.0f32; width * height * 4];
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = 1.0;
+    }
+
+    let block_count = height.div_ceil(lines_per_block);
+    for block in 0..block_count {
+        let table_entry = offset + block * 8;
+        if data.len() < table_entry + 8 {
+            return Err(invalid_data("truncated OpenEXR offset table"));
+        }
+        let chunk = read_u64(data, table_entry) as usize;
+        let first_line = read_i32(data, chunk)? - y_min;
+        let size = read_i32(data, chunk + 4)? as usize;
+        let packed = data
+            .get(chunk + 8..chunk + 8 + size)
+            .ok_or_else(|| invalid_data("truncated OpenEXR chunk"))?;
+
+        if first_line < 0 || first_line as usize >= height {
+            return Err(invalid_data("OpenEXR chunk outside of the data window"));
+        }
+        let first_line = first_line as usize;
+        let lines = lines_per_block.min(height - first_line);
+        let expected_size = line_size * lines;
+        // Blocks which do not get smaller by compressing them are stored as they are
+        let block_data = if size == expected_size {
+            packed.to_vec()
+        } else {
+            match compression {
+                COMPRESSION_RLE => reconstruct(&decode_rle(packed)?),
+                COMPRESSION_ZIPS | COMPRESSION_ZIP => {
+                    let inflated = miniz_oxide::inflate::decompress_to_vec_zlib(packed)
+                        .map_err(|_| invalid_data("corrupt OpenEXR zip data"))?;
+                    reconstruct(&inflated)
+                }
+                _ => packed.to_vec(),
+            }
+        };
+        if block_data.len() != expected_size {
+            return Err(invalid_data("unexpected OpenEXR block size"));
+        }
+
+        let mut position = 0;
+        for line in first_line..first_line + lines {
+            for channel in &channels {
+                let sample_size = sample_size(channel.pixel_type);
+                let target = target_channel(&channel.name);
+                for x in 0..width {
+                    let sample = &block_data[position..position + sample_size];
+                    position += sample_size;
+                    if let Some(target) = target {
+                        pixels[(line * width + x) * 4 + target] =
+                            read_sample(sample, channel.pixel_type);
+                    }
+                }
+            }
+        }
+    }
+
+    if gray {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel[1] = pixel[0];
+            pixel[2] = pixel[0];
+        }
+    }
+
+    let full_precision = channels.iter().any(|channel| {
+        target_channel(&channel.name).is_some() && channel.pixel_type != PIXEL_TYPE_HALF
+    });
+    Ok(ExrImage {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+        full_precision,
+    })
+}
+
+fn parse_channels(data: &[u8]) -> io::Result<Vec<Channel>> {
+    let mut channels = Vec::new();
+    let mut offset = 0;
+    loop {
+        let name = read_string(data, &mut offset)?;
+        if name.is_empty() {
+            break;
+        }
+        let pixel_type = read_i32(data, offset)? as u32;
+        let x_sampling = read_i32(data, offset + 8)?;
+        let y_sampling = read_i32(data, offset + 12)?;
+        offset += 16;
+
+        if pixel_type > PIXEL_TYPE_FLOAT {
+            return Err(invalid_data("unknown OpenEXR pixel type"));
+        }
+        if x_sampling != 1 || y_sampling != 1 {
+            return Err(invalid_data(
+                "subsampled OpenEXR channels are not supported",
+            ));
+        }
+        channels.push(Channel { name, pixel_type });
+    }
+    Ok(channels)
+}
+
+fn sample_size(pixel_type: u32) -> usize {
+    if pixel_type == PIXEL_TYPE_HALF {
+        2
+    } else {
+        4
+    }
+}
+
+fn read_sample(sample: &[u8], pixel_type: u32) -> f32 {
+    match pixel_type {
+        PIXEL_TYPE_UINT => read_u32(sample, 0) as f32,
+        PIXEL_TYPE_HALF => f16_to_f32(u16::from_le_bytes([sample[0], sample[1]])),
+        _ => f32::from_bits(read_u32(sample, 0)),
+    }
+}
+
+// Negative counts are followed by that many literal bytes, others repeat the next byte
+// count + 1 times
+fn decode_rle(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(data.len() * 2);
+    let mut offset = 0;
+    while offset < data.len() {
+        let count = data[offset] as i8;
+        offset += 1;
+        if count < 0 {
+            let length = -(count as i32) as usize;
+            let bytes = data
+                .get(offset..offset + length)
+                .ok_or_else(|| invalid_data("corrupt OpenEXR RLE data"))?;
+            output.extend_from_slice(bytes);
+            offset += length;
+        } else {
+            let value = *data
+                .get(offset)
+                .ok_or_else(|| invalid_data("corrupt OpenEXR RLE data"))?;
+            output.extend(std::iter::repeat_n(value, count as usize + 1));
+            offset += 1;
+        }
+    }
+    Ok(output)
+}
+
+// Undoes the delta predictor and interleaves the two halves the compressor splits bytes into
+fn reconstruct(data: &[u8]) -> Vec<u8> {
+    let mut deltas = data.to_vec();
+    for index in 1..deltas.len() {
+        deltas[index] = deltas[index - 1]
+            .wrapping_add(deltas[index])
+            .wrapping_sub(128);
+    }
+
+    let (first, second) = deltas.split_at(deltas.len().div_ceil(2));
+    let mut output = Vec::with_capacity(deltas.len());
+    for (index, &value) in first.iter().enumerate() {
+        output.push(value);
+        if let Some(&value) = second.get(index) {
+            output.push(value);
+        }
+    }
+    output
+}
+
+fn read_string(data: &[u8], offset: &mut usize) -> io::Result<String> {
+    let bytes = data
+        .get(*offset..)
+        .ok_or_else(|| invalid_data("truncated OpenEXR header"))?;
+    let length = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or_else(|| invalid_data("truncated OpenEXR header"))?;
+    *offset += length + 1;
+    Ok(String::from_utf8_lossy(&bytes[..length]).into_owned())
+}
+
+fn read_i32(data: &[u8], offset: usize) -> io::Result<i32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid_data("truncated OpenEXR data"))
+}
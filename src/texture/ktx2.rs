@@ -0,0 +1,179 @@
+use std::io;
+
+use ash::vk;
+
+use crate::vulkan::ImageDimensions;
+
+use super::{
+    block_layout, invalid_data, level_size, mip_level_count, read_u32, read_u64, TextureData,
+};
+
+pub const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+// https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+pub fn parse(data: &[u8]) -> io::Result<TextureData> {
+    if data.len() < HEADER_SIZE {
+        return Err(invalid_data("truncated KTX2 header"));
+    }
+
+    let format = vk::Format::from_raw(read_u32(data, 12) as i32);
+    let width = read_u32(data, 20);
+    let height = read_u32(data, 24);
+    let depth = read_u32(data, 28);
+    let layer_count = read_u32(data, 32);
+    let face_count = read_u32(data, 36);
+    let level_count = read_u32(data, 40).max(1);
+    let supercompression = read_u32(data, 44);
+
+    if format == vk::Format::UNDEFINED {
+        return Err(invalid_data(
+            "Basis Universal KTX2 textures are not supported",
+        ));
+    }
+    if supercompression != 0 {
+        return Err(invalid_data(
+            "supercompressed KTX2 textures are not supported",
+        ));
+    }
+    if width == 0 {
+        return Err(invalid_data("KTX2 texture has no width"));
+    }
+    // A depth, layer or face count of zero marks a texture which is not 3D, an array or a cube
+    let depth = depth.max(1);
+    let dimensions = match (depth, layer_count.max(1), face_count) {
+        _ if height == 0 => return Err(invalid_data("1D KTX2 textures are not supported")),
+        (1, 1, 1) => ImageDimensions::D2,
+        (1, layers, 1) => ImageDimensions::D2Array(layers),
+        (1, 1, 6) if width == height => ImageDimensions::Cube,
+        (_, 1, 1) => ImageDimensions::D3,
+        _ => return Err(invalid_data("unsupported KTX2 texture type")),
+    };
+    let images = dimensions.layer_count() as usize;
+    let layout = block_layout(format).ok_or_else(|| invalid_data("unsupported KTX2 format"))?;
+    if level_count > mip_level_count(width, height.max(depth)) {
+        return Err(invalid_data("too many KTX2 mip levels"));
+    }
+
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for level in 0..level_count {
+        let entry = HEADER_SIZE + level as usize * LEVEL_INDEX_ENTRY_SIZE;
+        if data.len() < entry + LEVEL_INDEX_ENTRY_SIZE {
+            return Err(invalid_data("truncated KTX2 level index"));
+        }
+        let offset = read_u64(data, entry) as usize;
+        let length = read_u64(data, entry + 8) as usize;
+
+        let level_width = (width >> level).max(1);
+        let level_height = (height >> level).max(1);
+        let level_depth = (depth >> level).max(1) as usize;
+        let expected_length = level_size(layout, level_width, level_height)
+            .saturating_mul(level_depth)
+            .saturating_mul(images);
+        if length != expected_length {
+            return Err(invalid_data("unexpected KTX2 level size"));
+        }
+        let end = offset
+            .checked_add(length)
+            .ok_or_else(|| invalid_data("truncated KTX2 level data"))?;
+        let bytes = data
+            .get(offset..end)
+            .ok_or_else(|| invalid_data("truncated KTX2 level data"))?;
+        levels.push(bytes.to_vec());
+    }
+
+    Ok(TextureData {
+        width,
+        height,
+        depth,
+        format,
+        dimensions,
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An RGBA8 texture with up to 4 entries in the level index, all of them point at the data
+    // of the first level so only single level textures are valid
+    fn texture(width: u32, height: u32, level_count: u32) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        data[..12].copy_from_slice(&IDENTIFIER);
+        data[12..16].copy_from_slice(&(vk::Format::R8G8B8A8_UNORM.as_raw() as u32).to_le_bytes());
+        data[20..24].copy_from_slice(&width.to_le_bytes());
+        data[24..28].copy_from_slice(&height.to_le_bytes());
+        data[36..40].copy_from_slice(&1u32.to_le_bytes());
+        data[40..44].copy_from_slice(&level_count.to_le_bytes());
+
+        let entries = level_count.clamp(1, 4);
+        let data_offset = HEADER_SIZE + entries as usize * LEVEL_INDEX_ENTRY_SIZE;
+        for level in 0..entries {
+            let length = ((width >> level).max(1) * (height >> level).max(1) * 4) as u64;
+            data.extend_from_slice(&(data_offset as u64).to_le_bytes());
+            data.extend_from_slice(&length.to_le_bytes());
+            data.extend_from_slice(&length.to_le_bytes());
+        }
+        data.resize(data_offset + (width * height * 4) as usize, 0x7F);
+        data
+    }
+
+    fn set_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn parses_valid_texture() {
+        let texture = parse(&texture(4, 4, 1)).unwrap();
+        assert_eq!((texture.width, texture.height, texture.depth), (4, 4, 1));
+        assert_eq!(texture.format, vk::Format::R8G8B8A8_UNORM);
+        assert_eq!(texture.levels, vec![vec![0x7F; 64]]);
+    }
+
+    #[test]
+    fn rejects_too_many_levels() {
+        // A 4x4 texture has 3 levels
+        assert!(parse(&texture(4, 4, 4)).is_err());
+        assert!(parse(&texture(4, 4, 33)).is_err());
+        assert!(parse(&texture(4, 4, u32::MAX)).is_err());
+
+        let mut data = texture(4, 4, 1);
+        data[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_level_ranges() {
+        let mut data = texture(4, 4, 1);
+        set_u64(&mut data, HEADER_SIZE, u64::MAX - 8);
+        assert!(parse(&data).is_err());
+
+        let mut data = texture(4, 4, 1);
+        set_u64(&mut data, HEADER_SIZE + 8, u64::MAX);
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let valid = texture(4, 4, 1);
+        for length in 0..valid.len() {
+            assert!(parse(&valid[..length]).is_err());
+        }
+        assert!(parse(&texture(0, 4, 1)).is_err());
+        assert!(parse(&texture(4, 0, 1)).is_err());
+
+        // Every header and level index field set to extreme values must fail cleanly
+        for offset in (12..HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE).step_by(4) {
+            for value in [0, 1, 6, 33, 0x8000_0000, u32::MAX] {
+                let mut data = valid.clone();
+                data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+                let _ = parse(&data);
+            }
+        }
+    }
+}
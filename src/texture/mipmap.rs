@@ -0,0 +1,467 @@
+use std::{
+    f32::consts::PI,
+    hash::{Hash, Hasher},
+};
+
+use ash::vk;
+
+use super::{f16_to_f32, f32_to_f16};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+    Box,
+    Kaiser,
+    Lanczos,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MipOptions {
+    pub filter: MipFilter,
+    // Alpha test reference of cutout textures, the alpha of every level is scaled so that the
+    // same fraction of texels passes the test as in the full resolution image
+    pub alpha_cutoff: Option<f32>,
+}
+
+// The cutoff is compared by its bits so that options can be used in hash map keys
+impl PartialEq for MipOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.filter == other.filter
+            && self.alpha_cutoff.map(f32::to_bits) == other.alpha_cutoff.map(f32::to_bits)
+    }
+}
+
+impl Eq for MipOptions {}
+
+impl Hash for MipOptions {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.filter.hash(state);
+        self.alpha_cutoff.map(f32::to_bits).hash(state);
+    }
+}
+
+impl Default for MipOptions {
+    fn default() -> Self {
+        MipOptions {
+            filter: MipFilter::Kaiser,
+            alpha_cutoff: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Unorm8,
+    Unorm16,
+    Float16,
+    Float32,
+}
+
+struct PixelLayout {
+    channels: usize,
+    encoding: Encoding,
+    srgb: bool,
+}
+
+// Number of levels down to 1x1, the smaller side stays at 1 once it is reached
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+// Returns the full mip chain starting with a copy of `level`, or None for formats the
+// generator does not support (block compressed and packed formats)
+pub fn generate_mip_chain(
+    level: &[u8],
+    width: u32,
+    height: u32,
+    format: vk::Format,
+    options: MipOptions,
+) -> Option<Vec<Vec<u8>>> {
+    let layout = pixel_layout(format)?;
+    // Coverage is measured on the last channel, the alpha of gray-alpha textures is stored in G
+    let alpha_channel = match layout.channels {
+        2 | 4 => Some(layout.channels - 1),
+        _ => None,
+    };
+
+    let mut source = decode(level, &layout);
+    let reference_coverage = match (options.alpha_cutoff, alpha_channel) {
+        (Some(cutoff), Some(channel)) => Some((
+            cutoff,
+            alpha_coverage(&source, layout.channels, channel, cutoff, 1.0),
+        )),
+        _ => None,
+    };
+
+    let mut levels = Vec::with_capacity(mip_level_count(width, height) as usize);
+    levels.push(level.to_vec());
+    let (mut width, mut height) = (width, height);
+    while width > 1 || height > 1 {
+        let target_width = (width / 2).max(1);
+        let target_height = (height / 2).max(1);
+        let horizontal = resample(
+            &source,
+            layout.channels,
+            (width, height),
+            (target_width, height),
+            options.filter,
+        );
+        source = resample(
+            &horizontal,
+            layout.channels,
+            (target_width, height),
+            (target_width, target_height),
+            options.filter,
+        );
+        width = target_width;
+        height = target_height;
+
+        // The next level is filtered from the unscaled values so that the scaling of alpha does
+        // not compound down the chain
+        match (reference_coverage, alpha_channel) {
+            (Some((cutoff, coverage)), Some(channel)) => {
+                let mut scaled = source.clone();
+                scale_alpha_to_coverage(&mut scaled, layout.channels, channel, cutoff, coverage);
+                levels.push(encode(&scaled, &layout));
+            }
+            _ => levels.push(encode(&source, &layout)),
+        }
+    }
+    Some(levels)
+}
+
+fn pixel_layout(format: vk::Format) -> Option<PixelLayout> {
+    use vk::Format as F;
+
+    let layout = |channels, encoding, srgb| {
+        Some(PixelLayout {
+            channels,
+            encoding,
+            srgb,
+        })
+    };
+    match format {
+        F::R8_UNORM => layout(1, Encoding::Unorm8, false),
+        F::R8_SRGB => layout(1, Encoding::Unorm8, true),
+        F::R8G8_UNORM => layout(2, Encoding::Unorm8, false),
+        F::R8G8_SRGB => layout(2, Encoding::Unorm8, true),
+        F::R8G8B8A8_UNORM | F::B8G8R8A8_UNORM => layout(4, Encoding::Unorm8, false),
+        F::R8G8B8A8_SRGB | F::B8G8R8A8_SRGB => layout(4, Encoding::Unorm8, true),
+        F::R16_UNORM => layout(1, Encoding::Unorm16, false),
+        F::R16G16_UNORM => layout(2, Encoding::Unorm16, false),
+        F::R16G16B16A16_UNORM => layout(4, Encoding::Unorm16, false),
+        F::R16_SFLOAT => layout(1, Encoding::Float16, false),
+        F::R16G16_SFLOAT => layout(2, Encoding::Float16, false),
+        F::R16G16B16A16_SFLOAT => layout(4, Encoding::Float16, false),
+        F::R32_SFLOAT => layout(1, Encoding::Float32, false),
+        F::R32G32_SFLOAT => layout(2, Encoding::Float32, false),
+        F::R32G32B32A32_SFLOAT => layout(4, Encoding::Float32, false),
+        _ => None,
+    }
+}
+
+// sRGB data is filtered as linear values, the alpha channel of RGBA formats is always linear
+fn decode(data: &[u8], layout: &PixelLayout) -> Vec<f32> {
+    let linear = |value: f32, channel: usize| {
+        if layout.srgb && channel != 3 {
+            srgb_to_linear(value)
+        } else {
+            value
+        }
+    };
+    match layout.encoding {
+        Encoding::Unorm8 => data
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| linear(value as f32 / 255.0, index % layout.channels))
+            .collect(),
+        Encoding::Unorm16 => data
+            .chunks_exact(2)
+            .map(|value| u16::from_le_bytes([value[0], value[1]]) as f32 / 65535.0)
+            .collect(),
+        Encoding::Float16 => data
+            .chunks_exact(2)
+            .map(|value| f16_to_f32(u16::from_le_bytes([value[0], value[1]])))
+            .collect(),
+        Encoding::Float32 => data
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect(),
+    }
+}
+
+fn encode(values: &[f32], layout: &PixelLayout) -> Vec<u8> {
+    match layout.encoding {
+        Encoding::Unorm8 => values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                let value = if layout.srgb && index % layout.channels != 3 {
+                    linear_to_srgb(value.clamp(0.0, 1.0))
+                } else {
+                    value.clamp(0.0, 1.0)
+                };
+                (value * 255.0).round() as u8
+            })
+            .collect(),
+        Encoding::Unorm16 => values
+            .iter()
+            .flat_map(|&value| ((value.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes())
+            .collect(),
+        Encoding::Float16 => values
+            .iter()
+            .flat_map(|&value| f32_to_f16(value).to_le_bytes())
+            .collect(),
+        Encoding::Float32 => values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect(),
+    }
+}
+
+// Resamples along the axis whose size changes, edges are clamped
+fn resample(
+    source: &[f32],
+    channels: usize,
+    (width, height): (u32, u32),
+    (target_width, target_height): (u32, u32),
+    filter: MipFilter,
+) -> Vec<f32> {
+    let horizontal = target_width != width;
+    let weights = if horizontal {
+        contributions(width, target_width, filter)
+    } else {
+        contributions(height, target_height, filter)
+    };
+
+    let mut target = vec![0.0; (target_width * target_height) as usize * channels];
+    for y in 0..target_height as usize {
+        for x in 0..target_width as usize {
+            let offset = (y * target_width as usize + x) * channels;
+            let taps = if horizontal { &weights[x] } else { &weights[y] };
+            for &(position, weight) in taps {
+                let source_offset = if horizontal {
+                    (y * width as usize + position) * channels
+                } else {
+                    (position * width as usize + x) * channels
+                };
+                for channel in 0..channels {
+                    target[offset + channel] += source[source_offset + channel] * weight;
+                }
+            }
+        }
+    }
+    target
+}
+
+// Normalized source taps of every target texel
+fn contributions(size: u32, target_size: u32, filter: MipFilter) -> Vec<Vec<(usize, f32)>> {
+    let scale = size as f32 / target_size as f32;
+    let radius = filter.radius() * scale;
+    (0..target_size)
+        .map(|position| {
+            let center = (position as f32 + 0.5) * scale;
+            let first = (center - radius).floor() as i64;
+            let last = (center + radius).ceil() as i64;
+            let mut taps: Vec<(usize, f32)> = (first..=last)
+                .filter_map(|source| {
+                    let weight = filter.weight((source as f32 + 0.5 - center) / scale);
+                    let source = source.clamp(0, size as i64 - 1) as usize;
+                    (weight != 0.0).then_some((source, weight))
+                })
+                .collect();
+            let total: f32 = taps.iter().map(|(_, weight)| weight).sum();
+            for (_, weight) in &mut taps {
+                *weight /= total;
+            }
+            taps
+        })
+        .collect()
+}
+
+impl MipFilter {
+    fn radius(self) -> f32 {
+        match self {
+            MipFilter::Box => 0.5,
+            MipFilter::Kaiser | MipFilter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let radius = self.radius();
+        if x.abs() > radius {
+            return 0.0;
+        }
+        match self {
+            MipFilter::Box => 1.0,
+            MipFilter::Lanczos => sinc(x) * sinc(x / radius),
+            MipFilter::Kaiser => {
+                const ALPHA: f32 = 4.0;
+                let t = x / radius;
+                sinc(x) * bessel_i0(ALPHA * (1.0 - t * t).sqrt()) / bessel_i0(ALPHA)
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Modified Bessel function of the first kind, evaluated with its power series
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..32 {
+        term *= half / k as f32;
+        let squared = term * term;
+        sum += squared;
+        if squared < sum * 1e-8 {
+            break;
+        }
+    }
+    sum
+}
+
+fn alpha_coverage(values: &[f32], channels: usize, alpha: usize, cutoff: f32, scale: f32) -> f32 {
+    let pixels = values.chunks_exact(channels);
+    let count = pixels.len();
+    let covered = pixels
+        .filter(|pixel| (pixel[alpha] * scale).min(1.0) > cutoff)
+        .count();
+    covered as f32 / count as f32
+}
+
+fn scale_alpha_to_coverage(
+    values: &mut [f32],
+    channels: usize,
+    alpha: usize,
+    cutoff: f32,
+    coverage: f32,
+) {
+    // Coverage only grows with the scale, so a binary search finds the closest match
+    let (mut low, mut high) = (0.0f32, 4.0f32);
+    for _ in 0..16 {
+        let middle = (low + high) / 2.0;
+        if alpha_coverage(values, channels, alpha, cutoff, middle) < coverage {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    for pixel in values.chunks_exact_mut(channels) {
+        pixel[alpha] = (pixel[alpha] * high).clamp(0.0, 1.0);
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [MipFilter; 3] = [MipFilter::Box, MipFilter::Kaiser, MipFilter::Lanczos];
+
+    // Mip chain of a single row of R32 values
+    fn mip_chain(row: &[f32], filter: MipFilter) -> Vec<Vec<f32>> {
+        let data: Vec<u8> = row.iter().flat_map(|value| value.to_le_bytes()).collect();
+        let options = MipOptions {
+            filter,
+            ..Default::default()
+        };
+        generate_mip_chain(&data, row.len() as u32, 1, vk::Format::R32_SFLOAT, options)
+            .unwrap()
+            .iter()
+            .map(|level| {
+                level
+                    .chunks_exact(4)
+                    .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn level_sizes() {
+        assert_eq!(mip_level_count(32, 1), 6);
+        assert_eq!(mip_level_count(1, 1), 1);
+        for filter in FILTERS {
+            let sizes: Vec<usize> = mip_chain(&[0.5; 32], filter).iter().map(Vec::len).collect();
+            assert_eq!(sizes, [32, 16, 8, 4, 2, 1]);
+        }
+    }
+
+    #[test]
+    fn constant_image_stays_constant() {
+        for filter in FILTERS {
+            for level in mip_chain(&[0.25; 32], filter) {
+                assert!(level.iter().all(|value| (value - 0.25).abs() < 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_keeps_its_slope() {
+        let gradient: Vec<f32> = (0..32).map(|x| x as f32).collect();
+        for filter in FILTERS {
+            let level = &mip_chain(&gradient, filter)[1];
+            // The wide filters clamp at the edges, interior texels see the full gradient
+            let interior = if filter == MipFilter::Box {
+                0..16
+            } else {
+                3..13
+            };
+            for x in interior {
+                let expected = 2.0 * x as f32 + 0.5;
+                assert!(
+                    (level[x] - expected).abs() < 1e-3,
+                    "{:?} {} {}",
+                    filter,
+                    level[x],
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn step_edge() {
+        let step: Vec<f32> = (0..32).map(|x| if x < 15 { 0.0 } else { 1.0 }).collect();
+        let levels: Vec<Vec<f32>> = FILTERS
+            .iter()
+            .map(|&filter| mip_chain(&step, filter)[1].clone())
+            .collect();
+
+        // The texel centered on the edge is halfway for every filter
+        for level in &levels {
+            assert!((level[7] - 0.5).abs() < 1e-5);
+        }
+
+        // Box filtering never leaves the range of its inputs, the windowed sinc filters ring
+        // next to the edge, Lanczos more than Kaiser
+        let undershoot = |level: &[f32]| -level.iter().cloned().fold(0.0, f32::min);
+        let (box_level, kaiser, lanczos) = (&levels[0], &levels[1], &levels[2]);
+        assert!(box_level.iter().all(|value| (0.0..=1.0).contains(value)));
+        assert!(undershoot(kaiser) > 0.0);
+        assert!(undershoot(lanczos) > undershoot(kaiser));
+    }
+}
@@ -1,22 +1,44 @@
 use std::{
-    fs::File,
-    io::{Cursor, Read},
-    path::Path,
+    env,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use crate::{
     app::App,
-    cgm::{Mat4, Vec2, Vec3, Vertex},
+    assets::{AssetManager, Handle, Material},
+    cgm::{Mat4, Vec3},
+    mesh::{projected_screen_size, LodSelector, Mesh, MeshRange, Primitive},
+    streaming::{ModelBuffers, StreamingProgress},
+    texture::{HdrImage, MipFilter, MipOptions},
     vulkan::{
-        VkBuffer, VkCommandPool, VkContext, VkDescriptorPool, VkDescriptorSetLayout, VkDevice,
-        VkImage, VkPipeline, VkRenderPass, VkSampler, VkSettings, VkShaderModule, VkSurface,
-        VkSwapChain, VkTexture,
+        CubemapSource, GraphPass, GraphResource, MipGeneration, PassDesc, SamplerDesc,
+        TextureColorSpace, VkBuffer, VkCommandPool, VkContext, VkDeletionQueue, VkDescriptorPool,
+        VkDescriptorSetLayout, VkDevice, VkDeviceRequirements, VkImage, VkPipeline, VkRenderGraph,
+        VkSampler, VkSemaphoreValue, VkSettings, VkShaderModule, VkSurface, VkSwapChain, VkTexture,
+        VkTimelineSemaphore,
     },
 };
-use ash::vk;
-use winit::{dpi::PhysicalSize, window::Window};
+use ash::{extensions::khr::Synchronization2, vk};
+use winit::{dpi::PhysicalSize, event::VirtualKeyCode, window::Window};
+
+const FIELD_OF_VIEW: f32 = 0.785;
+const NEAR_CLIP: f32 = 0.1;
+const FAR_CLIP: f32 = 10.0;
+const CAMERA_EYE: Vec3 = Vec3::new(0.0, 2.2, 0.9);
+const CAMERA_TARGET: Vec3 = Vec3::new(0.0, 0.0, 0.4);
+const CAMERA_UP: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+const MAX_LOD_LEVELS: usize = 4;
+const LOD_FULL_DETAIL_SIZE: f32 = 600.0;
+const LOD_HYSTERESIS: f32 = 0.15;
+
+// The scene is rendered into an HDR target and tonemapped into the swap-chain image
+const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const EXPOSURE_STEP: f32 = 0.5;
+// Frames normally finish within milliseconds, one taking this long usually means the GPU hung
+const FRAME_STALL_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -26,12 +48,55 @@ struct UniformBufferObject {
     proj: Mat4,
 }
 
-pub struct Model(Vec<Vertex>, Vec<u32>);
+// Operators understood by the tonemapping shader
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tonemapper {
+    Reinhard = 0,
+    Aces = 1,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TonemapParams {
+    exposure: f32,
+    tonemapper: u32,
+    encode_srgb: u32,
+}
+
+// The scene is rendered into an HDR image, which the tonemap pass reads while writing the
+// swap-chain image
+struct FrameGraph {
+    graph: VkRenderGraph,
+    scene_pass: GraphPass,
+    tonemap_pass: GraphPass,
+    hdr_image: GraphResource,
+}
+
+// Cubemap drawn behind the scene, see `load_skybox`
+struct Skybox {
+    texture: VkTexture,
+    sampler: Arc<VkSampler>,
+    vertex_shader: Handle<VkShaderModule>,
+    fragment_shader: Handle<VkShaderModule>,
+}
 
 pub struct TutorialAppSwapChainContext {
     uniform_buffers: Vec<VkBuffer>,
+    indirect_buffers: Vec<VkBuffer>,
     pipeline: VkPipeline,
+    // The pools own the descriptor sets of the context
+    _descriptor_pool: VkDescriptorPool,
     descriptor_sets: Vec<vk::DescriptorSet>,
+    skybox_pipeline: Option<VkPipeline>,
+    skybox_descriptor_sets: Vec<vk::DescriptorSet>,
+    tonemap_buffers: Vec<VkBuffer>,
+    tonemap_pipeline: VkPipeline,
+    _tonemap_descriptor_pool: VkDescriptorPool,
+    tonemap_descriptor_sets: Vec<vk::DescriptorSet>,
+    // Images whose descriptor set and commands are rewritten before their next use
+    stale_images: Vec<bool>,
+    // Number of the frame last submitted with each set of frame semaphores
+    frame_numbers: Vec<u64>,
     current_frame: usize,
     swap_chain: VkSwapChain,
 }
@@ -39,109 +104,344 @@ pub struct TutorialAppSwapChainContext {
 pub struct TutorialApp {
     start_time: Instant,
     swap_chain_context: Option<TutorialAppSwapChainContext>,
-    sampler: VkSampler,
-    texture_image: VkTexture,
-    index_buffer: VkBuffer,
-    vertex_buffer: VkBuffer,
-    model: Model,
-    descriptor_pool: VkDescriptorPool,
+    material: Handle<Material>,
+    // The chalet followed by the generated primitives, cycled through with M
+    models: Vec<Handle<ModelBuffers>>,
+    model_index: usize,
+    vertex_shader: Handle<VkShaderModule>,
+    fragment_shader: Handle<VkShaderModule>,
+    tonemap_vertex_shader: Handle<VkShaderModule>,
+    tonemap_fragment_shader: Handle<VkShaderModule>,
+    skybox: Option<Skybox>,
+    assets: AssetManager,
+    deletion_queue: VkDeletionQueue,
+    // Reaches the number of each frame from `deletion_queue` once it has finished on the GPU
+    frame_timeline: VkTimelineSemaphore,
+    tonemap_sampler: Arc<VkSampler>,
+    fullscreen_triangle: VkBuffer,
+    lod_selector: LodSelector,
     descriptor_set_layout: VkDescriptorSetLayout,
-    vertex_shader_module: VkShaderModule,
-    fragment_shader_module: VkShaderModule,
-    render_pass: VkRenderPass,
+    tonemap_descriptor_set_layout: VkDescriptorSetLayout,
+    exposure_stops: f32,
+    tonemapper: Tonemapper,
+    frame_graph: FrameGraph,
     swap_chain_format: vk::SurfaceFormatKHR,
     swap_chain_present_mode: vk::PresentModeKHR,
     swap_image_count: u32,
     command_pool: Arc<VkCommandPool>,
-    depth_format: vk::Format,
     msaa_samples: vk::SampleCountFlags,
     window_size: PhysicalSize<u32>,
     vk_context: VkContext,
 }
 
 impl TutorialApp {
-    pub fn new(window: &Window) -> TutorialApp {
-        let vk_settings = VkSettings { validation: true };
-        let vk_context = VkContext::new(&window, &vk_settings);
+    pub fn new(window: &Window, vk_settings: &VkSettings) -> TutorialApp {
+        let vk_context = VkContext::new(
+            &window,
+            vk_settings,
+            &Self::device_requirements(vk_settings),
+        );
         let device = &vk_context.device;
 
+        let features = &device.enabled_features().v1_0;
+        log::info!(
+            "Texture compression BC: {}, ETC2: {}, ASTC: {}",
+            features.texture_compression_bc == vk::TRUE,
+            features.texture_compression_etc2 == vk::TRUE,
+            features.texture_compression_astc_ldr == vk::TRUE
+        );
+        log::info!(
+            "Synchronization2: {}",
+            device.is_extension_enabled(Synchronization2::name())
+        );
+
         let msaa_samples = device.get_max_usable_sample_count();
         log::info!("Using {:?} MSAA samples", msaa_samples);
 
         log::info!("Creating swap-chain command pool");
-        let command_pool = Arc::new(VkCommandPool::new(&device, device.graphics_queue_family));
+        // Swap-chain command buffers are re-recorded when assets change
+        let command_pool = Arc::new(VkCommandPool::new_with_flags(
+            &device,
+            device.queues.graphics.family,
+            vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        ));
+        command_pool.set_name("swap-chain command pool");
+
+        let frame_timeline = VkTimelineSemaphore::new(device, 0);
+        frame_timeline.set_name("frame timeline");
 
         let depth_format = VkImage::find_depth_format(&vk_context.physical_device);
         log::info!("Choosing depth format {:?}", depth_format);
 
         let (swap_chain_format, swap_chain_present_mode, swap_image_count) =
-            Self::choose_swap_chain_format(device, &vk_context.surface);
-
-        log::info!("Creating render pass");
-        let render_pass = VkRenderPass::new(
-            &device,
-            swap_chain_format.format,
-            depth_format,
-            msaa_samples,
-        );
-
-        let vertex_shader_module = VkShaderModule::new_from_file(
-            &device,
+            Self::choose_swap_chain_format(device, &vk_context.surface, vk_settings.srgb_output);
+
+        log::info!("Creating render graph");
+        let frame_graph =
+            Self::create_frame_graph(device, swap_chain_format.format, depth_format, msaa_samples);
+
+        let mut assets = AssetManager::new(&device, &command_pool, Self::asset_roots());
+        let vertex_shader =
+            assets.load_shader("shader/vert.spv", vk::ShaderStageFlags::VERTEX, "main");
+        let fragment_shader =
+            assets.load_shader("shader/frag.spv", vk::ShaderStageFlags::FRAGMENT, "main");
+        let tonemap_vertex_shader = assets.load_shader(
+            "shader/tonemap_vert.spv",
             vk::ShaderStageFlags::VERTEX,
-            "shader/vert.spv",
             "main",
         );
-        let fragment_shader_module = VkShaderModule::new_from_file(
-            &device,
+        let tonemap_fragment_shader = assets.load_shader(
+            "shader/tonemap_frag.spv",
             vk::ShaderStageFlags::FRAGMENT,
-            "shader/frag.spv",
             "main",
         );
-        let descriptor_set_layout = Self::create_descriptor_set_layout(&vk_context);
-        let descriptor_pool = Self::create_descriptor_pool(&vk_context, swap_image_count);
+        let material = assets.load_material(
+            "assets/chalet.jpg",
+            TextureColorSpace::Srgb,
+            Self::mip_generation(),
+            Self::create_sampler(&vk_context),
+        );
+        let mut models = vec![assets.load_model("assets/chalet.obj", MAX_LOD_LEVELS)];
+        models.extend(
+            Primitive::ALL
+                .iter()
+                .map(|&primitive| assets.load_primitive(primitive, MAX_LOD_LEVELS)),
+        );
+
+        let skybox = Self::load_skybox(&vk_context, &command_pool, &mut assets);
 
-        let model = Self::load_model();
-        let vertex_buffer = Self::create_vertex_buffer(&vk_context, &command_pool, &model.0);
-        let index_buffer = Self::create_index_buffer(&vk_context, &command_pool, &model.1);
-        let texture_image = Self::create_texture_image(&vk_context, &command_pool);
-        let sampler = Self::create_sampler(&vk_context, &texture_image);
+        // The skybox uses the layout of the scene with its own sets
+        let descriptor_set_layout = Self::create_descriptor_set_layout(&vk_context);
+        descriptor_set_layout.set_name("scene descriptor set layout");
+        let tonemap_descriptor_set_layout = Self::create_tonemap_descriptor_set_layout(&vk_context);
+        tonemap_descriptor_set_layout.set_name("tonemap descriptor set layout");
+
+        let lod_selector = Self::create_lod_selector(assets.model(&models[0]));
+        let tonemap_sampler = Self::create_tonemap_sampler(&vk_context);
+        let fullscreen_triangle = VkBuffer::new_device_local(
+            &vk_context.device,
+            &command_pool,
+            &vk_context.device.queues.graphics,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            &Mesh::fullscreen_triangle().vertices,
+        );
+        fullscreen_triangle.set_name("fullscreen triangle");
         let window_size = window.inner_size();
 
         let mut app = TutorialApp {
             start_time: Instant::now(),
             swap_chain_context: None,
-            sampler,
-            texture_image,
-            index_buffer,
-            vertex_buffer,
-            model,
+            material,
+            models,
+            model_index: 0,
+            vertex_shader,
+            fragment_shader,
+            tonemap_vertex_shader,
+            tonemap_fragment_shader,
+            skybox,
+            assets,
+            deletion_queue: VkDeletionQueue::new(),
+            frame_timeline,
+            tonemap_sampler,
+            fullscreen_triangle,
+            lod_selector,
             descriptor_set_layout,
-            descriptor_pool,
-            vertex_shader_module,
-            fragment_shader_module,
-            render_pass,
+            tonemap_descriptor_set_layout,
+            exposure_stops: 0.0,
+            tonemapper: Tonemapper::Aces,
+            frame_graph,
             swap_chain_format,
             swap_chain_present_mode,
             swap_image_count,
             command_pool,
             msaa_samples,
-            depth_format,
             vk_context,
             window_size,
         };
-        app.swap_chain_context = Some(app.create_swap_chain(app.window_size));
+        app.swap_chain_context = Some(app.create_swap_chain(app.window_size, None));
         app.record_commands();
 
         app
     }
 
+    // Relative asset paths are looked up in the directories of `ASSET_ROOTS`, next to the
+    // executable and in the source tree, in that order
+    fn asset_roots() -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = env::var_os("ASSET_ROOTS")
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        if let Some(directory) = env::current_exe()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_owned))
+        {
+            roots.push(directory);
+        }
+        roots.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+        roots
+    }
+
+    // `SKYBOX` shows a cubemap behind the scene, given as six comma separated face images in
+    // the order +X, -X, +Y, -Y, +Z, -Z, as a single cross image or as an equirectangular HDR or
+    // OpenEXR image
+    fn load_skybox(
+        context: &VkContext,
+        command_pool: &Arc<VkCommandPool>,
+        assets: &mut AssetManager,
+    ) -> Option<Skybox> {
+        let value = env::var("SKYBOX").ok()?;
+        let paths: Vec<&str> = value.split(',').map(str::trim).collect();
+        let source = match paths[..] {
+            [path] if HdrImage::is_hdr_file(path) => CubemapSource::Equirectangular {
+                path,
+                face_size: None,
+            },
+            [path] => CubemapSource::Cross(path),
+            [px, nx, py, ny, pz, nz] => CubemapSource::Faces([px, nx, py, ny, pz, nz]),
+            _ => {
+                log::warn!(
+                    "Ignoring SKYBOX={}, expected one image or six comma separated faces",
+                    value
+                );
+                return None;
+            }
+        };
+
+        // HDR panoramas store linear radiance, LDR skyboxes are authored in sRGB
+        let color_space = match source {
+            CubemapSource::Equirectangular { .. } => TextureColorSpace::Linear,
+            _ => TextureColorSpace::Srgb,
+        };
+        let texture = VkImage::load_cubemap(
+            &context.device,
+            source,
+            color_space,
+            command_pool,
+            &context.device.queues.graphics,
+        );
+        let sampler = context.samplers.get(SamplerDesc {
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ..Default::default()
+        });
+        Some(Skybox {
+            texture,
+            sampler,
+            vertex_shader: assets.load_shader(
+                "shader/skybox_vert.spv",
+                vk::ShaderStageFlags::VERTEX,
+                "main",
+            ),
+            fragment_shader: assets.load_shader(
+                "shader/skybox_frag.spv",
+                vk::ShaderStageFlags::FRAGMENT,
+                "main",
+            ),
+        })
+    }
+
+    // `MIP_FILTER=box|kaiser|lanczos` generates the mip levels of textures on the CPU with the
+    // given filter instead of blitting them on the GPU
+    fn mip_generation() -> MipGeneration {
+        let value = match env::var("MIP_FILTER") {
+            Ok(value) => value,
+            Err(_) => return MipGeneration::Gpu,
+        };
+        let filter = match value.trim().to_lowercase().as_str() {
+            "box" => MipFilter::Box,
+            "kaiser" => MipFilter::Kaiser,
+            "lanczos" => MipFilter::Lanczos,
+            _ => {
+                log::warn!(
+                    "Ignoring MIP_FILTER={}, expected box, kaiser or lanczos",
+                    value
+                );
+                return MipGeneration::Gpu;
+            }
+        };
+        MipGeneration::Cpu(MipOptions {
+            filter,
+            ..Default::default()
+        })
+    }
+
+    // Compressed texture formats are enabled when available, other textures are decoded on the
+    // CPU. Barriers fall back to the core commands without synchronization2.
+    fn device_requirements(vk_settings: &VkSettings) -> VkDeviceRequirements {
+        let mut requirements = VkDeviceRequirements::new();
+        requirements.required_features.v1_0.sampler_anisotropy = vk::TRUE;
+        let optional = &mut requirements.optional_features.v1_0;
+        optional.texture_compression_bc = vk::TRUE;
+        optional.texture_compression_etc2 = vk::TRUE;
+        optional.texture_compression_astc_ldr = vk::TRUE;
+        if vk_settings.synchronization2 {
+            requirements
+                .optional_features
+                .synchronization2
+                .synchronization2 = vk::TRUE;
+            requirements
+                .optional_extensions
+                .push(Synchronization2::name());
+        }
+        requirements
+    }
+
+    // Without multisampling the scene is rendered straight into the HDR image
+    fn create_frame_graph(
+        device: &Arc<VkDevice>,
+        swap_chain_format: vk::Format,
+        depth_format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+    ) -> FrameGraph {
+        let mut graph = VkRenderGraph::new(device);
+        let hdr_image = graph.create_image("HDR image", HDR_FORMAT, vk::SampleCountFlags::TYPE_1);
+        let depth_image = graph.create_image("depth image", depth_format, msaa_samples);
+        let swap_chain_image = graph.import_swap_chain("swap-chain image", swap_chain_format);
+
+        let clear_color = Some([0.0, 0.0, 0.0, 1.0]);
+        let scene_desc = if msaa_samples == vk::SampleCountFlags::TYPE_1 {
+            PassDesc {
+                colors: vec![(hdr_image, clear_color)],
+                depth: Some((depth_image, Some(1.0))),
+                ..Default::default()
+            }
+        } else {
+            let color_image = graph.create_image("MSAA color image", HDR_FORMAT, msaa_samples);
+            PassDesc {
+                colors: vec![(color_image, clear_color)],
+                depth: Some((depth_image, Some(1.0))),
+                resolves: vec![hdr_image],
+                ..Default::default()
+            }
+        };
+        let scene_pass = graph.add_pass("scene", scene_desc);
+        // Every texel of the swap-chain image is written, so it is neither loaded nor cleared
+        let tonemap_pass = graph.add_pass(
+            "tonemap",
+            PassDesc {
+                colors: vec![(swap_chain_image, None)],
+                reads: vec![hdr_image],
+                ..Default::default()
+            },
+        );
+        graph.compile();
+
+        FrameGraph {
+            graph,
+            scene_pass,
+            tonemap_pass,
+            hdr_image,
+        }
+    }
+
     fn choose_swap_chain_format(
         device: &VkDevice,
         surface: &VkSurface,
+        srgb_output: bool,
     ) -> (vk::SurfaceFormatKHR, vk::PresentModeKHR, u32) {
         let surface_caps =
             surface.get_physical_device_surface_capabilities(&device.physical_device);
-        let format = Self::choose_swapchain_surface_format(&surface_caps.formats);
+        let format = Self::choose_swapchain_surface_format(&surface_caps.formats, srgb_output);
         log::info!("Choosing swap-chain image format: {:?}", format);
         let present_mode = Self::choose_swapchain_surface_present_mode(&surface_caps.present_modes);
         log::info!("Choosing swap-chain presentation mode: {:?}", present_mode);
@@ -153,16 +453,36 @@ impl TutorialApp {
 
     fn choose_swapchain_surface_format(
         available_formats: &[vk::SurfaceFormatKHR],
+        srgb_output: bool,
     ) -> vk::SurfaceFormatKHR {
-        *available_formats
+        let preferred_formats = if srgb_output {
+            [vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB]
+        } else {
+            [vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM]
+        };
+
+        *preferred_formats
             .iter()
-            .find(|format| {
-                format.format == vk::Format::B8G8R8A8_UNORM
-                    && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+            .find_map(|preferred| {
+                available_formats.iter().find(|format| {
+                    format.format == *preferred
+                        && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+                })
             })
             .unwrap_or(&available_formats[0])
     }
 
+    // The tonemapper writes linear colors, which have to be encoded manually for UNORM targets
+    fn needs_srgb_encoding(format: vk::SurfaceFormatKHR) -> bool {
+        let srgb_format = matches!(
+            format.format,
+            vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::A8B8G8R8_SRGB_PACK32
+        );
+        format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR && !srgb_format
+    }
+
     fn choose_swapchain_surface_present_mode(
         available_present_modes: &[vk::PresentModeKHR],
     ) -> vk::PresentModeKHR {
@@ -184,7 +504,11 @@ impl TutorialApp {
         preferred
     }
 
-    fn create_swap_chain(&mut self, size: PhysicalSize<u32>) -> TutorialAppSwapChainContext {
+    fn create_swap_chain(
+        &mut self,
+        size: PhysicalSize<u32>,
+        old_swap_chain: Option<&VkSwapChain>,
+    ) -> TutorialAppSwapChainContext {
         log::info!("Creating swap-chain");
 
         let mut swap_chain = VkSwapChain::new(
@@ -194,42 +518,95 @@ impl TutorialApp {
             self.swap_chain_present_mode,
             self.swap_image_count,
             &[size.width, size.height],
+            old_swap_chain,
         );
-        swap_chain.initialize_images(
-            2,
-            &self.render_pass,
-            self.depth_format,
-            self.msaa_samples,
-            &self.command_pool,
-            self.vk_context.device.graphics_queue,
-        );
+        swap_chain.set_name("swap-chain");
+        swap_chain.initialize_images(2, &self.command_pool);
+
+        // The graph images are created once per swap-chain image
+        let frame_graph = &mut self.frame_graph;
+        frame_graph
+            .graph
+            .resize(&swap_chain, &mut self.deletion_queue);
+        let hdr_views: Vec<_> = (0..swap_chain.images.len())
+            .map(|index| frame_graph.graph.image_view(frame_graph.hdr_image, index))
+            .collect();
 
         let context = &self.vk_context;
+
         let pipeline = self.create_pipeline(swap_chain.extent);
+        let tonemap_pipeline = self.create_tonemap_pipeline(swap_chain.extent);
         let uniform_buffers = Self::create_uniform_buffers(context, self.swap_image_count);
+        let indirect_buffers = Self::create_indirect_buffers(context, self.swap_image_count);
+        let material = self.assets.material(&self.material);
+        let descriptor_pool = Self::create_descriptor_pool(context, 2 * self.swap_image_count);
+        descriptor_pool.set_name("scene descriptor pool");
         let descriptor_sets = Self::create_descriptor_sets(
             &context.device,
-            &self.descriptor_pool,
+            &descriptor_pool,
             &self.descriptor_set_layout,
             &uniform_buffers,
-            &self.texture_image,
-            &self.sampler,
+            self.assets.texture(&material.base_color),
+            &material.sampler,
+        );
+        let (skybox_pipeline, skybox_descriptor_sets) = match &self.skybox {
+            Some(skybox) => (
+                Some(self.create_skybox_pipeline(skybox, swap_chain.extent)),
+                Self::create_descriptor_sets(
+                    &context.device,
+                    &descriptor_pool,
+                    &self.descriptor_set_layout,
+                    &uniform_buffers,
+                    &skybox.texture,
+                    &skybox.sampler,
+                ),
+            ),
+            None => (None, Vec::new()),
+        };
+        let tonemap_buffers = Self::create_tonemap_buffers(context, self.swap_image_count);
+        let tonemap_descriptor_pool =
+            Self::create_tonemap_descriptor_pool(context, self.swap_image_count);
+        tonemap_descriptor_pool.set_name("tonemap descriptor pool");
+        let tonemap_descriptor_sets = Self::create_tonemap_descriptor_sets(
+            &context.device,
+            &tonemap_descriptor_pool,
+            &self.tonemap_descriptor_set_layout,
+            &tonemap_buffers,
+            &hdr_views,
+            &self.tonemap_sampler,
         );
 
         TutorialAppSwapChainContext {
+            stale_images: vec![false; swap_chain.images.len()],
+            frame_numbers: vec![0; swap_chain.frame_count()],
             swap_chain,
             current_frame: 0,
             pipeline,
             uniform_buffers,
+            indirect_buffers,
+            _descriptor_pool: descriptor_pool,
             descriptor_sets,
+            skybox_pipeline,
+            skybox_descriptor_sets,
+            tonemap_buffers,
+            tonemap_pipeline,
+            _tonemap_descriptor_pool: tonemap_descriptor_pool,
+            tonemap_descriptor_sets,
         }
     }
 
+    // Frames in flight keep using the previous context, which is retired instead of waiting
+    // for them to finish
     fn recreate_swap_chain(&mut self, size: PhysicalSize<u32>) {
-        self.vk_context.device.wait_idle();
-        self.destroy_descriptor_sets();
-        self.swap_chain_context = None;
-        self.swap_chain_context = Some(self.create_swap_chain(size));
+        let old_context = self.swap_chain_context.take();
+        let context = self.create_swap_chain(
+            size,
+            old_context.as_ref().map(|context| &context.swap_chain),
+        );
+        self.swap_chain_context = Some(context);
+        if let Some(old_context) = old_context {
+            self.deletion_queue.retire(old_context);
+        }
         self.record_commands();
     }
 
@@ -239,55 +616,97 @@ impl TutorialApp {
             .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
             .stage_flags(vk::ShaderStageFlags::VERTEX);
+        let image_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+        let sampler_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(2)
+            .descriptor_type(vk::DescriptorType::SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+        VkDescriptorSetLayout::new(
+            &context.device,
+            &[
+                ubo_layout_binding.build(),
+                image_layout_binding.build(),
+                sampler_layout_binding.build(),
+            ],
+        )
+    }
+
+    fn create_tonemap_descriptor_set_layout(context: &VkContext) -> VkDescriptorSetLayout {
+        let image_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
         let sampler_layout_binding = vk::DescriptorSetLayoutBinding::builder()
             .binding(1)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER) // TODO Use combined or separate?
+            .descriptor_type(vk::DescriptorType::SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+        let params_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(2)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
             .stage_flags(vk::ShaderStageFlags::FRAGMENT);
         VkDescriptorSetLayout::new(
             &context.device,
-            &[ubo_layout_binding.build(), sampler_layout_binding.build()],
+            &[
+                image_layout_binding.build(),
+                sampler_layout_binding.build(),
+                params_layout_binding.build(),
+            ],
         )
     }
 
     fn create_pipeline(&self, extent: vk::Extent2D) -> VkPipeline {
-        VkPipeline::new(
+        let pipeline = VkPipeline::new(
             &self.vk_context.device,
             extent,
-            &self.render_pass,
-            &self.vertex_shader_module,
-            &self.fragment_shader_module,
+            self.frame_graph
+                .graph
+                .render_pass(self.frame_graph.scene_pass),
+            self.assets.shader(&self.vertex_shader),
+            self.assets.shader(&self.fragment_shader),
             &[self.descriptor_set_layout.handle],
             self.msaa_samples,
-        )
+        );
+        pipeline.set_name("scene pipeline");
+        pipeline
     }
 
-    fn create_vertex_buffer(
-        context: &VkContext,
-        command_pool: &Arc<VkCommandPool>,
-        vertices: &[Vertex],
-    ) -> VkBuffer {
-        VkBuffer::new_device_local(
-            &context.device,
-            &command_pool,
-            context.device.graphics_queue,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
-            vertices,
-        )
+    fn create_skybox_pipeline(&self, skybox: &Skybox, extent: vk::Extent2D) -> VkPipeline {
+        let pipeline = VkPipeline::new_background(
+            &self.vk_context.device,
+            extent,
+            self.frame_graph
+                .graph
+                .render_pass(self.frame_graph.scene_pass),
+            self.assets.shader(&skybox.vertex_shader),
+            self.assets.shader(&skybox.fragment_shader),
+            &[self.descriptor_set_layout.handle],
+            self.msaa_samples,
+        );
+        pipeline.set_name("skybox pipeline");
+        pipeline
     }
 
-    fn create_index_buffer(
-        context: &VkContext,
-        command_pool: &Arc<VkCommandPool>,
-        indices: &[u32],
-    ) -> VkBuffer {
-        VkBuffer::new_device_local(
-            &context.device,
-            &command_pool,
-            context.device.graphics_queue,
-            vk::BufferUsageFlags::INDEX_BUFFER,
-            indices,
-        )
+    fn create_tonemap_pipeline(&self, extent: vk::Extent2D) -> VkPipeline {
+        let pipeline = VkPipeline::new_full_screen(
+            &self.vk_context.device,
+            extent,
+            self.frame_graph
+                .graph
+                .render_pass(self.frame_graph.tonemap_pass),
+            self.assets.shader(&self.tonemap_vertex_shader),
+            self.assets.shader(&self.tonemap_fragment_shader),
+            &[self.tonemap_descriptor_set_layout.handle],
+        );
+        pipeline.set_name("tonemap pipeline");
+        pipeline
     }
 
     fn create_uniform_buffers(context: &VkContext, count: u32) -> Vec<VkBuffer> {
@@ -295,44 +714,145 @@ impl TutorialApp {
         log::info!("Creating {} uniform buffers", count);
 
         (0..count)
-            .map(|_| {
-                VkBuffer::new(
+            .map(|index| {
+                let buffer = VkBuffer::new(
+                    &context.device,
+                    vk::BufferUsageFlags::UNIFORM_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    size,
+                );
+                buffer.set_name(&format!("uniform buffer {}", index));
+                buffer
+            })
+            .collect()
+    }
+
+    fn create_indirect_buffers(context: &VkContext, count: u32) -> Vec<VkBuffer> {
+        let size = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u64;
+        log::info!("Creating {} indirect draw buffers", count);
+
+        (0..count)
+            .map(|index| {
+                let buffer = VkBuffer::new(
+                    &context.device,
+                    vk::BufferUsageFlags::INDIRECT_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    size,
+                );
+                buffer.set_name(&format!("indirect draw buffer {}", index));
+                buffer
+            })
+            .collect()
+    }
+
+    fn create_tonemap_buffers(context: &VkContext, count: u32) -> Vec<VkBuffer> {
+        let size = std::mem::size_of::<TonemapParams>() as u64;
+        log::info!("Creating {} tonemap parameter buffers", count);
+
+        (0..count)
+            .map(|index| {
+                let buffer = VkBuffer::new(
                     &context.device,
                     vk::BufferUsageFlags::UNIFORM_BUFFER,
                     vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
                     size,
-                )
+                );
+                buffer.set_name(&format!("tonemap parameter buffer {}", index));
+                buffer
             })
             .collect()
     }
 
-    fn update_uniform_buffer(buffer: &VkBuffer, extent: vk::Extent2D, elapsed_time: f32) {
+    fn model_matrix(elapsed_time: f32) -> Mat4 {
+        Mat4::rotate_z(-0.2 * elapsed_time)
+    }
+
+    fn view_matrix() -> Mat4 {
+        Mat4::look_at(&CAMERA_EYE, &CAMERA_TARGET, &CAMERA_UP)
+    }
+
+    fn update_uniform_buffer(buffer: &VkBuffer, extent: vk::Extent2D, model: Mat4) {
         let screen_width = extent.width as f32;
         let screen_height = extent.height as f32;
         let ubo = UniformBufferObject {
-            model: Mat4::rotate_z(-0.2 * elapsed_time),
-            view: Mat4::look_at(
-                &Vec3::new(0.0, 2.2, 0.9),
-                &Vec3::new(0.0, 0.0, 0.4),
-                &Vec3::new(0.0, 0.0, 1.0),
+            model,
+            view: Self::view_matrix(),
+            proj: Mat4::perspective(
+                FIELD_OF_VIEW,
+                screen_width / screen_height,
+                NEAR_CLIP,
+                FAR_CLIP,
             ),
-            proj: Mat4::perspective(0.785, screen_width / screen_height, 0.1, 10.0),
         };
 
         buffer.map_memory(&[ubo]);
     }
 
+    fn select_lod(
+        selector: &mut LodSelector,
+        bounding_sphere: &(Vec3, f32),
+        extent: vk::Extent2D,
+        model: &Mat4,
+    ) -> usize {
+        let (center, radius) = bounding_sphere;
+        let view_center = Self::view_matrix() * (model * center.homogenous());
+        let distance = -view_center.z();
+        let screen_size =
+            projected_screen_size(*radius, distance, FIELD_OF_VIEW, extent.height as f32);
+
+        let previous = selector.current();
+        let lod = selector.select(screen_size);
+        if lod != previous {
+            log::debug!("Switching to LOD {} ({:.0} px)", lod, screen_size);
+        }
+        lod
+    }
+
+    fn update_indirect_buffer(buffer: &VkBuffer, lod: &MeshRange) {
+        let command = vk::DrawIndexedIndirectCommand {
+            index_count: lod.index_count,
+            instance_count: 1,
+            first_index: lod.first_index,
+            vertex_offset: lod.vertex_offset,
+            first_instance: 0,
+        };
+
+        buffer.map_memory(&[command]);
+    }
+
+    fn update_tonemap_buffer(
+        buffer: &VkBuffer,
+        exposure_stops: f32,
+        tonemapper: Tonemapper,
+        format: vk::SurfaceFormatKHR,
+    ) {
+        let params = TonemapParams {
+            exposure: exposure_stops.exp2(),
+            tonemapper: tonemapper as u32,
+            encode_srgb: Self::needs_srgb_encoding(format) as u32,
+        };
+
+        buffer.map_memory(&[params]);
+    }
+
     fn create_descriptor_pool(context: &VkContext, count: u32) -> VkDescriptorPool {
         let ubo_pool_size = vk::DescriptorPoolSize::builder()
             .ty(vk::DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(count);
+        let image_pool_size = vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::SAMPLED_IMAGE)
+            .descriptor_count(count);
         let sampler_pool_size = vk::DescriptorPoolSize::builder()
-            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .ty(vk::DescriptorType::SAMPLER)
             .descriptor_count(count);
 
         VkDescriptorPool::new(
             &context.device,
-            &[ubo_pool_size.build(), sampler_pool_size.build()],
+            &[
+                ubo_pool_size.build(),
+                image_pool_size.build(),
+                sampler_pool_size.build(),
+            ],
             count,
         )
     }
@@ -354,37 +874,161 @@ impl TutorialApp {
             .iter()
             .zip(uniform_buffers.iter())
             .for_each(|(set, buffer)| {
-                let buffer_info = vk::DescriptorBufferInfo::builder()
-                    .buffer(buffer.handle)
-                    .offset(0)
-                    .range(std::mem::size_of::<UniformBufferObject>() as vk::DeviceSize)
+                Self::write_descriptor_set(device, *set, buffer, texture, sampler)
+            });
+
+        descriptor_sets
+    }
+
+    fn write_descriptor_set(
+        device: &VkDevice,
+        set: vk::DescriptorSet,
+        buffer: &VkBuffer,
+        texture: &VkTexture,
+        sampler: &VkSampler,
+    ) {
+        let buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(buffer.handle)
+            .offset(0)
+            .range(std::mem::size_of::<UniformBufferObject>() as vk::DeviceSize)
+            .build();
+        let buffer_infos = [buffer_info];
+
+        let ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_infos)
+            .build();
+
+        let image_info = vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.view)
+            .build();
+        let image_infos = [image_info];
+
+        let image_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(1)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+            .image_info(&image_infos)
+            .build();
+
+        let sampler_info = vk::DescriptorImageInfo::builder()
+            .sampler(sampler.handle)
+            .build();
+        let sampler_infos = [sampler_info];
+
+        let sampler_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(2)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::SAMPLER)
+            .image_info(&sampler_infos)
+            .build();
+
+        let descriptor_writes = [
+            ubo_descriptor_write,
+            image_descriptor_write,
+            sampler_descriptor_write,
+        ];
+
+        unsafe {
+            device
+                .handle
+                .update_descriptor_sets(&descriptor_writes, &[])
+        }
+    }
+
+    fn create_tonemap_descriptor_pool(context: &VkContext, count: u32) -> VkDescriptorPool {
+        let image_pool_size = vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::SAMPLED_IMAGE)
+            .descriptor_count(count);
+        let sampler_pool_size = vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::SAMPLER)
+            .descriptor_count(count);
+        let params_pool_size = vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(count);
+
+        VkDescriptorPool::new(
+            &context.device,
+            &[
+                image_pool_size.build(),
+                sampler_pool_size.build(),
+                params_pool_size.build(),
+            ],
+            count,
+        )
+    }
+
+    // One set per swap-chain image, reading the HDR image rendered for it
+    fn create_tonemap_descriptor_sets(
+        device: &VkDevice,
+        pool: &VkDescriptorPool,
+        layout: &VkDescriptorSetLayout,
+        params_buffers: &[VkBuffer],
+        hdr_views: &[vk::ImageView],
+        sampler: &VkSampler,
+    ) -> Vec<vk::DescriptorSet> {
+        let count = hdr_views.len();
+        log::info!("Creating {} tonemap descriptor sets", count);
+
+        let descriptor_sets = pool.create_descriptor_sets(layout, count);
+
+        descriptor_sets
+            .iter()
+            .zip(hdr_views.iter().zip(params_buffers.iter()))
+            .for_each(|(set, (view, buffer))| {
+                let image_info = vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(*view)
                     .build();
-                let buffer_infos = [buffer_info];
+                let image_infos = [image_info];
 
-                let ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+                let image_descriptor_write = vk::WriteDescriptorSet::builder()
                     .dst_set(*set)
                     .dst_binding(0)
                     .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&buffer_infos)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .image_info(&image_infos)
                     .build();
 
-                let image_info = vk::DescriptorImageInfo::builder()
-                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                    .image_view(texture.view)
+                let sampler_info = vk::DescriptorImageInfo::builder()
                     .sampler(sampler.handle)
                     .build();
-                let image_infos = [image_info];
+                let sampler_infos = [sampler_info];
 
-                let image_descriptor_write = vk::WriteDescriptorSet::builder()
+                let sampler_descriptor_write = vk::WriteDescriptorSet::builder()
                     .dst_set(*set)
                     .dst_binding(1)
                     .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&image_infos)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .image_info(&sampler_infos)
+                    .build();
+
+                let buffer_info = vk::DescriptorBufferInfo::builder()
+                    .buffer(buffer.handle)
+                    .offset(0)
+                    .range(std::mem::size_of::<TonemapParams>() as vk::DeviceSize)
+                    .build();
+                let buffer_infos = [buffer_info];
+
+                let params_descriptor_write = vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(2)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(&buffer_infos)
                     .build();
 
-                let descriptor_writes = [ubo_descriptor_write, image_descriptor_write];
+                let descriptor_writes = [
+                    image_descriptor_write,
+                    sampler_descriptor_write,
+                    params_descriptor_write,
+                ];
 
                 unsafe {
                     device
@@ -396,73 +1040,86 @@ impl TutorialApp {
         descriptor_sets
     }
 
-    fn destroy_descriptor_sets(&self) {
-        self.descriptor_pool.reset_descriptor_sets();
+    fn wait_for_frame(frame_timeline: &VkTimelineSemaphore, frame: u64) {
+        if !frame_timeline.wait_timeout(frame, FRAME_STALL_TIMEOUT.as_nanos() as u64) {
+            log::warn!(
+                "Frame {} has not finished after {:?}, still waiting",
+                frame,
+                FRAME_STALL_TIMEOUT
+            );
+            frame_timeline.wait(frame);
+        }
     }
 
-    fn create_texture_image(context: &VkContext, command_pool: &Arc<VkCommandPool>) -> VkTexture {
-        VkImage::load_texture(
-            &context.device,
-            "assets/chalet.jpg",
-            &command_pool,
-            context.device.graphics_queue, // TODO: Use transfer queue
-        )
+    fn create_sampler(context: &VkContext) -> Arc<VkSampler> {
+        context.samplers.get(SamplerDesc::default())
     }
 
-    fn create_sampler(context: &VkContext, texture: &VkTexture) -> VkSampler {
-        let properties = context.device.get_properties();
-        VkSampler::new(
-            &context.device,
-            texture.image.mip_levels,
-            properties.limits.max_sampler_anisotropy,
-        )
+    fn create_lod_selector(model: &ModelBuffers) -> LodSelector {
+        LodSelector::new(model.lods.len(), LOD_FULL_DETAIL_SIZE, LOD_HYSTERESIS)
     }
 
-    fn load_model() -> Model {
-        log::info!("Loading model");
-
-        let mut buf = Vec::new();
-        let fullpath = &Path::new("assets").join("chalet.obj");
-        let mut file = File::open(&fullpath).unwrap();
-        file.read_to_end(&mut buf).unwrap();
-        let mut cursor = Cursor::new(buf);
+    // The HDR target has the same size as the swap-chain, so texels are read one to one
+    fn create_tonemap_sampler(context: &VkContext) -> Arc<VkSampler> {
+        context.samplers.get(SamplerDesc {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            max_lod: 0.0,
+            max_anisotropy: 1.0,
+            ..Default::default()
+        })
+    }
 
-        let (models, _) = tobj::load_obj_buf(
-            &mut cursor,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-            |_| unreachable!(),
-        )
-        .unwrap();
-
-        let mesh = &models[0].mesh;
-        let positions = mesh.positions.as_slice();
-        let coords = mesh.texcoords.as_slice();
-        let vertex_count = mesh.positions.len() / 3;
-
-        let mut vertices = Vec::with_capacity(vertex_count);
-        for i in 0..vertex_count {
-            let x = positions[i * 3];
-            let y = positions[i * 3 + 1];
-            let z = positions[i * 3 + 2];
-            let u = coords[i * 2];
-            let v = coords[i * 2 + 1];
-
-            let vertex = Vertex {
-                position: Vec3::new(x, y, z),
-                color: Vec3::new(1.0, 1.0, 1.0),
-                tex_coord: Vec2::new(u, v),
-            };
-            vertices.push(vertex);
+    // Streamed assets replace their placeholders, which changes the descriptor sets and the
+    // recorded buffers. Frames in flight may still use the previous assets, so each image is
+    // only updated once its last frame has finished, see `refresh_image`.
+    fn refresh_assets(&mut self) {
+        self.assets.unload_unused(&mut self.deletion_queue);
+        let model = self.assets.model(&self.models[self.model_index]);
+        self.lod_selector = Self::create_lod_selector(model);
+        if let Some(context) = &mut self.swap_chain_context {
+            context
+                .stale_images
+                .iter_mut()
+                .for_each(|stale| *stale = true);
         }
+    }
 
-        Model(vertices, mesh.indices.clone())
+    // The image must not be used by a frame in flight
+    fn refresh_image(&mut self, index: usize) {
+        let swap_context = match &self.swap_chain_context {
+            Some(context) => context,
+            None => return,
+        };
+        let material = self.assets.material(&self.material);
+        Self::write_descriptor_set(
+            &self.vk_context.device,
+            swap_context.descriptor_sets[index],
+            &swap_context.uniform_buffers[index],
+            self.assets.texture(&material.base_color),
+            &material.sampler,
+        );
+        self.record_image_commands(index);
+        if let Some(context) = &mut self.swap_chain_context {
+            context.stale_images[index] = false;
+        }
     }
 
     fn record_commands(&self) {
+        let image_count = match &self.swap_chain_context {
+            Some(context) => context.swap_chain.images.len(),
+            None => return,
+        };
+        for index in 0..image_count {
+            self.record_image_commands(index);
+        }
+    }
+
+    fn record_image_commands(&self, index: usize) {
         let context = &self.vk_context;
         let device = &context.device.handle;
         let swap_context = match &self.swap_chain_context {
@@ -471,75 +1128,97 @@ impl TutorialApp {
         };
 
         let swap_chain = &swap_context.swap_chain;
-        for (index, swap_image) in swap_chain.images.iter().enumerate() {
-            let buffer = &swap_image.command_buffer;
-            let command_begin_info = vk::CommandBufferBeginInfo::builder();
-            unsafe {
-                device
-                    .begin_command_buffer(buffer.handle, &command_begin_info)
-                    .expect("Unable to begin command buffer")
-            };
-
-            let clear_values = [
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 1.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
-                },
-            ];
-
-            let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
-                .render_pass(self.render_pass.handle)
-                .framebuffer(swap_image.framebuffer)
-                .render_area(vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: swap_chain.extent,
-                })
-                .clear_values(&clear_values);
-
-            unsafe {
-                device.cmd_begin_render_pass(
-                    buffer.handle,
-                    &render_pass_begin_info,
-                    vk::SubpassContents::INLINE,
-                );
+        let swap_image = &swap_chain.images[index];
+        let buffer = &swap_image.command_buffer;
+        let command_begin_info = vk::CommandBufferBeginInfo::builder();
+        unsafe {
+            device
+                .begin_command_buffer(buffer.handle, &command_begin_info)
+                .expect("Unable to begin command buffer")
+        };
 
-                device.cmd_bind_pipeline(
-                    buffer.handle,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    swap_context.pipeline.handle,
-                );
+        let frame_graph = &self.frame_graph;
+        frame_graph
+            .graph
+            .record(buffer, index, |pass, buffer| unsafe {
+                if pass == frame_graph.scene_pass {
+                    device.cmd_bind_pipeline(
+                        buffer.handle,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        swap_context.pipeline.handle,
+                    );
+
+                    let model = self.assets.model(&self.models[self.model_index]);
+                    let buffers = [model.vertex_buffer.handle];
+                    let offsets = [0];
+                    device.cmd_bind_vertex_buffers(buffer.handle, 0, &buffers, &offsets);
+                    device.cmd_bind_index_buffer(
+                        buffer.handle,
+                        model.index_buffer.handle,
+                        0,
+                        vk::IndexType::UINT32,
+                    );
+                    device.cmd_bind_descriptor_sets(
+                        buffer.handle,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        swap_context.pipeline.layout,
+                        0,
+                        &swap_context.descriptor_sets[index..=index],
+                        &[],
+                    );
+                    device.cmd_draw_indexed_indirect(
+                        buffer.handle,
+                        swap_context.indirect_buffers[index].handle,
+                        0,
+                        1,
+                        std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+                    );
+
+                    if let Some(pipeline) = &swap_context.skybox_pipeline {
+                        device.cmd_bind_pipeline(
+                            buffer.handle,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline.handle,
+                        );
+                        device.cmd_bind_descriptor_sets(
+                            buffer.handle,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline.layout,
+                            0,
+                            &swap_context.skybox_descriptor_sets[index..=index],
+                            &[],
+                        );
+                        device.cmd_draw(buffer.handle, 3, 1, 0, 0);
+                    }
+                } else if pass == frame_graph.tonemap_pass {
+                    device.cmd_bind_pipeline(
+                        buffer.handle,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        swap_context.tonemap_pipeline.handle,
+                    );
+                    device.cmd_bind_descriptor_sets(
+                        buffer.handle,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        swap_context.tonemap_pipeline.layout,
+                        0,
+                        &swap_context.tonemap_descriptor_sets[index..=index],
+                        &[],
+                    );
+                    device.cmd_bind_vertex_buffers(
+                        buffer.handle,
+                        0,
+                        &[self.fullscreen_triangle.handle],
+                        &[0],
+                    );
+                    device.cmd_draw(buffer.handle, 3, 1, 0, 0);
+                }
+            });
 
-                let buffers = [self.vertex_buffer.handle];
-                let offsets = [0];
-                device.cmd_bind_vertex_buffers(buffer.handle, 0, &buffers, &offsets);
-                device.cmd_bind_index_buffer(
-                    buffer.handle,
-                    self.index_buffer.handle,
-                    0,
-                    vk::IndexType::UINT32,
-                );
-                device.cmd_bind_descriptor_sets(
-                    buffer.handle,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    swap_context.pipeline.layout,
-                    0,
-                    &swap_context.descriptor_sets[index..=index],
-                    &[],
-                );
-                device.cmd_draw_indexed(buffer.handle, self.model.1.len() as u32, 1, 0, 0, 0);
-                device.cmd_end_render_pass(buffer.handle);
-                device
-                    .end_command_buffer(buffer.handle)
-                    .expect("Failed to record end of command buffer");
-            };
-        }
+        unsafe {
+            device
+                .end_command_buffer(buffer.handle)
+                .expect("Failed to record end of command buffer");
+        };
     }
 }
 
@@ -548,7 +1227,11 @@ impl App for TutorialApp {
         self.vk_context.device.wait_idle();
     }
 
-    fn update(&mut self) {}
+    fn update(&mut self) {
+        if self.assets.update(&mut self.deletion_queue) {
+            self.refresh_assets();
+        }
+    }
 
     fn resized(&mut self, _window: &Window, size: PhysicalSize<u32>) {
         self.recreate_swap_chain(size);
@@ -556,6 +1239,39 @@ impl App for TutorialApp {
 
     fn minimized(&mut self, _window: &Window) {}
 
+    fn loading_progress(&self) -> StreamingProgress {
+        self.assets.progress()
+    }
+
+    fn key_pressed(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Up => self.exposure_stops += EXPOSURE_STEP,
+            VirtualKeyCode::Down => self.exposure_stops -= EXPOSURE_STEP,
+            VirtualKeyCode::T => {
+                self.tonemapper = match self.tonemapper {
+                    Tonemapper::Reinhard => Tonemapper::Aces,
+                    Tonemapper::Aces => Tonemapper::Reinhard,
+                }
+            }
+            VirtualKeyCode::M => {
+                self.model_index = (self.model_index + 1) % self.models.len();
+                log::info!(
+                    "Showing model {} of {}",
+                    self.model_index + 1,
+                    self.models.len()
+                );
+                self.refresh_assets();
+                return;
+            }
+            _ => return,
+        }
+        log::info!(
+            "Tonemapping with {:?} at {:+.1} EV",
+            self.tonemapper,
+            self.exposure_stops
+        );
+    }
+
     fn draw_frame(&mut self, window: &Window) {
         let swap_context = match &mut self.swap_chain_context {
             Some(context) => context,
@@ -564,13 +1280,14 @@ impl App for TutorialApp {
 
         let current_frame = swap_context.current_frame;
         let swap_chain = &mut swap_context.swap_chain;
-        let context = &self.vk_context;
-        let device = &context.device;
-
         let swap_frame = &swap_chain.frames[current_frame];
-        let fence = &swap_frame.in_flight;
 
-        device.wait_for_fences(&[fence]);
+        Self::wait_for_frame(
+            &self.frame_timeline,
+            swap_context.frame_numbers[current_frame],
+        );
+        self.deletion_queue
+            .frame_completed(self.frame_timeline.value());
 
         let acquire_result = swap_chain.acquire_next_image(&swap_frame.available);
         let image_index = match acquire_result {
@@ -583,43 +1300,79 @@ impl App for TutorialApp {
 
         let swap_image = &mut swap_chain.images[image_index];
         if let Some(image_frame) = swap_image.frame {
-            let in_flight_fence = &swap_chain.frames[image_frame].in_flight;
-            device.wait_for_fences(&[in_flight_fence]);
+            Self::wait_for_frame(
+                &self.frame_timeline,
+                swap_context.frame_numbers[image_frame],
+            );
+            self.deletion_queue
+                .frame_completed(self.frame_timeline.value());
         }
 
         swap_image.frame = Some(current_frame);
 
-        let fence = &swap_frame.in_flight;
+        if swap_context.stale_images[image_index] {
+            self.refresh_image(image_index);
+        }
+
+        let swap_context = self.swap_chain_context.as_mut().unwrap();
+        let swap_chain = &mut swap_context.swap_chain;
+        let device = &self.vk_context.device;
+        let swap_frame = &swap_chain.frames[current_frame];
+        let swap_image = &swap_chain.images[image_index];
         let elapsed_time = self.start_time.elapsed().as_secs_f32();
+        let model = Self::model_matrix(elapsed_time);
         Self::update_uniform_buffer(
             &swap_context.uniform_buffers[image_index],
             swap_chain.extent,
-            elapsed_time,
+            model,
         );
 
-        let wait_semaphores = [swap_frame.available.handle];
-        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = [swap_image.command_buffer.handle];
-        let signal_semaphores = [swap_frame.finished.handle];
-        let submit_info = vk::SubmitInfo::builder()
-            .wait_semaphores(&wait_semaphores)
-            .wait_dst_stage_mask(&wait_stages)
-            .command_buffers(&command_buffers)
-            .signal_semaphores(&signal_semaphores);
-        let infos = [submit_info.build()];
-
-        device.reset_fences(&[fence]);
-        unsafe {
-            device
-                .handle
-                .queue_submit(context.device.graphics_queue, &infos, fence.handle)
-                .expect("Unable to submit queue")
-        };
+        let model_buffers = self.assets.model(&self.models[self.model_index]);
+        let lod = Self::select_lod(
+            &mut self.lod_selector,
+            &model_buffers.bounding_sphere,
+            swap_chain.extent,
+            &model,
+        );
+        Self::update_indirect_buffer(
+            &swap_context.indirect_buffers[image_index],
+            &model_buffers.lods[lod],
+        );
+        Self::update_tonemap_buffer(
+            &swap_context.tonemap_buffers[image_index],
+            self.exposure_stops,
+            self.tonemapper,
+            self.swap_chain_format,
+        );
+
+        let frame_number = self.deletion_queue.frame_submitted();
+        let waits = [(
+            VkSemaphoreValue {
+                semaphore: swap_frame.available.handle,
+                value: 0,
+            },
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        )];
+        let signals = [
+            VkSemaphoreValue {
+                semaphore: swap_frame.finished.handle,
+                value: 0,
+            },
+            VkSemaphoreValue {
+                semaphore: self.frame_timeline.handle,
+                value: frame_number,
+            },
+        ];
+        device
+            .queues
+            .graphics
+            .submit(&[swap_image.command_buffer.handle], &waits, &signals);
+        swap_context.frame_numbers[current_frame] = frame_number;
 
         swap_context.current_frame = swap_chain.advance_frame(current_frame);
 
         let result = swap_chain.present_image(
-            device.presentation_queue,
+            &device.queues.present,
             image_index as _,
             &[&swap_frame.finished],
         );
@@ -1,35 +1,58 @@
+mod barrier;
 mod buffer;
 mod command;
 mod context;
 mod debug;
+mod deletion_queue;
 mod descriptor;
 mod device;
-mod fence;
+mod device_report;
+mod features;
+mod framebuffer;
 mod image;
 mod instance;
 mod physical_device;
 mod pipeline;
+mod queue;
 mod queue_family;
+mod render_graph;
 mod render_pass;
+mod requirements;
+mod sampler;
 mod semaphore;
 mod settings;
 mod shader;
 mod surface;
 mod swap_chain;
+mod timeline_semaphore;
+mod tracker;
+mod upload;
 mod utils;
 mod version;
 
-pub use self::image::{VkImage, VkSampler, VkTexture};
+pub use self::image::{
+    CubemapSource, ImageDimensions, MipGeneration, TextureColorSpace, TexturePixels, VkImage,
+    VkTexture,
+};
+pub use barrier::{ImageAccess, VkBarrierTracker};
 pub use buffer::VkBuffer;
 pub use command::{VkCommandBuffer, VkCommandPool};
 pub use context::VkContext;
+pub use deletion_queue::VkDeletionQueue;
 pub use descriptor::{VkDescriptorPool, VkDescriptorSetLayout};
 pub use device::VkDevice;
-pub use fence::VkFence;
+pub use device_report::VkDeviceReport;
+pub use framebuffer::VkFramebuffer;
 pub use physical_device::VkPhysicalDevice;
 pub use pipeline::VkPipeline;
-pub use render_pass::VkRenderPass;
+pub use queue::{VkQueue, VkQueues, VkSemaphoreValue};
+pub use render_graph::{GraphPass, GraphResource, PassDesc, VkRenderGraph};
+pub use render_pass::{AttachmentDesc, SubpassDesc, VkRenderPass, VkRenderPassBuilder};
+pub use requirements::VkDeviceRequirements;
+pub use sampler::{SamplerDesc, VkSampler};
 pub use settings::VkSettings;
 pub use shader::VkShaderModule;
 pub use surface::VkSurface;
 pub use swap_chain::VkSwapChain;
+pub use timeline_semaphore::VkTimelineSemaphore;
+pub use upload::VkPendingUpload;
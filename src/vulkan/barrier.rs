@@ -0,0 +1,626 @@
+use std::{collections::HashMap, ops::Range};
+
+use ash::vk;
+
+use super::{command::VkCommandBuffer, device::VkDevice, image::VkImage};
+
+// Accesses which produce data that has to be made available to later commands
+const WRITE_ACCESS: vk::AccessFlags = vk::AccessFlags::from_raw(
+    vk::AccessFlags::SHADER_WRITE.as_raw()
+        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags::TRANSFER_WRITE.as_raw()
+        | vk::AccessFlags::HOST_WRITE.as_raw()
+        | vk::AccessFlags::MEMORY_WRITE.as_raw(),
+);
+
+// How the commands following a barrier use an image, each usage implies the layout the image
+// has to be in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageAccess {
+    // Initial state of new images, the contents are discarded by the first transition
+    Undefined,
+    TransferRead,
+    TransferWrite,
+    FragmentShaderRead,
+    ColorAttachment,
+    DepthAttachment,
+    // Depth testing without writes, or sampling the depth in a fragment shader
+    DepthRead,
+    Present,
+}
+
+// Batches the pipeline barriers needed to use images in a new way. The layout and the last
+// accesses of every mip level and layer are tracked, a barrier is only emitted for a layout
+// change, after a write or before writing over something which is still read. A tracker lives
+// for the recording of one command buffer, images are tracked from their last use before it.
+#[derive(Default)]
+pub struct VkBarrierTracker {
+    images: HashMap<vk::Image, TrackedImage>,
+    pending: VkBarrierBatch,
+}
+
+// Barriers recorded with a single command, can be kept to be recorded again
+#[derive(Default)]
+pub struct VkBarrierBatch {
+    images: Vec<PendingBarrier>,
+    memory: Vec<PendingMemoryBarrier>,
+}
+
+struct TrackedImage {
+    aspect_mask: vk::ImageAspectFlags,
+    layer_count: u32,
+    // Indexed by `mip_level * layer_count + layer`
+    states: Vec<SubresourceState>,
+    // Subresources with a barrier in the current batch
+    batched: Vec<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct SubresourceState {
+    layout: vk::ImageLayout,
+    // Stages which have to finish before the next write, reads since the last barrier are added
+    stages: vk::PipelineStageFlags,
+    // Writes which have not been made available yet
+    writes: vk::AccessFlags,
+    // Stages the last write is visible to, reads in other stages need another barrier
+    visible: vk::PipelineStageFlags,
+}
+
+struct PendingBarrier {
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    before: SubresourceState,
+    after: ImageAccess,
+}
+
+struct PendingMemoryBarrier {
+    stages: vk::PipelineStageFlags,
+    writes: vk::AccessFlags,
+    after: ImageAccess,
+}
+
+impl ImageAccess {
+    fn info(self) -> (vk::PipelineStageFlags, vk::AccessFlags, vk::ImageLayout) {
+        match self {
+            ImageAccess::Undefined => (
+                vk::PipelineStageFlags::empty(),
+                vk::AccessFlags::empty(),
+                vk::ImageLayout::UNDEFINED,
+            ),
+            ImageAccess::TransferRead => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ),
+            ImageAccess::TransferWrite => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ),
+            ImageAccess::FragmentShaderRead => (
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::AccessFlags::SHADER_READ,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            ImageAccess::ColorAttachment => (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            ImageAccess::DepthAttachment => (
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+            ImageAccess::DepthRead => (
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ),
+            ImageAccess::Present => (
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::empty(),
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            ),
+        }
+    }
+
+    pub fn layout(self) -> vk::ImageLayout {
+        self.info().2
+    }
+
+    pub fn stages(self) -> vk::PipelineStageFlags {
+        self.info().0
+    }
+
+    pub fn writes(self) -> vk::AccessFlags {
+        self.info().1 & WRITE_ACCESS
+    }
+
+    pub fn is_write(self) -> bool {
+        self.info().1.intersects(WRITE_ACCESS)
+    }
+}
+
+impl SubresourceState {
+    // State right after a barrier to the access
+    fn after(access: ImageAccess) -> SubresourceState {
+        let (stages, access_mask, layout) = access.info();
+        let writes = access_mask & WRITE_ACCESS;
+        SubresourceState {
+            layout,
+            stages,
+            writes,
+            visible: if writes.is_empty() {
+                stages
+            } else {
+                vk::PipelineStageFlags::empty()
+            },
+        }
+    }
+
+    // Reads in the current layout of something already visible to them can run without a barrier
+    fn needs_barrier(&self, access: ImageAccess) -> bool {
+        let (stages, _, layout) = access.info();
+        layout != self.layout
+            || access.is_write()
+            || !self.writes.is_empty()
+            || !self.visible.contains(stages)
+    }
+}
+
+impl VkBarrierTracker {
+    pub fn new() -> VkBarrierTracker {
+        VkBarrierTracker::default()
+    }
+
+    // Starts tracking an image whose subresources were all last used with `access`
+    pub fn track_image(
+        &mut self,
+        image: &VkImage,
+        aspect_mask: vk::ImageAspectFlags,
+        access: ImageAccess,
+    ) {
+        let mut state = SubresourceState::after(access);
+        state.visible = vk::PipelineStageFlags::empty();
+        self.insert(
+            image.handle,
+            image.mip_levels,
+            image.dimensions.layer_count(),
+            aspect_mask,
+            state,
+        );
+    }
+
+    // Starts tracking an image with a single level and layer, such as an attachment or a
+    // swap-chain image. Besides the last access, the given stages have to finish before the
+    // next use, e.g. those waiting for the image to be acquired.
+    pub fn track_attachment(
+        &mut self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        access: ImageAccess,
+        stages: vk::PipelineStageFlags,
+    ) {
+        let state = SubresourceState {
+            layout: access.layout(),
+            stages: access.stages() | stages,
+            writes: access.writes(),
+            visible: vk::PipelineStageFlags::empty(),
+        };
+        self.insert(image, 1, 1, aspect_mask, state);
+    }
+
+    pub fn use_image(&mut self, image: &VkImage, access: ImageAccess) {
+        self.use_subresources(
+            image,
+            0..image.mip_levels,
+            0..image.dimensions.layer_count(),
+            access,
+        );
+    }
+
+    pub fn use_attachment(&mut self, image: vk::Image, access: ImageAccess) {
+        self.use_range(image, 0..1, 0..1, access);
+    }
+
+    // Image barriers only cover writes through the same image, so the writes of another image
+    // sharing the memory are made available with a global barrier before it is reused
+    pub fn use_aliased_memory(
+        &mut self,
+        stages: vk::PipelineStageFlags,
+        writes: vk::AccessFlags,
+        access: ImageAccess,
+    ) {
+        self.pending.memory.push(PendingMemoryBarrier {
+            stages,
+            writes,
+            after: access,
+        });
+    }
+
+    // Adds the barriers needed before the subresources are used with `access` to the batch. A
+    // subresource can only change once per batch, the batch has to be flushed in between.
+    pub fn use_subresources(
+        &mut self,
+        image: &VkImage,
+        mip_levels: Range<u32>,
+        layers: Range<u32>,
+        access: ImageAccess,
+    ) {
+        self.use_range(image.handle, mip_levels, layers, access);
+    }
+
+    fn use_range(
+        &mut self,
+        image: vk::Image,
+        mip_levels: Range<u32>,
+        layers: Range<u32>,
+        access: ImageAccess,
+    ) {
+        if access == ImageAccess::Undefined {
+            panic!("Images cannot be transitioned to an undefined layout");
+        }
+
+        let tracked = self.images.get_mut(&image).expect("Image is not tracked");
+        let (stages, _, _) = access.info();
+        for mip_level in mip_levels {
+            // Consecutive layers which share their state are covered by one barrier
+            let mut run: Option<(u32, SubresourceState)> = None;
+            for layer in layers.clone() {
+                let index = (mip_level * tracked.layer_count + layer) as usize;
+                let state = tracked.states[index];
+                if !state.needs_barrier(access) {
+                    tracked.states[index].stages |= stages;
+                    if let Some((base_layer, before)) = run.take() {
+                        push_barrier(
+                            &mut self.pending.images,
+                            image,
+                            tracked,
+                            mip_level,
+                            base_layer..layer,
+                            before,
+                            access,
+                        );
+                    }
+                    continue;
+                }
+
+                if tracked.batched[index] {
+                    panic!(
+                        "Subresource (level {}, layer {}) of image {:?} changes twice in one barrier batch",
+                        mip_level, layer, image
+                    );
+                }
+                tracked.batched[index] = true;
+                tracked.states[index] = SubresourceState::after(access);
+                match run {
+                    Some((_, before)) if before == state => {}
+                    _ => {
+                        if let Some((base_layer, before)) = run.take() {
+                            push_barrier(
+                                &mut self.pending.images,
+                                image,
+                                tracked,
+                                mip_level,
+                                base_layer..layer,
+                                before,
+                                access,
+                            );
+                        }
+                        run = Some((layer, state));
+                    }
+                }
+            }
+            if let Some((base_layer, before)) = run {
+                push_barrier(
+                    &mut self.pending.images,
+                    image,
+                    tracked,
+                    mip_level,
+                    base_layer..layers.end,
+                    before,
+                    access,
+                );
+            }
+        }
+    }
+
+    pub fn flush(&mut self, device: &VkDevice, command_buffer: &VkCommandBuffer) {
+        self.take_batch().record(device, command_buffer);
+    }
+
+    // Ends the batch without recording it
+    pub fn take_batch(&mut self) -> VkBarrierBatch {
+        for tracked in self.images.values_mut() {
+            tracked.batched.fill(false);
+        }
+        std::mem::take(&mut self.pending)
+    }
+
+    fn insert(
+        &mut self,
+        image: vk::Image,
+        mip_levels: u32,
+        layer_count: u32,
+        aspect_mask: vk::ImageAspectFlags,
+        state: SubresourceState,
+    ) {
+        let count = (mip_levels * layer_count) as usize;
+        self.images.insert(
+            image,
+            TrackedImage {
+                aspect_mask,
+                layer_count,
+                states: vec![state; count],
+                batched: vec![false; count],
+            },
+        );
+    }
+}
+
+impl VkBarrierBatch {
+    // Records every batched barrier with a single command, through synchronization2 when the
+    // device has it enabled
+    pub fn record(&self, device: &VkDevice, command_buffer: &VkCommandBuffer) {
+        if self.images.is_empty() && self.memory.is_empty() {
+            return;
+        }
+
+        match device.synchronization2() {
+            Some(synchronization2) => {
+                let barriers: Vec<_> = self
+                    .images
+                    .iter()
+                    .map(|barrier| {
+                        let (dst_stages, dst_access, new_layout) = barrier.after.info();
+                        vk::ImageMemoryBarrier2KHR::builder()
+                            .src_stage_mask(vk::PipelineStageFlags2KHR::from_raw(
+                                barrier.before.stages.as_raw() as u64,
+                            ))
+                            .src_access_mask(vk::AccessFlags2KHR::from_raw(
+                                barrier.before.writes.as_raw() as u64,
+                            ))
+                            .dst_stage_mask(vk::PipelineStageFlags2KHR::from_raw(
+                                dst_stages.as_raw() as u64,
+                            ))
+                            .dst_access_mask(vk::AccessFlags2KHR::from_raw(
+                                dst_access.as_raw() as u64
+                            ))
+                            .old_layout(barrier.before.layout)
+                            .new_layout(new_layout)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .image(barrier.image)
+                            .subresource_range(barrier.range)
+                            .build()
+                    })
+                    .collect();
+                let memory_barriers: Vec<_> = self
+                    .memory
+                    .iter()
+                    .map(|barrier| {
+                        let (dst_stages, dst_access, _) = barrier.after.info();
+                        vk::MemoryBarrier2KHR::builder()
+                            .src_stage_mask(vk::PipelineStageFlags2KHR::from_raw(
+                                barrier.stages.as_raw() as u64,
+                            ))
+                            .src_access_mask(vk::AccessFlags2KHR::from_raw(
+                                barrier.writes.as_raw() as u64
+                            ))
+                            .dst_stage_mask(vk::PipelineStageFlags2KHR::from_raw(
+                                dst_stages.as_raw() as u64,
+                            ))
+                            .dst_access_mask(vk::AccessFlags2KHR::from_raw(
+                                dst_access.as_raw() as u64
+                            ))
+                            .build()
+                    })
+                    .collect();
+                let dependency_info = vk::DependencyInfoKHR::builder()
+                    .memory_barriers(&memory_barriers)
+                    .image_memory_barriers(&barriers);
+                unsafe {
+                    synchronization2.cmd_pipeline_barrier2(command_buffer.handle, &dependency_info)
+                };
+            }
+            None => {
+                let mut src_stages = vk::PipelineStageFlags::empty();
+                let mut dst_stages = vk::PipelineStageFlags::empty();
+                let barriers: Vec<_> = self
+                    .images
+                    .iter()
+                    .map(|barrier| {
+                        let (stages, dst_access, new_layout) = barrier.after.info();
+                        src_stages |= barrier.before.stages;
+                        dst_stages |= stages;
+                        vk::ImageMemoryBarrier::builder()
+                            .src_access_mask(barrier.before.writes)
+                            .dst_access_mask(dst_access)
+                            .old_layout(barrier.before.layout)
+                            .new_layout(new_layout)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .image(barrier.image)
+                            .subresource_range(barrier.range)
+                            .build()
+                    })
+                    .collect();
+                let memory_barriers: Vec<_> = self
+                    .memory
+                    .iter()
+                    .map(|barrier| {
+                        let (stages, dst_access, _) = barrier.after.info();
+                        src_stages |= barrier.stages;
+                        dst_stages |= stages;
+                        vk::MemoryBarrier::builder()
+                            .src_access_mask(barrier.writes)
+                            .dst_access_mask(dst_access)
+                            .build()
+                    })
+                    .collect();
+                // The core command does not accept empty stage masks
+                if src_stages.is_empty() {
+                    src_stages = vk::PipelineStageFlags::TOP_OF_PIPE;
+                }
+                if dst_stages.is_empty() {
+                    dst_stages = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+                }
+                unsafe {
+                    device.handle.cmd_pipeline_barrier(
+                        command_buffer.handle,
+                        src_stages,
+                        dst_stages,
+                        vk::DependencyFlags::empty(),
+                        &memory_barriers,
+                        &[],
+                        &barriers,
+                    )
+                };
+            }
+        }
+    }
+}
+
+// Extends the previous barrier over the next mip level when the layers and states match
+fn push_barrier(
+    pending: &mut Vec<PendingBarrier>,
+    image: vk::Image,
+    tracked: &TrackedImage,
+    mip_level: u32,
+    layers: Range<u32>,
+    before: SubresourceState,
+    after: ImageAccess,
+) {
+    if let Some(last) = pending.last_mut() {
+        let range = &mut last.range;
+        if last.image == image
+            && last.before == before
+            && last.after == after
+            && range.base_mip_level + range.level_count == mip_level
+            && range.base_array_layer == layers.start
+            && range.layer_count == layers.end - layers.start
+        {
+            range.level_count += 1;
+            return;
+        }
+    }
+    pending.push(PendingBarrier {
+        image,
+        range: vk::ImageSubresourceRange {
+            aspect_mask: tracked.aspect_mask,
+            base_mip_level: mip_level,
+            level_count: 1,
+            base_array_layer: layers.start,
+            layer_count: layers.end - layers.start,
+        },
+        before,
+        after,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::Handle;
+
+    use super::*;
+
+    fn tracker(
+        mip_levels: u32,
+        layer_count: u32,
+        access: ImageAccess,
+    ) -> (VkBarrierTracker, vk::Image) {
+        let image = vk::Image::from_raw(1);
+        let mut tracker = VkBarrierTracker::new();
+        tracker.insert(
+            image,
+            mip_levels,
+            layer_count,
+            vk::ImageAspectFlags::COLOR,
+            SubresourceState::after(access),
+        );
+        (tracker, image)
+    }
+
+    fn ranges(tracker: &VkBarrierTracker) -> Vec<(u32, u32, u32, u32)> {
+        tracker
+            .pending
+            .images
+            .iter()
+            .map(|barrier| {
+                let range = barrier.range;
+                (
+                    range.base_mip_level,
+                    range.level_count,
+                    range.base_array_layer,
+                    range.layer_count,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_of_visible_data_need_no_barrier() {
+        let read = SubresourceState::after(ImageAccess::FragmentShaderRead);
+        assert!(!read.needs_barrier(ImageAccess::FragmentShaderRead));
+        assert!(read.needs_barrier(ImageAccess::ColorAttachment));
+        assert!(read.needs_barrier(ImageAccess::TransferRead));
+
+        let depth_read = SubresourceState::after(ImageAccess::DepthRead);
+        assert!(!depth_read.needs_barrier(ImageAccess::DepthRead));
+
+        // Writes have to be made available, even to an access in the same layout
+        let write = SubresourceState::after(ImageAccess::TransferWrite);
+        assert!(write.needs_barrier(ImageAccess::TransferWrite));
+        let attachment = SubresourceState::after(ImageAccess::ColorAttachment);
+        assert!(attachment.needs_barrier(ImageAccess::ColorAttachment));
+
+        // Reads in stages the data was not made visible to
+        let mut hidden = read;
+        hidden.visible = vk::PipelineStageFlags::empty();
+        assert!(hidden.needs_barrier(ImageAccess::FragmentShaderRead));
+    }
+
+    #[test]
+    fn layers_with_the_same_state_share_a_barrier() {
+        let (mut tracker, image) = tracker(1, 6, ImageAccess::TransferWrite);
+        let states = &mut tracker.images.get_mut(&image).unwrap().states;
+        states[2] = SubresourceState::after(ImageAccess::FragmentShaderRead);
+        states[4] = SubresourceState::after(ImageAccess::TransferRead);
+
+        tracker.use_range(image, 0..1, 0..6, ImageAccess::FragmentShaderRead);
+        // Layer 2 is already readable, layer 4 comes from another state
+        assert_eq!(
+            ranges(&tracker),
+            [(0, 1, 0, 2), (0, 1, 3, 1), (0, 1, 4, 1), (0, 1, 5, 1)]
+        );
+        assert_eq!(
+            tracker.pending.images[2].before.layout,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+        );
+    }
+
+    #[test]
+    fn mip_levels_with_the_same_layers_share_a_barrier() {
+        let (mut tracker, image) = tracker(4, 2, ImageAccess::TransferWrite);
+        tracker.use_range(image, 0..3, 0..2, ImageAccess::TransferRead);
+        assert_eq!(ranges(&tracker), [(0, 3, 0, 2)]);
+
+        // The last level changes after the others, so it is not merged into their barrier
+        tracker.use_range(image, 3..4, 0..1, ImageAccess::FragmentShaderRead);
+        assert_eq!(ranges(&tracker), [(0, 3, 0, 2), (3, 1, 0, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "changes twice in one barrier batch")]
+    fn subresources_change_once_per_batch() {
+        let (mut tracker, image) = tracker(1, 1, ImageAccess::TransferWrite);
+        tracker.use_range(image, 0..1, 0..1, ImageAccess::TransferRead);
+        tracker.use_range(image, 0..1, 0..1, ImageAccess::FragmentShaderRead);
+    }
+}
@@ -2,7 +2,7 @@ use std::{mem::align_of, sync::Arc};
 
 use ash::vk;
 
-use super::{VkCommandPool, VkDevice};
+use super::{VkCommandPool, VkDevice, VkPendingUpload, VkQueue, VkQueues};
 
 pub struct VkBuffer {
     device: Arc<VkDevice>,
@@ -18,8 +18,22 @@ impl VkBuffer {
         properties: vk::MemoryPropertyFlags,
         size: u64,
     ) -> VkBuffer {
-        let handle = create_vertex_buffer(device, usage, size);
+        Self::new_with_sharing(device, usage, properties, size, &[])
+    }
+
+    // Concurrent sharing between `queue_families` when there is more than one
+    pub fn new_with_sharing(
+        device: &Arc<VkDevice>,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+        size: u64,
+        queue_families: &[u32],
+    ) -> VkBuffer {
+        let handle = create_vertex_buffer(device, usage, size, queue_families);
         let memory = assign_buffer_memory(device, handle, properties);
+        let label = format!("{:?} buffer of size {}", usage, size);
+        device.track(handle, &label);
+        device.track(memory, &label);
 
         VkBuffer {
             device: Arc::clone(device),
@@ -32,10 +46,21 @@ impl VkBuffer {
     pub fn new_device_local<T: Copy>(
         device: &Arc<VkDevice>,
         command_pool: &Arc<VkCommandPool>,
-        queue: vk::Queue,
+        queue: &Arc<VkQueue>,
         usage: vk::BufferUsageFlags,
         data: &[T],
     ) -> VkBuffer {
+        Self::upload_device_local(device, command_pool, queue, usage, data).wait()
+    }
+
+    // The buffer is shared with the graphics queue when the copy runs on another queue family
+    pub fn upload_device_local<T: Copy>(
+        device: &Arc<VkDevice>,
+        command_pool: &Arc<VkCommandPool>,
+        queue: &Arc<VkQueue>,
+        usage: vk::BufferUsageFlags,
+        data: &[T],
+    ) -> VkPendingUpload<VkBuffer> {
         let size = (data.len() * std::mem::size_of::<T>()) as u64;
         log::info!("creating device-local buffer of size {}", size);
 
@@ -47,17 +72,31 @@ impl VkBuffer {
         );
         staging_buffer.map_memory(data);
 
-        let buffer = VkBuffer::new(
+        let buffer = VkBuffer::new_with_sharing(
             device,
             usage | vk::BufferUsageFlags::TRANSFER_DST,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
             size,
+            &VkQueues::sharing_families(&[queue, &device.queues.graphics]),
         );
 
         log::info!("Copying buffer data");
-        device.copy_buffer(&staging_buffer, &buffer, command_pool, queue);
-
-        buffer
+        let (command_buffer, value) =
+            device.submit_one_time_commands(command_pool, queue, |device, command_buffer| unsafe {
+                let regions = [vk::BufferCopy {
+                    src_offset: 0,
+                    dst_offset: 0,
+                    size,
+                }];
+                device.handle.cmd_copy_buffer(
+                    command_buffer.handle,
+                    staging_buffer.handle,
+                    buffer.handle,
+                    &regions,
+                );
+            });
+
+        VkPendingUpload::new(queue, value, buffer, command_buffer, vec![staging_buffer])
     }
 
     pub fn map_memory<T: Copy>(&self, data: &[T]) {
@@ -72,11 +111,18 @@ impl VkBuffer {
             self.device.handle.unmap_memory(self.memory);
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+        self.device.set_object_name(self.memory, name);
+    }
 }
 
 impl Drop for VkBuffer {
     fn drop(&mut self) {
         log::debug!("Dropping buffer");
+        self.device.untrack(self.handle);
+        self.device.untrack(self.memory);
         unsafe {
             self.device.handle.destroy_buffer(self.handle, None);
             self.device.handle.free_memory(self.memory, None);
@@ -84,11 +130,20 @@ impl Drop for VkBuffer {
     }
 }
 
-fn create_vertex_buffer(device: &VkDevice, usage: vk::BufferUsageFlags, size: u64) -> vk::Buffer {
-    let buffer_info = vk::BufferCreateInfo::builder()
-        .size(size)
-        .usage(usage)
-        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+fn create_vertex_buffer(
+    device: &VkDevice,
+    usage: vk::BufferUsageFlags,
+    size: u64,
+    queue_families: &[u32],
+) -> vk::Buffer {
+    let mut buffer_info = vk::BufferCreateInfo::builder().size(size).usage(usage);
+    if queue_families.len() > 1 {
+        buffer_info = buffer_info
+            .sharing_mode(vk::SharingMode::CONCURRENT)
+            .queue_family_indices(queue_families);
+    } else {
+        buffer_info = buffer_info.sharing_mode(vk::SharingMode::EXCLUSIVE);
+    }
 
     unsafe {
         device
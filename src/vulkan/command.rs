@@ -12,9 +12,21 @@ pub struct VkCommandPool {
 
 impl VkCommandPool {
     pub fn new(device: &Arc<VkDevice>, queue_family_index: u32) -> VkCommandPool {
+        Self::new_with_flags(
+            device,
+            queue_family_index,
+            vk::CommandPoolCreateFlags::empty(),
+        )
+    }
+
+    pub fn new_with_flags(
+        device: &Arc<VkDevice>,
+        queue_family_index: u32,
+        flags: vk::CommandPoolCreateFlags,
+    ) -> VkCommandPool {
         let pool_info = vk::CommandPoolCreateInfo::builder()
             .queue_family_index(queue_family_index)
-            .flags(vk::CommandPoolCreateFlags::empty());
+            .flags(flags);
 
         let handle = unsafe {
             device
@@ -22,6 +34,7 @@ impl VkCommandPool {
                 .create_command_pool(&pool_info, None)
                 .expect("Unable to create command pool")
         };
+        device.track(handle, "command pool");
 
         VkCommandPool {
             device: Arc::clone(device),
@@ -53,11 +66,16 @@ impl VkCommandPool {
                 .free_command_buffers(self.handle, &[buffer]);
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkCommandPool {
     fn drop(&mut self) {
         log::debug!("Dropping command pool");
+        self.device.untrack(self.handle);
         unsafe {
             self.device.handle.destroy_command_pool(self.handle, None);
         }
@@ -79,6 +97,10 @@ impl VkCommandBuffer {
             auto_release,
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.pool.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkCommandBuffer {
@@ -6,10 +6,12 @@ use ash::Entry;
 
 use super::{
     debug::VkValidation, device::VkDevice, instance::VkInstance, physical_device::VkPhysicalDevice,
-    settings::VkSettings, surface::VkSurface,
+    requirements::VkDeviceRequirements, sampler::VkSamplerCache, settings::VkSettings,
+    surface::VkSurface,
 };
 
 pub struct VkContext {
+    pub samplers: VkSamplerCache,
     pub device: Arc<VkDevice>,
     pub physical_device: Arc<VkPhysicalDevice>,
     pub surface: VkSurface,
@@ -19,19 +21,38 @@ pub struct VkContext {
 }
 
 impl VkContext {
-    pub fn new(window: &Window, settings: &VkSettings) -> VkContext {
+    pub fn new(
+        window: &Window,
+        settings: &VkSettings,
+        requirements: &VkDeviceRequirements,
+    ) -> VkContext {
         let entry = Box::new(unsafe { Entry::new().expect("Failed to create Vulkan entry.") });
-        let instance = Arc::new(VkInstance::new(window, &entry, settings.validation));
-        let validation = if settings.validation {
+        VkValidation::set_fail_on_error(settings.fail_on_validation_error);
+        let instance = Arc::new(VkInstance::new(window, &entry, settings));
+        let validation = if instance.validation {
             Some(VkValidation::new(&entry, &instance))
         } else {
             None
         };
         let surface = VkSurface::new(&entry, &instance, window);
-        let physical_device = Arc::new(VkPhysicalDevice::new(&instance, &surface));
-        let device = Arc::new(VkDevice::new(&physical_device, &surface));
+        let physical_device = Arc::new(VkPhysicalDevice::new(
+            &instance,
+            &surface,
+            &settings.device,
+            settings.device_preference,
+            requirements,
+        ));
+        let debug_utils = validation.as_ref().map(VkValidation::debug_utils);
+        let device = Arc::new(VkDevice::new(
+            &physical_device,
+            &surface,
+            debug_utils,
+            requirements,
+        ));
+        let samplers = VkSamplerCache::new(&device);
 
         VkContext {
+            samplers,
             device,
             physical_device,
             surface,
@@ -1,16 +1,37 @@
-use std::ffi::{c_void, CStr, CString};
+use std::{
+    collections::BTreeMap,
+    ffi::{c_void, CStr, CString},
+    os::raw::c_char,
+    process, slice,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
 
 use ash::{extensions::ext::DebugUtils, vk, Entry};
+use log::Level;
 
-use super::instance::VkInstance;
+use super::{device::VkDevice, instance::VkInstance};
 
-const REQUIRED_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
+// The callback is also used while the instance is created, before any messenger exists, so its
+// state is global
+static FAIL_ON_ERROR: AtomicBool = AtomicBool::new(false);
+static ERROR_COUNT: AtomicUsize = AtomicUsize::new(0);
+// Times each message was reported by message id and text
+static MESSAGES: Mutex<BTreeMap<(i32, String), usize>> = Mutex::new(BTreeMap::new());
 
 pub struct VkValidation {
     extension: DebugUtils,
     messenger: vk::DebugUtilsMessengerEXT,
 }
 
+// Marks the commands recorded while it is alive, see `VkDevice::begin_label`
+pub struct VkDebugLabel<'a> {
+    device: &'a VkDevice,
+    command_buffer: vk::CommandBuffer,
+}
+
 impl VkValidation {
     pub fn new(entry: &ash::Entry, instance: &VkInstance) -> VkValidation {
         let extension = DebugUtils::new(entry, &instance.handle);
@@ -27,6 +48,19 @@ impl VkValidation {
             messenger,
         }
     }
+
+    pub fn debug_utils(&self) -> DebugUtils {
+        self.extension.clone()
+    }
+
+    pub fn error_count() -> usize {
+        ERROR_COUNT.load(Ordering::Relaxed)
+    }
+
+    // Aborts the process on the first validation error
+    pub fn set_fail_on_error(fail_on_error: bool) {
+        FAIL_ON_ERROR.store(fail_on_error, Ordering::Relaxed);
+    }
 }
 
 impl Drop for VkValidation {
@@ -36,55 +70,166 @@ impl Drop for VkValidation {
             self.extension
                 .destroy_debug_utils_messenger(self.messenger, None);
         }
+
+        for ((id, message), count) in MESSAGES.lock().unwrap().iter() {
+            if *count > 1 {
+                log::warn!(
+                    "[Vulkan] Message {:#x} was reported {} times: {}",
+                    id,
+                    count,
+                    message
+                );
+            }
+        }
+        let error_count = Self::error_count();
+        if error_count > 0 {
+            log::error!("[Vulkan] {} validation errors were reported", error_count);
+        }
     }
 }
 
-pub fn get_validation_layers() -> Vec<CString> {
-    REQUIRED_LAYERS
-        .iter()
-        .map(|name| CString::new(*name).unwrap())
-        .collect()
+impl<'a> VkDebugLabel<'a> {
+    pub fn new(device: &'a VkDevice, command_buffer: vk::CommandBuffer) -> VkDebugLabel<'a> {
+        VkDebugLabel {
+            device,
+            command_buffer,
+        }
+    }
 }
 
-pub fn check_validation_layer_support(entry: &Entry) {
+impl Drop for VkDebugLabel<'_> {
+    fn drop(&mut self) {
+        self.device.end_label(self.command_buffer);
+    }
+}
+
+// Requested layers which are not installed are skipped with a warning
+pub fn find_layers(entry: &Entry, requested: &[String]) -> Vec<CString> {
     let available_layers = entry
         .enumerate_instance_layer_properties()
         .expect("Failed to enumerate Instance Layers Properties");
-
-    for required in &REQUIRED_LAYERS {
-        let found = available_layers.iter().any(|layer| {
+    let available_names: Vec<String> = available_layers
+        .iter()
+        .map(|layer| {
             let name = unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) };
-            let name = name.to_str().expect("Failed to get layer name pointer");
-            required == &name
-        });
+            name.to_string_lossy().into_owned()
+        })
+        .collect();
 
-        if !found {
-            panic!("Validation layer not supported: {}", required);
-        }
+    requested
+        .iter()
+        .filter(|&required| {
+            let found = available_names.contains(required);
+            if !found {
+                log::warn!("Layer {} is not available and is skipped", required);
+            }
+            found
+        })
+        .map(|name| CString::new(name.as_str()).unwrap())
+        .collect()
+}
+
+// Returns false for messages which were already logged
+fn first_occurrence(id: i32, message: &str) -> bool {
+    let mut messages = MESSAGES.lock().unwrap();
+    let count = messages.entry((id, message.to_owned())).or_insert(0);
+    *count += 1;
+    *count == 1
+}
+
+fn log_level(severity: vk::DebugUtilsMessageSeverityFlagsEXT) -> Level {
+    if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        Level::Error
+    } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        Level::Warn
+    } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        Level::Info
+    } else {
+        Level::Debug
+    }
+}
+
+fn type_names(message_type: vk::DebugUtilsMessageTypeFlagsEXT) -> String {
+    [
+        (vk::DebugUtilsMessageTypeFlagsEXT::GENERAL, "General"),
+        (vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION, "Validation"),
+        (
+            vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            "Performance",
+        ),
+    ]
+    .iter()
+    .filter(|(flag, _)| message_type.contains(*flag))
+    .map(|(_, name)| format!("[{}]", name))
+    .collect()
+}
+
+unsafe fn string_or_empty(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
     }
 }
 
+unsafe fn describe_objects(data: &vk::DebugUtilsMessengerCallbackDataEXT) -> String {
+    if data.p_objects.is_null() || data.object_count == 0 {
+        return String::new();
+    }
+    let objects = slice::from_raw_parts(data.p_objects, data.object_count as usize)
+        .iter()
+        .map(|object| {
+            let name = string_or_empty(object.p_object_name);
+            if name.is_empty() {
+                format!("{:?} {:#x}", object.object_type, object.object_handle)
+            } else {
+                format!(
+                    "{:?} {:#x} '{}'",
+                    object.object_type, object.object_handle, name
+                )
+            }
+        })
+        .collect::<Vec<_>>();
+    format!(" (objects: {})", objects.join(", "))
+}
+
 unsafe extern "system" fn vulkan_debug_utils_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
     _p_user_data: *mut c_void,
 ) -> vk::Bool32 {
-    let severity = match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => "[Verbose]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => "[Warning]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => "[Error]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => "[Info]",
-        _ => "[Unknown]",
-    };
-    let types = match message_type {
-        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "[General]",
-        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "[Performance]",
-        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "[Validation]",
-        _ => "[Unknown]",
-    };
-    let message = CStr::from_ptr((*p_callback_data).p_message);
-    log::debug!("[Vulkan] {}{}{:?}", severity, types, message);
+    let data = &*p_callback_data;
+    let is_error = message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR);
+    if is_error {
+        ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Validation messages repeat the VUID and the objects before the description
+    let message = string_or_empty(data.p_message);
+    if first_occurrence(data.message_id_number, &message) {
+        let description = message.rsplit(" | ").next().unwrap_or_default();
+        let id_name = string_or_empty(data.p_message_id_name);
+        let id_name = if id_name.is_empty() {
+            id_name
+        } else {
+            format!(" {}:", id_name)
+        };
+        log::log!(
+            log_level(message_severity),
+            "[Vulkan]{}{} {}{}",
+            type_names(message_type),
+            id_name,
+            description,
+            describe_objects(data)
+        );
+    }
+
+    // Aborting keeps the stack of the failing call for debuggers and fails CI runs
+    if is_error && FAIL_ON_ERROR.load(Ordering::Relaxed) {
+        log::error!("Aborting on Vulkan validation error");
+        process::abort();
+    }
 
     vk::FALSE
 }
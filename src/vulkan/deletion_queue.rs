@@ -0,0 +1,42 @@
+use std::{any::Any, collections::VecDeque};
+
+// Keeps retired resources alive until the frames which may still use them have finished on the
+// GPU. Frames are numbered from 1 in submission order, resources retired after frame `n` was
+// submitted are dropped once frame `n` has completed.
+#[derive(Default)]
+pub struct VkDeletionQueue {
+    submitted: u64,
+    completed: u64,
+    retired: VecDeque<(u64, Box<dyn Any>)>,
+}
+
+impl VkDeletionQueue {
+    pub fn new() -> VkDeletionQueue {
+        VkDeletionQueue::default()
+    }
+
+    pub fn retire<T: 'static>(&mut self, resource: T) {
+        if self.completed == self.submitted {
+            // No frame is in flight which could use the resource
+            return;
+        }
+        self.retired.push_back((self.submitted, Box::new(resource)));
+    }
+
+    // Returns the number of the frame that was just submitted
+    pub fn frame_submitted(&mut self) -> u64 {
+        self.submitted += 1;
+        self.submitted
+    }
+
+    // Frames complete in submission order, so this also completes all earlier frames
+    pub fn frame_completed(&mut self, frame: u64) {
+        self.completed = self.completed.max(frame);
+        while let Some((frame, _)) = self.retired.front() {
+            if *frame > self.completed {
+                break;
+            }
+            self.retired.pop_front();
+        }
+    }
+}
@@ -21,16 +21,22 @@ impl VkDescriptorSetLayout {
                 .create_descriptor_set_layout(&layout_info, None)
                 .expect("Unable to create descriptor set layout")
         };
+        device.track(handle, "descriptor set layout");
         VkDescriptorSetLayout {
             device: Arc::clone(device),
             handle,
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkDescriptorSetLayout {
     fn drop(&mut self) {
         log::debug!("Dropping descriptor set layout");
+        self.device.untrack(self.handle);
         unsafe {
             self.device
                 .handle
@@ -60,6 +66,7 @@ impl VkDescriptorPool {
                 .create_descriptor_pool(&create_info, None)
                 .expect("Unable to create descriptor pool")
         };
+        device.track(handle, "descriptor pool");
 
         VkDescriptorPool {
             device: Arc::clone(device),
@@ -85,19 +92,15 @@ impl VkDescriptorPool {
         }
     }
 
-    pub fn reset_descriptor_sets(&self) {
-        unsafe {
-            self.device
-                .handle
-                .reset_descriptor_pool(self.handle, vk::DescriptorPoolResetFlags::empty())
-                .expect("Resetting descriptor pool failed");
-        }
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
     }
 }
 
 impl Drop for VkDescriptorPool {
     fn drop(&mut self) {
         log::debug!("Dropping descriptor pool");
+        self.device.untrack(self.handle);
         unsafe {
             self.device
                 .handle
@@ -1,60 +1,80 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    ffi::{CStr, CString},
+    sync::Arc,
+};
 
-use ash::vk;
+use ash::{
+    extensions::{ext::DebugUtils, khr::Synchronization2},
+    vk::{self, Handle},
+};
 
 use super::{
-    command::VkCommandBuffer, physical_device::VkPhysicalDevice, queue_family::VkQueueFamily,
-    surface::VkSurface, utils, VkBuffer, VkCommandPool, VkFence,
+    command::VkCommandBuffer,
+    debug::VkDebugLabel,
+    features::VkFeatures,
+    physical_device::VkPhysicalDevice,
+    queue::{VkQueue, VkQueueLayout, VkQueues},
+    requirements::{VkDeviceCapabilities, VkDeviceRequirements},
+    surface::VkSurface,
+    tracker, utils, VkCommandPool,
 };
 
 pub struct VkDevice {
     pub physical_device: Arc<VkPhysicalDevice>,
     pub handle: ash::Device,
-
-    // TODO: Remove these from VkDevice
-    pub graphics_queue: vk::Queue,
-    pub graphics_queue_family: u32,
-    pub presentation_queue: vk::Queue,
-    pub presentation_queue_family: u32,
+    // Only available with validation, names and labels are skipped otherwise
+    debug_utils: Option<DebugUtils>,
+    // The required and supported optional features and extensions
+    capabilities: VkDeviceCapabilities,
+    // Loaded when VK_KHR_synchronization2 and its feature are enabled
+    synchronization2: Option<Synchronization2>,
+    pub queues: VkQueues,
 }
 
 impl VkDevice {
-    pub fn new(physical_device: &Arc<VkPhysicalDevice>, surface: &VkSurface) -> VkDevice {
-        let graphics_queue_family = find_queue_family(physical_device, |family| {
-            family.flags.contains(vk::QueueFlags::GRAPHICS)
-        });
-        log::info!("Choosing graphics queue family: {}", graphics_queue_family);
-
-        let presentation_queue_family = find_queue_family(physical_device, |family| {
-            surface.physical_device_queue_support(physical_device, family.index)
-        });
+    pub fn new(
+        physical_device: &Arc<VkPhysicalDevice>,
+        surface: &VkSurface,
+        debug_utils: Option<DebugUtils>,
+        requirements: &VkDeviceRequirements,
+    ) -> VkDevice {
+        let queue_layout = VkQueueLayout::new(physical_device, surface);
+        let queue_counts = queue_layout.queue_counts();
+        let queue_priorities = [1.0f32; 4];
+        let queue_infos: Vec<_> = queue_counts
+            .iter()
+            .map(|(&family, &count)| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(family)
+                    .queue_priorities(&queue_priorities[..count as usize])
+                    .build()
+            })
+            .collect();
+
+        let capabilities = requirements.negotiate(physical_device);
         log::info!(
-            "Choosing presentation queue family: {}",
-            presentation_queue_family
+            "Enabling {} features and extensions {:?}",
+            capabilities.features.count(),
+            capabilities.extensions
         );
-
-        let mut unique_queue_families = HashSet::new();
-        unique_queue_families.insert(graphics_queue_family);
-        unique_queue_families.insert(presentation_queue_family);
-
-        let queue_priorities = [1.0f32];
-        let mut queue_infos = Vec::new();
-        for &queue_family in &unique_queue_families {
-            let queue_create_info = vk::DeviceQueueCreateInfo::builder()
-                .queue_family_index(queue_family)
-                .queue_priorities(&queue_priorities)
-                .build();
-            queue_infos.push(queue_create_info);
-        }
-
-        let extensions = VkPhysicalDevice::get_required_device_extensions();
-        let extension_names = utils::as_raw_handles(&extensions);
-        let physical_device_features =
-            vk::PhysicalDeviceFeatures::builder().sampler_anisotropy(true);
-        let device_create_info = vk::DeviceCreateInfo::builder()
+        let extension_names = utils::as_raw_handles(&capabilities.extensions);
+        let mut features = capabilities.features;
+        let mut device_create_info = vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_infos)
-            .enabled_features(&physical_device_features)
             .enabled_extension_names(&extension_names);
+        // Features of later versions are chained, which is only valid on 1.2 devices
+        let mut features2 = vk::PhysicalDeviceFeatures2::builder().features(features.v1_0);
+        if features.needs_chain() {
+            features2 = features2
+                .push_next(&mut features.v1_1)
+                .push_next(&mut features.v1_2);
+            if features.synchronization2.synchronization2 == vk::TRUE {
+                features2 = features2.push_next(&mut features.synchronization2);
+            }
+            device_create_info = device_create_info.push_next(&mut features2);
+        } else {
+            device_create_info = device_create_info.enabled_features(&features.v1_0);
+        }
         let handle = unsafe {
             physical_device
                 .instance
@@ -63,16 +83,27 @@ impl VkDevice {
                 .expect("Unable to create logical device")
         };
 
-        let graphics_queue = unsafe { handle.get_device_queue(graphics_queue_family, 0) };
-        let presentation_queue = unsafe { handle.get_device_queue(presentation_queue_family, 0) };
+        tracker::register_device(handle.handle());
+
+        let queues = VkQueues::new(&handle, &queue_layout);
+        let synchronization2 = if capabilities.has_extension(Synchronization2::name())
+            && features.synchronization2.synchronization2 == vk::TRUE
+        {
+            Some(Synchronization2::new(
+                &physical_device.instance.handle,
+                &handle,
+            ))
+        } else {
+            None
+        };
 
         VkDevice {
             physical_device: Arc::clone(physical_device),
             handle,
-            graphics_queue,
-            graphics_queue_family,
-            presentation_queue,
-            presentation_queue_family,
+            debug_utils,
+            capabilities,
+            synchronization2,
+            queues,
         }
     }
 
@@ -94,32 +125,63 @@ impl VkDevice {
         panic!("Failed to find suitable memory type.")
     }
 
-    pub fn wait_idle(&self) {
-        log::debug!("Waiting device idle");
+    // Debug builds record the objects created from the device, objects still alive when the
+    // device is dropped are reported together with where they were created. Objects outliving
+    // their device are only noticed when they are created or destroyed, not when they are used.
+    pub fn track<H: Handle>(&self, handle: H, label: &str) {
+        tracker::track(self.handle.handle(), handle, label);
+    }
 
-        unsafe {
-            self.handle
-                .device_wait_idle()
-                .expect("Failed to wait device idle!")
-        };
+    pub fn untrack<H: Handle>(&self, handle: H) {
+        tracker::untrack(self.handle.handle(), handle);
     }
 
-    pub fn wait_for_fences(&self, fences: &[&VkFence]) {
-        let fence_handles = utils::as_raw_handles(fences);
+    // Shown by validation messages and debuggers such as RenderDoc, also used as the label of
+    // tracked objects
+    pub fn set_object_name<H: Handle + Copy>(&self, handle: H, name: &str) {
+        tracker::rename(self.handle.handle(), handle, name);
+
+        let debug_utils = match &self.debug_utils {
+            Some(debug_utils) => debug_utils,
+            None => return,
+        };
+        let name = debug_name(name);
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(H::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name);
         unsafe {
-            self.handle
-                .wait_for_fences(&fence_handles, true, std::u64::MAX)
-                .expect("Waiting for fence failed");
+            debug_utils
+                .debug_utils_set_object_name(self.handle.handle(), &name_info)
+                .expect("Unable to set object name");
+        }
+    }
+
+    // Labels the commands recorded until the returned label is dropped
+    pub fn begin_label(&self, command_buffer: &VkCommandBuffer, name: &str) -> VkDebugLabel<'_> {
+        if let Some(debug_utils) = &self.debug_utils {
+            let name = debug_name(name);
+            let label = vk::DebugUtilsLabelEXT::builder().label_name(&name);
+            unsafe { debug_utils.cmd_begin_debug_utils_label(command_buffer.handle, &label) };
+        }
+        VkDebugLabel::new(self, command_buffer.handle)
+    }
+
+    pub fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(debug_utils) = &self.debug_utils {
+            unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) };
         }
     }
 
-    pub fn reset_fences(&self, fences: &[&VkFence]) {
-        let fence_handles = utils::as_raw_handles(fences);
+    pub fn wait_idle(&self) {
+        log::debug!("Waiting device idle");
+
+        let _queues = self.queues.lock_all();
         unsafe {
             self.handle
-                .reset_fences(&fence_handles)
-                .expect("Fence reset failed");
-        }
+                .device_wait_idle()
+                .expect("Failed to wait device idle!")
+        };
     }
 
     pub fn _get_mem_properties(&self) -> vk::PhysicalDeviceMemoryProperties {
@@ -134,6 +196,19 @@ impl VkDevice {
         self.physical_device.get_features()
     }
 
+    // Features enabled on the device, optional features are only enabled when supported
+    pub fn enabled_features(&self) -> &VkFeatures {
+        &self.capabilities.features
+    }
+
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        self.capabilities.has_extension(name)
+    }
+
+    pub fn synchronization2(&self) -> Option<&Synchronization2> {
+        self.synchronization2.as_ref()
+    }
+
     pub fn get_properties(&self) -> vk::PhysicalDeviceProperties {
         self.physical_device.get_properties()
     }
@@ -142,12 +217,23 @@ impl VkDevice {
         self.physical_device.get_max_usable_sample_count()
     }
 
-    pub fn execute_one_time_commands(
+    // The command buffer has to be kept alive until the queue has reached the returned value
+    pub fn submit_one_time_commands(
+        &self,
+        pool: &Arc<VkCommandPool>,
+        queue: &VkQueue,
+        executor: impl FnOnce(&VkDevice, &VkCommandBuffer),
+    ) -> (VkCommandBuffer, u64) {
+        let command_buffer = self.record_one_time_commands(pool, executor);
+        let value = queue.submit(&[command_buffer.handle], &[], &[]);
+        (command_buffer, value)
+    }
+
+    fn record_one_time_commands(
         &self,
         pool: &Arc<VkCommandPool>,
-        queue: vk::Queue,
         executor: impl FnOnce(&VkDevice, &VkCommandBuffer),
-    ) {
+    ) -> VkCommandBuffer {
         let command_buffer = VkCommandBuffer::new(pool, true);
 
         let command_begin_info = vk::CommandBufferBeginInfo::builder()
@@ -165,55 +251,36 @@ impl VkDevice {
             self.handle
                 .end_command_buffer(command_buffer.handle)
                 .expect("Unable to end command buffer");
-
-            let command_buffers = [command_buffer.handle];
-            let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
-            let infos = [submit_info.build()];
-            self.handle
-                .queue_submit(queue, &infos, vk::Fence::null())
-                .expect("Unable to submit queue");
-            self.handle
-                .queue_wait_idle(queue)
-                .expect("Unable to wait for queue idle state");
         }
-    }
 
-    pub fn copy_buffer(
-        &self,
-        src: &VkBuffer,
-        dst: &VkBuffer,
-        command_pool: &Arc<VkCommandPool>,
-        queue: vk::Queue,
-    ) {
-        self.execute_one_time_commands(command_pool, queue, |device, command_buffer| unsafe {
-            let regions = [vk::BufferCopy {
-                src_offset: 0,
-                dst_offset: 0,
-                size: src.size,
-            }];
-            device
-                .handle
-                .cmd_copy_buffer(command_buffer.handle, src.handle, dst.handle, &regions);
-        });
+        command_buffer
     }
 }
 
 impl Drop for VkDevice {
     fn drop(&mut self) {
         log::debug!("Dropping logical device");
+        self.queues.destroy(&self.handle);
+        // Reports the leaked objects once, right before the device is destroyed
+        tracker::unregister_device(self.handle.handle());
         unsafe {
             self.handle.destroy_device(None);
         }
     }
 }
 
-fn find_queue_family(
-    physical_device: &VkPhysicalDevice,
-    predicate: impl Fn(&VkQueueFamily) -> bool,
-) -> u32 {
-    physical_device
-        .queue_families
-        .iter()
-        .position(|family| family.queue_count > 0 && predicate(family))
-        .expect("Unable to find suitable queue family") as u32
+// Names come from asset paths and such, interior NUL characters are dropped
+fn debug_name(name: &str) -> CString {
+    CString::new(name.replace('\0', "")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_names_drop_nul_characters() {
+        assert_eq!(debug_name("scene pass").to_bytes(), b"scene pass");
+        assert_eq!(debug_name("a\0b\0").to_bytes(), b"ab");
+    }
 }
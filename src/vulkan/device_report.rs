@@ -0,0 +1,569 @@
+use std::{fmt::Write, sync::Arc};
+
+use ash::{vk, Entry};
+use winit::window::Window;
+
+use super::{
+    features::VkFeatures, instance::VkInstance, physical_device::VkPhysicalDevice,
+    settings::VkSettings, surface::VkSurface, utils::coerce_string, version::VkVersion,
+};
+
+// Lists struct members by name, the values are converted with `ToReport`
+macro_rules! report_fields {
+    ($source:expr, [$($field:ident),* $(,)?]) => {
+        vec![$((stringify!($field), $source.$field.to_report())),*]
+    };
+}
+
+// Same for `vk::Bool32` members, which would otherwise be reported as numbers
+macro_rules! report_flags {
+    ($source:expr, [$($field:ident),* $(,)?]) => {
+        vec![$((stringify!($field), Report::Bool($source.$field == vk::TRUE))),*]
+    };
+}
+
+enum Report {
+    Bool(bool),
+    Number(String),
+    Text(String),
+    List(Vec<Report>),
+    Object(Vec<(String, Report)>),
+}
+
+trait ToReport {
+    fn to_report(&self) -> Report;
+}
+
+// Everything the instance knows about each physical device, including how it can present to the
+// window surface
+pub struct VkDeviceReport {
+    root: Report,
+}
+
+impl VkDeviceReport {
+    pub fn new(window: &Window, settings: &VkSettings) -> VkDeviceReport {
+        let entry = unsafe { Entry::new().expect("Failed to create Vulkan entry.") };
+        let instance = Arc::new(VkInstance::new(window, &entry, settings));
+        let surface = VkSurface::new(&entry, &instance, window);
+
+        let instance_version = entry
+            .try_enumerate_instance_version()
+            .expect("Unable to query instance version")
+            .unwrap_or(vk::API_VERSION_1_0);
+        let devices = VkPhysicalDevice::enumerate(&instance)
+            .iter()
+            .enumerate()
+            .map(|(index, device)| report_device(index, device, &surface))
+            .collect();
+
+        let root = object(vec![
+            (
+                "instance_version",
+                Report::Text(VkVersion::parse(instance_version).to_string()),
+            ),
+            ("devices", Report::List(devices)),
+        ]);
+        VkDeviceReport { root }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        write_json(&mut json, &self.root, 0);
+        json.push('\n');
+        json
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if let Report::Object(members) = &self.root {
+            write_text_members(&mut text, members, 0);
+        }
+        text
+    }
+}
+
+fn report_device(index: usize, device: &VkPhysicalDevice, surface: &VkSurface) -> Report {
+    let properties = device.get_properties();
+    object(vec![
+        ("index", index.to_report()),
+        ("name", Report::Text(device.name.clone())),
+        ("properties", report_properties(&properties)),
+        ("limits", report_limits(&properties.limits)),
+        (
+            "sparse_properties",
+            report_sparse_properties(&properties.sparse_properties),
+        ),
+        (
+            "features",
+            report_features(&VkFeatures::supported(device), properties.api_version),
+        ),
+        ("extensions", report_extensions(device)),
+        ("memory", report_memory(&device.get_mem_properties())),
+        ("queue_families", report_queue_families(device, surface)),
+        ("surface", report_surface(device, surface)),
+    ])
+}
+
+fn report_properties(properties: &vk::PhysicalDeviceProperties) -> Report {
+    let uuid = properties
+        .pipeline_cache_uuid
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    object(vec![
+        (
+            "api_version",
+            Report::Text(VkVersion::parse(properties.api_version).to_string()),
+        ),
+        // The encoding is vendor specific
+        ("driver_version", properties.driver_version.to_report()),
+        (
+            "vendor_id",
+            Report::Text(format!("{:#06x}", properties.vendor_id)),
+        ),
+        (
+            "device_id",
+            Report::Text(format!("{:#06x}", properties.device_id)),
+        ),
+        ("device_type", properties.device_type.to_report()),
+        ("pipeline_cache_uuid", Report::Text(uuid)),
+    ])
+}
+
+fn report_limits(limits: &vk::PhysicalDeviceLimits) -> Report {
+    let mut fields = report_fields!(
+        limits,
+        [
+            max_image_dimension1_d,
+            max_image_dimension2_d,
+            max_image_dimension3_d,
+            max_image_dimension_cube,
+            max_image_array_layers,
+            max_texel_buffer_elements,
+            max_uniform_buffer_range,
+            max_storage_buffer_range,
+            max_push_constants_size,
+            max_memory_allocation_count,
+            max_sampler_allocation_count,
+            buffer_image_granularity,
+            sparse_address_space_size,
+            max_bound_descriptor_sets,
+            max_per_stage_descriptor_samplers,
+            max_per_stage_descriptor_uniform_buffers,
+            max_per_stage_descriptor_storage_buffers,
+            max_per_stage_descriptor_sampled_images,
+            max_per_stage_descriptor_storage_images,
+            max_per_stage_descriptor_input_attachments,
+            max_per_stage_resources,
+            max_descriptor_set_samplers,
+            max_descriptor_set_uniform_buffers,
+            max_descriptor_set_uniform_buffers_dynamic,
+            max_descriptor_set_storage_buffers,
+            max_descriptor_set_storage_buffers_dynamic,
+            max_descriptor_set_sampled_images,
+            max_descriptor_set_storage_images,
+            max_descriptor_set_input_attachments,
+            max_vertex_input_attributes,
+            max_vertex_input_bindings,
+            max_vertex_input_attribute_offset,
+            max_vertex_input_binding_stride,
+            max_vertex_output_components,
+            max_tessellation_generation_level,
+            max_tessellation_patch_size,
+            max_tessellation_control_per_vertex_input_components,
+            max_tessellation_control_per_vertex_output_components,
+            max_tessellation_control_per_patch_output_components,
+            max_tessellation_control_total_output_components,
+            max_tessellation_evaluation_input_components,
+            max_tessellation_evaluation_output_components,
+            max_geometry_shader_invocations,
+            max_geometry_input_components,
+            max_geometry_output_components,
+            max_geometry_output_vertices,
+            max_geometry_total_output_components,
+            max_fragment_input_components,
+            max_fragment_output_attachments,
+            max_fragment_dual_src_attachments,
+            max_fragment_combined_output_resources,
+            max_compute_shared_memory_size,
+            max_compute_work_group_count,
+            max_compute_work_group_invocations,
+            max_compute_work_group_size,
+            sub_pixel_precision_bits,
+            sub_texel_precision_bits,
+            mipmap_precision_bits,
+            max_draw_indexed_index_value,
+            max_draw_indirect_count,
+            max_sampler_lod_bias,
+            max_sampler_anisotropy,
+            max_viewports,
+            max_viewport_dimensions,
+            viewport_bounds_range,
+            viewport_sub_pixel_bits,
+            min_memory_map_alignment,
+            min_texel_buffer_offset_alignment,
+            min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment,
+            min_texel_offset,
+            max_texel_offset,
+            min_texel_gather_offset,
+            max_texel_gather_offset,
+            min_interpolation_offset,
+            max_interpolation_offset,
+            sub_pixel_interpolation_offset_bits,
+            max_framebuffer_width,
+            max_framebuffer_height,
+            max_framebuffer_layers,
+            framebuffer_color_sample_counts,
+            framebuffer_depth_sample_counts,
+            framebuffer_stencil_sample_counts,
+            framebuffer_no_attachments_sample_counts,
+            max_color_attachments,
+            sampled_image_color_sample_counts,
+            sampled_image_integer_sample_counts,
+            sampled_image_depth_sample_counts,
+            sampled_image_stencil_sample_counts,
+            storage_image_sample_counts,
+            max_sample_mask_words,
+            timestamp_period,
+            max_clip_distances,
+            max_cull_distances,
+            max_combined_clip_and_cull_distances,
+            discrete_queue_priorities,
+            point_size_range,
+            line_width_range,
+            point_size_granularity,
+            line_width_granularity,
+            optimal_buffer_copy_offset_alignment,
+            optimal_buffer_copy_row_pitch_alignment,
+            non_coherent_atom_size,
+        ]
+    );
+    fields.extend(report_flags!(
+        limits,
+        [
+            timestamp_compute_and_graphics,
+            strict_lines,
+            standard_sample_locations,
+        ]
+    ));
+    object(fields)
+}
+
+fn report_sparse_properties(properties: &vk::PhysicalDeviceSparseProperties) -> Report {
+    object(report_flags!(
+        properties,
+        [
+            residency_standard2_d_block_shape,
+            residency_standard2_d_multisample_block_shape,
+            residency_standard3_d_block_shape,
+            residency_aligned_mip_size,
+            residency_non_resident_strict,
+        ]
+    ))
+}
+
+// Groups which were not queried are reported as unavailable instead of unsupported
+fn report_features(features: &VkFeatures, api_version: u32) -> Report {
+    let groups = features
+        .list()
+        .into_iter()
+        .enumerate()
+        .map(|(index, (version, fields))| {
+            if index > 0 && !VkFeatures::is_queried(api_version) {
+                return (version, Report::Text("not queried".to_owned()));
+            }
+            let fields = fields
+                .into_iter()
+                .map(|(name, supported)| (name, Report::Bool(supported)))
+                .collect();
+            (version, object(fields))
+        })
+        .collect();
+    object(groups)
+}
+
+fn report_extensions(device: &VkPhysicalDevice) -> Report {
+    let mut extensions = unsafe {
+        device
+            .instance
+            .handle
+            .enumerate_device_extension_properties(device.handle)
+            .expect("Unable to query device extensions")
+    };
+    extensions.sort_by_key(|extension| coerce_string(&extension.extension_name));
+    let extensions = extensions
+        .iter()
+        .map(|extension| {
+            object(vec![
+                (
+                    "name",
+                    Report::Text(coerce_string(&extension.extension_name)),
+                ),
+                ("spec_version", extension.spec_version.to_report()),
+            ])
+        })
+        .collect();
+    Report::List(extensions)
+}
+
+fn report_memory(memory: &vk::PhysicalDeviceMemoryProperties) -> Report {
+    let heaps = memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .map(|heap| {
+            object(vec![
+                ("size", heap.size.to_report()),
+                ("flags", heap.flags.to_report()),
+            ])
+        })
+        .collect();
+    let types = memory.memory_types[..memory.memory_type_count as usize]
+        .iter()
+        .map(|memory_type| {
+            object(vec![
+                ("heap_index", memory_type.heap_index.to_report()),
+                ("flags", memory_type.property_flags.to_report()),
+            ])
+        })
+        .collect();
+    object(vec![
+        ("heaps", Report::List(heaps)),
+        ("types", Report::List(types)),
+    ])
+}
+
+fn report_queue_families(device: &VkPhysicalDevice, surface: &VkSurface) -> Report {
+    let families = unsafe {
+        device
+            .instance
+            .handle
+            .get_physical_device_queue_family_properties(device.handle)
+    };
+    let families = families
+        .iter()
+        .enumerate()
+        .map(|(index, family)| {
+            let present = surface.physical_device_queue_support(device, index as u32);
+            object(vec![
+                ("index", index.to_report()),
+                ("queue_count", family.queue_count.to_report()),
+                ("flags", family.queue_flags.to_report()),
+                (
+                    "timestamp_valid_bits",
+                    family.timestamp_valid_bits.to_report(),
+                ),
+                (
+                    "min_image_transfer_granularity",
+                    family.min_image_transfer_granularity.to_report(),
+                ),
+                ("present", Report::Bool(present)),
+            ])
+        })
+        .collect();
+    Report::List(families)
+}
+
+fn report_surface(device: &VkPhysicalDevice, surface: &VkSurface) -> Report {
+    let surface_caps = surface.get_physical_device_surface_capabilities(device);
+    let capabilities = &surface_caps.capabilities;
+    let formats = surface_caps
+        .formats
+        .iter()
+        .map(|format| {
+            object(vec![
+                ("format", format.format.to_report()),
+                ("color_space", format.color_space.to_report()),
+            ])
+        })
+        .collect();
+    let present_modes = surface_caps
+        .present_modes
+        .iter()
+        .map(ToReport::to_report)
+        .collect();
+    object(vec![
+        (
+            "capabilities",
+            object(report_fields!(
+                capabilities,
+                [
+                    min_image_count,
+                    max_image_count,
+                    current_extent,
+                    min_image_extent,
+                    max_image_extent,
+                    max_image_array_layers,
+                    supported_transforms,
+                    current_transform,
+                    supported_composite_alpha,
+                    supported_usage_flags,
+                ]
+            )),
+        ),
+        ("formats", Report::List(formats)),
+        ("present_modes", Report::List(present_modes)),
+    ])
+}
+
+fn object(members: Vec<(&str, Report)>) -> Report {
+    Report::Object(
+        members
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect(),
+    )
+}
+
+macro_rules! number_to_report {
+    ($($type:ty),*) => {
+        $(impl ToReport for $type {
+            fn to_report(&self) -> Report {
+                Report::Number(self.to_string())
+            }
+        })*
+    };
+}
+
+// Vulkan enums and flags are reported by their names
+macro_rules! debug_to_report {
+    ($($type:ty),*) => {
+        $(impl ToReport for $type {
+            fn to_report(&self) -> Report {
+                Report::Text(format!("{:?}", self))
+            }
+        })*
+    };
+}
+
+number_to_report!(u32, i32, u64, usize);
+
+debug_to_report!(
+    vk::PhysicalDeviceType,
+    vk::SampleCountFlags,
+    vk::MemoryHeapFlags,
+    vk::MemoryPropertyFlags,
+    vk::QueueFlags,
+    vk::Format,
+    vk::ColorSpaceKHR,
+    vk::PresentModeKHR,
+    vk::SurfaceTransformFlagsKHR,
+    vk::CompositeAlphaFlagsKHR,
+    vk::ImageUsageFlags
+);
+
+impl ToReport for f32 {
+    fn to_report(&self) -> Report {
+        if self.is_finite() {
+            Report::Number(self.to_string())
+        } else {
+            Report::Text(self.to_string())
+        }
+    }
+}
+
+impl<T: ToReport, const N: usize> ToReport for [T; N] {
+    fn to_report(&self) -> Report {
+        Report::List(self.iter().map(ToReport::to_report).collect())
+    }
+}
+
+impl ToReport for vk::Extent2D {
+    fn to_report(&self) -> Report {
+        object(report_fields!(self, [width, height]))
+    }
+}
+
+impl ToReport for vk::Extent3D {
+    fn to_report(&self) -> Report {
+        object(report_fields!(self, [width, height, depth]))
+    }
+}
+
+fn write_json(out: &mut String, value: &Report, indent: usize) {
+    match value {
+        Report::Bool(value) => write!(out, "{}", value).unwrap(),
+        Report::Number(value) => out.push_str(value),
+        Report::Text(value) => write_json_string(out, value),
+        Report::List(items) if items.is_empty() => out.push_str("[]"),
+        Report::List(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write!(out, "\n{:width$}", "", width = indent + 2).unwrap();
+                write_json(out, item, indent + 2);
+            }
+            write!(out, "\n{:width$}]", "", width = indent).unwrap();
+        }
+        Report::Object(members) if members.is_empty() => out.push_str("{}"),
+        Report::Object(members) => {
+            out.push('{');
+            for (index, (name, member)) in members.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write!(out, "\n{:width$}", "", width = indent + 2).unwrap();
+                write_json_string(out, name);
+                out.push_str(": ");
+                write_json(out, member, indent + 2);
+            }
+            write!(out, "\n{:width$}}}", "", width = indent).unwrap();
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_text_members(out: &mut String, members: &[(String, Report)], indent: usize) {
+    for (name, member) in members {
+        write!(out, "{:width$}{}:", "", name, width = indent).unwrap();
+        write_text(out, member, indent);
+    }
+}
+
+// Scalars and lists of scalars stay on the line of their name, nested values are indented below
+fn write_text(out: &mut String, value: &Report, indent: usize) {
+    match value {
+        Report::List(items) if items.iter().all(is_scalar) => {
+            let items: Vec<_> = items.iter().map(scalar_text).collect();
+            writeln!(out, " [{}]", items.join(", ")).unwrap();
+        }
+        Report::List(items) => {
+            out.push('\n');
+            for (index, item) in items.iter().enumerate() {
+                write!(out, "{:width$}[{}]:", "", index, width = indent + 2).unwrap();
+                write_text(out, item, indent + 2);
+            }
+        }
+        Report::Object(members) => {
+            out.push('\n');
+            write_text_members(out, members, indent + 2);
+        }
+        scalar => writeln!(out, " {}", scalar_text(scalar)).unwrap(),
+    }
+}
+
+fn is_scalar(value: &Report) -> bool {
+    !matches!(value, Report::List(_) | Report::Object(_))
+}
+
+fn scalar_text(value: &Report) -> String {
+    match value {
+        Report::Bool(value) => value.to_string(),
+        Report::Number(value) | Report::Text(value) => value.clone(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,299 @@
+use ash::{extensions::khr::Synchronization2, vk};
+
+use super::physical_device::VkPhysicalDevice;
+
+type FeatureFields<'a> = Vec<(&'static str, &'a mut vk::Bool32)>;
+
+// Lists the boolean members of a feature struct by name
+macro_rules! feature_fields {
+    ($name:ident, $type:ty, [$($field:ident),* $(,)?]) => {
+        fn $name(features: &mut $type) -> FeatureFields<'_> {
+            vec![$((stringify!($field), &mut features.$field)),*]
+        }
+    };
+}
+
+// Core features of each API version and features of extensions, the structs are left empty on
+// devices which do not support them. Only the 1.0 features are queried on devices older than 1.2.
+#[derive(Clone, Copy, Default)]
+pub struct VkFeatures {
+    pub v1_0: vk::PhysicalDeviceFeatures,
+    pub v1_1: vk::PhysicalDeviceVulkan11Features,
+    pub v1_2: vk::PhysicalDeviceVulkan12Features,
+    // Requires VK_KHR_synchronization2
+    pub synchronization2: vk::PhysicalDeviceSynchronization2FeaturesKHR,
+}
+
+// The structs are never chained, their `p_next` pointers stay null
+unsafe impl Send for VkFeatures {}
+unsafe impl Sync for VkFeatures {}
+
+impl VkFeatures {
+    pub fn supported(physical_device: &VkPhysicalDevice) -> VkFeatures {
+        let api_version = physical_device.get_properties().api_version;
+        if !Self::is_queried(api_version) {
+            return VkFeatures {
+                v1_0: physical_device.get_features(),
+                ..Default::default()
+            };
+        }
+
+        let mut v1_1 = vk::PhysicalDeviceVulkan11Features::default();
+        let mut v1_2 = vk::PhysicalDeviceVulkan12Features::default();
+        let mut synchronization2 = vk::PhysicalDeviceSynchronization2FeaturesKHR::default();
+        let mut features = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut v1_1)
+            .push_next(&mut v1_2);
+        let has_synchronization2 = physical_device
+            .get_extension_names()
+            .iter()
+            .any(|name| name.as_c_str() == Synchronization2::name());
+        if has_synchronization2 {
+            features = features.push_next(&mut synchronization2);
+        }
+        unsafe {
+            physical_device
+                .instance
+                .handle
+                .get_physical_device_features2(physical_device.handle, &mut features);
+        }
+        let v1_0 = features.features;
+
+        // The chain points into this function
+        v1_1.p_next = std::ptr::null_mut();
+        v1_2.p_next = std::ptr::null_mut();
+        synchronization2.p_next = std::ptr::null_mut();
+        VkFeatures {
+            v1_0,
+            v1_1,
+            v1_2,
+            synchronization2,
+        }
+    }
+
+    // Whether the features besides the 1.0 ones are known for a device of the version
+    pub fn is_queried(api_version: u32) -> bool {
+        api_version >= vk::API_VERSION_1_2
+    }
+
+    // Named features grouped by the API version which made them core or by extension
+    pub fn list(&self) -> Vec<(&'static str, Vec<(&'static str, bool)>)> {
+        let mut features = *self;
+        features
+            .fields()
+            .into_iter()
+            .map(|(version, fields)| {
+                let fields = fields
+                    .into_iter()
+                    .map(|(name, value)| (name, *value == vk::TRUE))
+                    .collect();
+                (version, fields)
+            })
+            .collect()
+    }
+
+    // Features enabled here but not in `supported`, named as `<version>.<feature>`
+    pub fn missing_from(&self, supported: &VkFeatures) -> Vec<String> {
+        let supported = supported.list();
+        let mut missing = Vec::new();
+        for ((version, fields), (_, supported_fields)) in self.list().into_iter().zip(supported) {
+            for ((name, enabled), (_, supported)) in fields.into_iter().zip(supported_fields) {
+                if enabled && !supported {
+                    missing.push(format!("{}.{}", version, name));
+                }
+            }
+        }
+        missing
+    }
+
+    pub fn intersection(&self, other: &VkFeatures) -> VkFeatures {
+        self.combine(other, |left, right| left && right)
+    }
+
+    pub fn union(&self, other: &VkFeatures) -> VkFeatures {
+        self.combine(other, |left, right| left || right)
+    }
+
+    pub fn count(&self) -> usize {
+        self.list()
+            .iter()
+            .flat_map(|(_, fields)| fields)
+            .filter(|(_, enabled)| *enabled)
+            .count()
+    }
+
+    // Whether features besides the 1.0 ones are enabled, those are chained to the device create
+    // info which requires a 1.2 device
+    pub fn needs_chain(&self) -> bool {
+        self.list()
+            .iter()
+            .skip(1)
+            .flat_map(|(_, fields)| fields)
+            .any(|(_, enabled)| *enabled)
+    }
+
+    fn combine(&self, other: &VkFeatures, op: impl Fn(bool, bool) -> bool) -> VkFeatures {
+        let mut result = VkFeatures::default();
+        let values = self.list().into_iter().zip(other.list());
+        for ((_, fields), ((_, left), (_, right))) in result.fields().into_iter().zip(values) {
+            let values = left.into_iter().zip(right);
+            for ((_, field), ((_, left), (_, right))) in fields.into_iter().zip(values) {
+                *field = if op(left, right) { vk::TRUE } else { vk::FALSE };
+            }
+        }
+        result
+    }
+
+    fn fields(&mut self) -> Vec<(&'static str, FeatureFields<'_>)> {
+        vec![
+            ("vulkan_1_0", features_1_0(&mut self.v1_0)),
+            ("vulkan_1_1", features_1_1(&mut self.v1_1)),
+            ("vulkan_1_2", features_1_2(&mut self.v1_2)),
+            (
+                "khr_synchronization2",
+                features_synchronization2(&mut self.synchronization2),
+            ),
+        ]
+    }
+}
+
+feature_fields!(
+    features_1_0,
+    vk::PhysicalDeviceFeatures,
+    [
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    ]
+);
+
+feature_fields!(
+    features_1_1,
+    vk::PhysicalDeviceVulkan11Features,
+    [
+        storage_buffer16_bit_access,
+        uniform_and_storage_buffer16_bit_access,
+        storage_push_constant16,
+        storage_input_output16,
+        multiview,
+        multiview_geometry_shader,
+        multiview_tessellation_shader,
+        variable_pointers_storage_buffer,
+        variable_pointers,
+        protected_memory,
+        sampler_ycbcr_conversion,
+        shader_draw_parameters,
+    ]
+);
+
+feature_fields!(
+    features_1_2,
+    vk::PhysicalDeviceVulkan12Features,
+    [
+        sampler_mirror_clamp_to_edge,
+        draw_indirect_count,
+        storage_buffer8_bit_access,
+        uniform_and_storage_buffer8_bit_access,
+        storage_push_constant8,
+        shader_buffer_int64_atomics,
+        shader_shared_int64_atomics,
+        shader_float16,
+        shader_int8,
+        descriptor_indexing,
+        shader_input_attachment_array_dynamic_indexing,
+        shader_uniform_texel_buffer_array_dynamic_indexing,
+        shader_storage_texel_buffer_array_dynamic_indexing,
+        shader_uniform_buffer_array_non_uniform_indexing,
+        shader_sampled_image_array_non_uniform_indexing,
+        shader_storage_buffer_array_non_uniform_indexing,
+        shader_storage_image_array_non_uniform_indexing,
+        shader_input_attachment_array_non_uniform_indexing,
+        shader_uniform_texel_buffer_array_non_uniform_indexing,
+        shader_storage_texel_buffer_array_non_uniform_indexing,
+        descriptor_binding_uniform_buffer_update_after_bind,
+        descriptor_binding_sampled_image_update_after_bind,
+        descriptor_binding_storage_image_update_after_bind,
+        descriptor_binding_storage_buffer_update_after_bind,
+        descriptor_binding_uniform_texel_buffer_update_after_bind,
+        descriptor_binding_storage_texel_buffer_update_after_bind,
+        descriptor_binding_update_unused_while_pending,
+        descriptor_binding_partially_bound,
+        descriptor_binding_variable_descriptor_count,
+        runtime_descriptor_array,
+        sampler_filter_minmax,
+        scalar_block_layout,
+        imageless_framebuffer,
+        uniform_buffer_standard_layout,
+        shader_subgroup_extended_types,
+        separate_depth_stencil_layouts,
+        host_query_reset,
+        timeline_semaphore,
+        buffer_device_address,
+        buffer_device_address_capture_replay,
+        buffer_device_address_multi_device,
+        vulkan_memory_model,
+        vulkan_memory_model_device_scope,
+        vulkan_memory_model_availability_visibility_chains,
+        shader_output_viewport_index,
+        shader_output_layer,
+        subgroup_broadcast_dynamic_id,
+    ]
+);
+
+feature_fields!(
+    features_synchronization2,
+    vk::PhysicalDeviceSynchronization2FeaturesKHR,
+    [synchronization2]
+);
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{device::VkDevice, render_pass::VkRenderPass};
+
+pub struct VkFramebuffer {
+    device: Arc<VkDevice>,
+    pub handle: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+}
+
+impl VkFramebuffer {
+    // The views are given in the order of the attachments of the render pass
+    pub fn new(
+        device: &Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        attachments: &[vk::ImageView],
+        extent: vk::Extent2D,
+    ) -> VkFramebuffer {
+        let framebuffer_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass.handle)
+            .attachments(attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let handle = unsafe {
+            device
+                .handle
+                .create_framebuffer(&framebuffer_info, None)
+                .expect("Unable to create framebuffer")
+        };
+        device.track(handle, "framebuffer");
+
+        VkFramebuffer {
+            device: Arc::clone(device),
+            handle,
+            extent,
+        }
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
+}
+
+impl Drop for VkFramebuffer {
+    fn drop(&mut self) {
+        log::debug!("Dropping framebuffer");
+        self.device.untrack(self.handle);
+        unsafe {
+            self.device.handle.destroy_framebuffer(self.handle, None);
+        }
+    }
+}
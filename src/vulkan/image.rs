@@ -1,13 +1,16 @@
-use std::{
-    fs::File,
-    io::{Cursor, Read},
-    mem::size_of,
-    sync::Arc,
-};
+use std::{fs::File, io::Read, mem::size_of, path::Path, sync::Arc};
 
 use ash::vk;
+use image::{DynamicImage, GenericImageView, ImageFormat};
 
-use super::{VkBuffer, VkCommandPool, VkDevice, VkPhysicalDevice};
+use super::{
+    ImageAccess, VkBarrierTracker, VkBuffer, VkCommandBuffer, VkCommandPool, VkDevice,
+    VkPendingUpload, VkPhysicalDevice, VkQueue,
+};
+use crate::texture::{
+    cross_faces, equirectangular_to_cube, face_size, generate_mip_chain, mip_level_count, HdrImage,
+    MipOptions, TextureData,
+};
 
 pub struct VkImage {
     device: Arc<VkDevice>,
@@ -16,6 +19,7 @@ pub struct VkImage {
     pub extent: vk::Extent3D,
     pub mip_levels: u32,
     pub msaa_samples: vk::SampleCountFlags,
+    pub dimensions: ImageDimensions,
 }
 
 pub struct VkTexture {
@@ -25,34 +29,128 @@ pub struct VkTexture {
     pub format: vk::Format,
 }
 
-pub struct VkSampler {
-    device: Arc<VkDevice>,
-    pub handle: vk::Sampler,
+// How the color values of a texture file are encoded. Color maps are usually authored in sRGB,
+// data such as normal maps, roughness or height maps is stored as linear values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureColorSpace {
+    Srgb,
+    Linear,
+}
+
+// Layout of the image, the number of slices of 3D images is given by the depth of the extent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageDimensions {
+    D2,
+    D2Array(u32),
+    Cube,
+    D3,
+}
+
+// Everything needed to create an image, the memory properties are chosen separately
+#[derive(Clone, Copy, Debug)]
+pub struct ImageDesc {
+    pub extent: vk::Extent3D,
+    pub dimensions: ImageDimensions,
+    pub mip_levels: u32,
+    pub msaa_samples: vk::SampleCountFlags,
+    pub format: vk::Format,
+    pub tiling: vk::ImageTiling,
+    pub usage: vk::ImageUsageFlags,
+}
+
+// Where the six faces of a cubemap come from. Faces are given in the order +X, -X, +Y, -Y, +Z,
+// -Z, equirectangular images are projected onto faces of `face_size` (a quarter of the image
+// width by default).
+#[derive(Clone, Copy, Debug)]
+pub enum CubemapSource<'a> {
+    Faces([&'a str; 6]),
+    Cross(&'a str),
+    Equirectangular {
+        path: &'a str,
+        face_size: Option<u32>,
+    },
+}
+
+// How missing mip levels are created. GPU blits fall back to the CPU generator with default
+// options for formats which cannot be blitted with linear filtering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MipGeneration {
+    Gpu,
+    Cpu(MipOptions),
+}
+
+// Decoded pixel data laid out for upload into an image of the given format, files without mip
+// maps have a single level. Every level holds all layers (or faces) one after another.
+pub struct TexturePixels {
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: vk::Format,
+    components: vk::ComponentMapping,
+    dimensions: ImageDimensions,
+    levels: Vec<Vec<u8>>,
+}
+
+impl ImageDimensions {
+    pub fn layer_count(self) -> u32 {
+        match self {
+            ImageDimensions::D2Array(layers) => layers,
+            ImageDimensions::Cube => 6,
+            ImageDimensions::D2 | ImageDimensions::D3 => 1,
+        }
+    }
+
+    fn image_type(self) -> vk::ImageType {
+        match self {
+            ImageDimensions::D3 => vk::ImageType::TYPE_3D,
+            _ => vk::ImageType::TYPE_2D,
+        }
+    }
+
+    fn view_type(self) -> vk::ImageViewType {
+        match self {
+            ImageDimensions::D2 => vk::ImageViewType::TYPE_2D,
+            ImageDimensions::D2Array(_) => vk::ImageViewType::TYPE_2D_ARRAY,
+            ImageDimensions::Cube => vk::ImageViewType::CUBE,
+            ImageDimensions::D3 => vk::ImageViewType::TYPE_3D,
+        }
+    }
+
+    fn create_flags(self) -> vk::ImageCreateFlags {
+        match self {
+            ImageDimensions::Cube => vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            _ => vk::ImageCreateFlags::empty(),
+        }
+    }
 }
 
 impl VkImage {
     pub fn new(
         device: &Arc<VkDevice>,
         properties: vk::MemoryPropertyFlags,
-        extent: vk::Extent3D,
-        mip_levels: u32,
-        msaa_samples: vk::SampleCountFlags,
-        format: vk::Format,
-        tiling: vk::ImageTiling,
-        usage: vk::ImageUsageFlags,
+        desc: &ImageDesc,
     ) -> VkImage {
+        let ImageDesc {
+            extent,
+            dimensions,
+            mip_levels,
+            msaa_samples,
+            format,
+            tiling,
+            usage,
+        } = *desc;
         let image_info = vk::ImageCreateInfo::builder()
-            .image_type(vk::ImageType::TYPE_2D)
+            .image_type(dimensions.image_type())
             .extent(extent)
             .mip_levels(mip_levels)
-            .array_layers(1)
+            .array_layers(dimensions.layer_count())
             .format(format)
             .tiling(tiling)
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .usage(usage)
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
             .samples(msaa_samples)
-            .flags(vk::ImageCreateFlags::empty());
+            .flags(dimensions.create_flags());
 
         let handle = unsafe { device.handle.create_image(&image_info, None).unwrap() };
         let mem_requirements = unsafe { device.handle.get_image_memory_requirements(handle) };
@@ -66,6 +164,12 @@ impl VkImage {
             device.handle.bind_image_memory(handle, mem, 0).unwrap();
             mem
         };
+        let label = format!(
+            "{:?} image of {}x{}x{}",
+            format, extent.width, extent.height, extent.depth
+        );
+        device.track(handle, &label);
+        device.track(memory, &label);
 
         VkImage {
             device: Arc::clone(device),
@@ -74,211 +178,300 @@ impl VkImage {
             extent,
             mip_levels,
             msaa_samples,
+            dimensions,
         }
     }
 
-    pub fn load_texture(
-        device: &Arc<VkDevice>,
+    // Only reads the file and prepares the pixel data, so this can run on any thread. The
+    // device is used to pick formats it can sample.
+    pub fn decode_texture(
+        device: &VkDevice,
         path: &str,
+        color_space: TextureColorSpace,
+        mip_generation: MipGeneration,
+    ) -> TexturePixels {
+        let pixels = if TextureData::is_container_file(path) {
+            let texture = TextureData::load(path, color_space).expect("Unable to load texture");
+            TexturePixels::from_texture_data(&device.physical_device, texture)
+        } else if HdrImage::is_hdr_file(path) {
+            let mut image = HdrImage::load(path).expect("Unable to load texture");
+            image.flip_vertically();
+            TexturePixels::from_hdr_image(&device.physical_device, image)
+        } else {
+            // OBJ texture coordinates start at the bottom of the image
+            let image = load_image_file(path).flipv();
+            TexturePixels::new(&device.physical_device, image, color_space)
+        };
+        pixels.with_mipmaps(device, mip_generation)
+    }
+
+    // Single texel texture, shown in place of textures which are still loading
+    pub fn create_placeholder_texture(
+        device: &Arc<VkDevice>,
+        command_pool: &Arc<VkCommandPool>,
+        transfer_queue: &Arc<VkQueue>,
+        color: [u8; 4],
+    ) -> VkTexture {
+        let pixels = TexturePixels {
+            width: 1,
+            height: 1,
+            depth: 1,
+            format: vk::Format::R8G8B8A8_UNORM,
+            components: vk::ComponentMapping::default(),
+            dimensions: ImageDimensions::D2,
+            levels: vec![color.to_vec()],
+        };
+        Self::create_texture(device, command_pool, transfer_queue, "placeholder", &pixels)
+    }
+
+    // Cube faces are used as they are stored, without the flip applied to OBJ textures
+    pub fn load_cubemap(
+        device: &Arc<VkDevice>,
+        source: CubemapSource,
+        color_space: TextureColorSpace,
+        command_pool: &Arc<VkCommandPool>,
+        transfer_queue: &Arc<VkQueue>,
+    ) -> VkTexture {
+        let face_pixels = |faces: Vec<DynamicImage>| {
+            let faces = faces
+                .into_iter()
+                .map(|face| TexturePixels::new(&device.physical_device, face, color_space))
+                .collect();
+            TexturePixels::from_layers(faces, ImageDimensions::Cube)
+        };
+
+        let (name, pixels) = match source {
+            CubemapSource::Faces(paths) => {
+                let faces: Vec<DynamicImage> =
+                    paths.iter().map(|path| load_image_file(path)).collect();
+                face_size(&faces).expect("Cubemap faces have to be square and of the same size");
+                (paths.join(", "), face_pixels(faces))
+            }
+            CubemapSource::Cross(path) => {
+                let faces = cross_faces(&load_image_file(path))
+                    .expect("Cubemap cross has to be 4x3 or 3x4 faces");
+                (path.to_owned(), face_pixels(faces))
+            }
+            CubemapSource::Equirectangular { path, face_size } => {
+                let (width, height, data) = load_float_image_file(path, color_space);
+                let face_size = face_size.unwrap_or(width / 4).max(1);
+                let pixels = TexturePixels {
+                    width: face_size,
+                    height: face_size,
+                    depth: 1,
+                    format: vk::Format::R16G16B16A16_SFLOAT,
+                    components: vk::ComponentMapping::default(),
+                    dimensions: ImageDimensions::Cube,
+                    levels: vec![equirectangular_to_cube(&data, width, height, face_size)],
+                };
+                (path.to_owned(), pixels)
+            }
+        };
+        let pixels = pixels.with_mipmaps(device, MipGeneration::Gpu);
+
+        Self::create_texture(device, command_pool, transfer_queue, &name, &pixels)
+    }
+
+    fn create_texture(
+        device: &Arc<VkDevice>,
         command_pool: &Arc<VkCommandPool>,
-        transfer_queue: vk::Queue,
+        transfer_queue: &Arc<VkQueue>,
+        name: &str,
+        pixels: &TexturePixels,
     ) -> VkTexture {
-        let mut buf = Vec::new();
-        let mut file = File::open(path).unwrap();
-        file.read_to_end(&mut buf).unwrap();
-        let cursor = Cursor::new(buf);
-
-        let image = image::load(cursor, image::ImageFormat::Jpeg)
-            .unwrap()
-            .flipv();
-        let image_as_rgb = image.to_rgba8();
-        let width = (&image_as_rgb).width();
-        let height = (&image_as_rgb).height();
-        let max_mip_levels = ((width.min(height) as f32).log2().floor() + 1.0) as u32;
+        Self::upload_texture(device, command_pool, transfer_queue, name, pixels).wait()
+    }
+
+    // Records the copy and mipmap generation into a single submission without waiting for it
+    pub fn upload_texture(
+        device: &Arc<VkDevice>,
+        command_pool: &Arc<VkCommandPool>,
+        transfer_queue: &Arc<VkQueue>,
+        name: &str,
+        pixels: &TexturePixels,
+    ) -> VkPendingUpload<VkTexture> {
+        log::info!(
+            "Loaded texture {} ({}x{}x{}, {:?}, {:?}, {} levels)",
+            name,
+            pixels.width,
+            pixels.height,
+            pixels.depth,
+            pixels.dimensions,
+            pixels.format,
+            pixels.levels.len()
+        );
+
+        let width = pixels.width;
+        let height = pixels.height;
+        let format = pixels.format;
+        let dimensions = pixels.dimensions;
         let extent = vk::Extent3D {
             width,
             height,
-            depth: 1,
+            depth: pixels.depth,
+        };
+
+        // Mip levels stored in the file are uploaded as they are, otherwise they are generated
+        // with blits when the format allows it. Blits would also halve the depth of 3D images
+        // so these only get the levels of the file.
+        let generate_mips = pixels.levels.len() == 1
+            && dimensions != ImageDimensions::D3
+            && supports_blit_mipmaps(device, format);
+        let max_mip_levels = if generate_mips {
+            mip_level_count(width, height)
+        } else {
+            pixels.levels.len() as u32
         };
-        let pixels = image_as_rgb.into_raw();
-        let image_size = (pixels.len() * size_of::<u8>()) as vk::DeviceSize;
 
+        let data = pixels.levels.concat();
+        let image_size = (data.len() * size_of::<u8>()) as vk::DeviceSize;
         let staging_buffer = VkBuffer::new(
             device,
             vk::BufferUsageFlags::TRANSFER_SRC,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
             image_size,
         );
-        staging_buffer.map_memory(&pixels);
+        staging_buffer.map_memory(&data);
+        staging_buffer.set_name(&format!("{} staging", name));
 
-        let format = vk::Format::R8G8B8A8_UNORM;
-        let image = Self::new(
-            device,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            extent,
-            max_mip_levels,
-            vk::SampleCountFlags::TYPE_1,
-            format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_SRC
-                | vk::ImageUsageFlags::TRANSFER_DST
-                | vk::ImageUsageFlags::SAMPLED,
-        );
-
-        transition_image_layout(
-            device,
-            command_pool,
-            transfer_queue,
-            &image,
-            max_mip_levels,
-            format,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        );
-
-        copy_buffer_to_image(
-            device,
-            command_pool,
-            transfer_queue,
-            &staging_buffer,
-            &image,
-            extent,
-        );
-
-        generate_mipmaps(
-            device,
-            command_pool,
-            transfer_queue,
-            &image,
-            extent,
-            format,
-            max_mip_levels,
-        );
-
-        let view = image.create_view(max_mip_levels, format, vk::ImageAspectFlags::COLOR);
-
-        VkTexture {
-            device: Arc::clone(device),
-            image,
-            view,
-            format,
+        let mut usage = vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED;
+        if generate_mips {
+            usage |= vk::ImageUsageFlags::TRANSFER_SRC;
         }
-    }
-
-    pub fn create_depth_image(
-        device: &Arc<VkDevice>,
-        command_pool: &Arc<VkCommandPool>,
-        transfer_queue: vk::Queue,
-        format: vk::Format,
-        extent: vk::Extent2D,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> VkTexture {
-        let image = VkImage::new(
+        let image = Self::new(
             device,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            vk::Extent3D {
-                width: extent.width,
-                height: extent.height,
-                depth: 1,
+            &ImageDesc {
+                extent,
+                dimensions,
+                mip_levels: max_mip_levels,
+                msaa_samples: vk::SampleCountFlags::TYPE_1,
+                format,
+                tiling: vk::ImageTiling::OPTIMAL,
+                usage,
             },
-            1,
-            msaa_samples,
-            format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
         );
 
-        transition_image_layout(
-            device,
-            command_pool,
-            transfer_queue,
-            &image,
-            1,
-            format,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-        );
+        let mut offset = 0;
+        let regions: Vec<vk::BufferImageCopy> = pixels
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let region = vk::BufferImageCopy::builder()
+                    .buffer_offset(offset)
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: level as u32,
+                        base_array_layer: 0,
+                        layer_count: dimensions.layer_count(),
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: (width >> level).max(1),
+                        height: (height >> level).max(1),
+                        depth: (pixels.depth >> level).max(1),
+                    })
+                    .build();
+                offset += data.len() as vk::DeviceSize;
+                region
+            })
+            .collect();
 
-        let view = image.create_view(1, format, vk::ImageAspectFlags::DEPTH);
+        let mut barriers = VkBarrierTracker::new();
+        barriers.track_image(&image, vk::ImageAspectFlags::COLOR, ImageAccess::Undefined);
+        let (command_buffer, value) =
+            device.submit_one_time_commands(command_pool, transfer_queue, |device, buffer| {
+                barriers.use_image(&image, ImageAccess::TransferWrite);
+                barriers.flush(device, buffer);
+                copy_buffer_to_image(device, buffer, &staging_buffer, &image, &regions);
 
-        VkTexture {
-            device: Arc::clone(device),
-            image,
-            view,
-            format,
-        }
-    }
+                if generate_mips {
+                    generate_mipmaps(device, buffer, &mut barriers, &image, extent, format);
+                } else {
+                    barriers.use_image(&image, ImageAccess::FragmentShaderRead);
+                    barriers.flush(device, buffer);
+                }
+            });
 
-    pub fn create_color_image(
-        device: &Arc<VkDevice>,
-        format: vk::Format,
-        extent: vk::Extent2D,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> VkTexture {
-        let image = VkImage::new(
-            &device,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            vk::Extent3D {
-                width: extent.width,
-                height: extent.height,
-                depth: 1,
-            },
-            1,
-            msaa_samples,
+        let view = Self::create_image_view_with_components(
+            device,
+            image.handle,
+            max_mip_levels,
             format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::ImageAspectFlags::COLOR,
+            pixels.components,
+            dimensions,
         );
-        let view = image.create_view(1, format, vk::ImageAspectFlags::COLOR);
 
-        VkTexture {
+        let texture = VkTexture {
             device: Arc::clone(device),
             image,
             view,
             format,
-        }
+        };
+        texture.set_name(name);
+        VkPendingUpload::new(
+            transfer_queue,
+            value,
+            texture,
+            command_buffer,
+            vec![staging_buffer],
+        )
     }
 
-    pub fn create_view(
-        &self,
+    pub fn create_image_view(
+        device: &VkDevice,
+        image: vk::Image,
         mip_levels: u32,
         format: vk::Format,
         aspect_mask: vk::ImageAspectFlags,
     ) -> vk::ImageView {
-        Self::create_image_view(&self.device, self.handle, mip_levels, format, aspect_mask)
+        Self::create_image_view_with_components(
+            device,
+            image,
+            mip_levels,
+            format,
+            aspect_mask,
+            vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            },
+            ImageDimensions::D2,
+        )
     }
 
-    pub fn create_image_view(
+    pub fn create_image_view_with_components(
         device: &VkDevice,
         image: vk::Image,
         mip_levels: u32,
         format: vk::Format,
         aspect_mask: vk::ImageAspectFlags,
+        components: vk::ComponentMapping,
+        dimensions: ImageDimensions,
     ) -> vk::ImageView {
         let create_info = vk::ImageViewCreateInfo::builder()
             .image(image)
-            .view_type(vk::ImageViewType::TYPE_2D)
+            .view_type(dimensions.view_type())
             .format(format)
-            .components(vk::ComponentMapping {
-                r: vk::ComponentSwizzle::IDENTITY,
-                g: vk::ComponentSwizzle::IDENTITY,
-                b: vk::ComponentSwizzle::IDENTITY,
-                a: vk::ComponentSwizzle::IDENTITY,
-            })
+            .components(components)
             .subresource_range(vk::ImageSubresourceRange {
                 aspect_mask,
                 base_mip_level: 0,
                 level_count: mip_levels,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: dimensions.layer_count(),
             });
 
-        unsafe {
+        let view = unsafe {
             device
                 .handle
                 .create_image_view(&create_info, None)
                 .expect("Unable to create image view")
-        }
+        };
+        device.track(view, &format!("{:?} image view", format));
+        view
     }
 
     pub fn find_depth_format(physical_device: &VkPhysicalDevice) -> vk::Format {
@@ -314,11 +507,37 @@ impl VkImage {
     pub fn has_stencil_component(format: vk::Format) -> bool {
         format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT
     }
+
+    pub fn is_depth_format(format: vk::Format) -> bool {
+        matches!(
+            format,
+            vk::Format::D16_UNORM
+                | vk::Format::X8_D24_UNORM_PACK32
+                | vk::Format::D32_SFLOAT
+                | vk::Format::D16_UNORM_S8_UINT
+                | vk::Format::D24_UNORM_S8_UINT
+                | vk::Format::D32_SFLOAT_S8_UINT
+        )
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+        self.device.set_object_name(self.memory, name);
+    }
+}
+
+impl VkTexture {
+    pub fn set_name(&self, name: &str) {
+        self.image.set_name(name);
+        self.device.set_object_name(self.view, name);
+    }
 }
 
 impl Drop for VkImage {
     fn drop(&mut self) {
         log::debug!("Dropping image");
+        self.device.untrack(self.handle);
+        self.device.untrack(self.memory);
         unsafe {
             self.device.handle.destroy_image(self.handle, None);
             self.device.handle.free_memory(self.memory, None);
@@ -329,182 +548,372 @@ impl Drop for VkImage {
 impl Drop for VkTexture {
     fn drop(&mut self) {
         log::debug!("Dropping texture");
+        self.device.untrack(self.view);
         unsafe {
             self.device.handle.destroy_image_view(self.view, None);
         }
     }
 }
 
-impl VkSampler {
-    pub fn new(device: &Arc<VkDevice>, mip_levels: u32, max_anisotropy: f32) -> VkSampler {
-        let sampler_info = vk::SamplerCreateInfo::builder()
-            .mag_filter(vk::Filter::LINEAR)
-            .min_filter(vk::Filter::LINEAR)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
-            .max_anisotropy(max_anisotropy)
-            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
-            .unnormalized_coordinates(false)
-            .compare_enable(false)
-            .compare_op(vk::CompareOp::ALWAYS)
-            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-            .mip_lod_bias(0.0)
-            .min_lod(0.0)
-            .max_lod(mip_levels as _);
-
-        let handle = unsafe { device.handle.create_sampler(&sampler_info, None).unwrap() };
-
-        VkSampler {
-            device: Arc::clone(device),
-            handle,
+impl TexturePixels {
+    // Keeps the channel count and bit depth of the source image when the device can sample
+    // the matching format, grayscale images are swizzled so that they read as gray and not red
+    fn new(
+        physical_device: &VkPhysicalDevice,
+        image: DynamicImage,
+        color_space: TextureColorSpace,
+    ) -> TexturePixels {
+        let srgb = color_space == TextureColorSpace::Srgb;
+        let width = image.width();
+        let height = image.height();
+        let gray = vk::ComponentMapping {
+            r: vk::ComponentSwizzle::R,
+            g: vk::ComponentSwizzle::R,
+            b: vk::ComponentSwizzle::R,
+            a: vk::ComponentSwizzle::ONE,
+        };
+        let gray_alpha = vk::ComponentMapping {
+            a: vk::ComponentSwizzle::G,
+            ..gray
+        };
+
+        // 8-bit sRGB data is decoded by the sampler, there are no 16-bit sRGB formats so wide
+        // images are converted to linear values up front
+        let (format, components, data) = match image {
+            DynamicImage::ImageLuma8(image) => {
+                let format = if srgb {
+                    vk::Format::R8_SRGB
+                } else {
+                    vk::Format::R8_UNORM
+                };
+                (format, gray, image.into_raw())
+            }
+            // R8G8_SRGB would decode the alpha in G as well, sRGB images with alpha are
+            // expanded to RGBA below
+            DynamicImage::ImageLumaA8(image) if !srgb => {
+                (vk::Format::R8G8_UNORM, gray_alpha, image.into_raw())
+            }
+            DynamicImage::ImageLuma16(image) => {
+                let mut data = image.into_raw();
+                if srgb {
+                    srgb_to_linear_u16(&mut data, 1, false);
+                }
+                (vk::Format::R16_UNORM, gray, u16_to_bytes(&data))
+            }
+            DynamicImage::ImageLumaA16(image) => {
+                let mut data = image.into_raw();
+                if srgb {
+                    srgb_to_linear_u16(&mut data, 2, true);
+                }
+                (vk::Format::R16G16_UNORM, gray_alpha, u16_to_bytes(&data))
+            }
+            DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+                let mut data = image.into_rgba16().into_raw();
+                if srgb {
+                    srgb_to_linear_u16(&mut data, 4, true);
+                }
+                (
+                    vk::Format::R16G16B16A16_UNORM,
+                    vk::ComponentMapping::default(),
+                    u16_to_bytes(&data),
+                )
+            }
+            // 3 channel 8-bit formats are rarely sampleable, expand them (and sRGB gray images
+            // with alpha) to RGBA
+            image => {
+                let format = if srgb {
+                    vk::Format::R8G8B8A8_SRGB
+                } else {
+                    vk::Format::R8G8B8A8_UNORM
+                };
+                (
+                    format,
+                    vk::ComponentMapping::default(),
+                    image.into_rgba8().into_raw(),
+                )
+            }
+        };
+
+        let pixels = TexturePixels {
+            width,
+            height,
+            depth: 1,
+            format,
+            components,
+            dimensions: ImageDimensions::D2,
+            levels: vec![data],
+        };
+
+        if is_sampleable(physical_device, format, true) {
+            pixels
+        } else {
+            log::warn!("Format {:?} is not supported, converting to RGBA", format);
+            pixels.into_rgba()
         }
     }
-}
 
-impl Drop for VkSampler {
-    fn drop(&mut self) {
-        log::debug!("Dropping sampler");
-        unsafe {
-            self.device.handle.destroy_sampler(self.handle, None);
+    // Linear filtering of 32-bit floats is optional, half floats are used without it
+    fn from_hdr_image(physical_device: &VkPhysicalDevice, mut image: HdrImage) -> TexturePixels {
+        if image.format == vk::Format::R32G32B32A32_SFLOAT
+            && !is_sampleable(physical_device, image.format, false)
+        {
+            log::warn!(
+                "Format {:?} is not supported, converting to half floats",
+                image.format
+            );
+            image.format = vk::Format::R16G16B16A16_SFLOAT;
+        }
+
+        TexturePixels {
+            width: image.width,
+            height: image.height,
+            depth: 1,
+            format: image.format,
+            components: vk::ComponentMapping::default(),
+            dimensions: ImageDimensions::D2,
+            levels: vec![image.to_bytes()],
         }
     }
-}
 
-fn transition_image_layout(
-    device: &VkDevice,
-    command_pool: &Arc<VkCommandPool>,
-    transition_queue: vk::Queue,
-    image: &VkImage,
-    mip_levels: u32,
-    format: vk::Format,
-    old_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout,
-) {
-    device.execute_one_time_commands(command_pool, transition_queue, |device, buffer| {
-        let (src_access_mask, src_stage) = match old_layout {
-            vk::ImageLayout::UNDEFINED => (
-                vk::AccessFlags::empty(),
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-            ),
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::PipelineStageFlags::TRANSFER,
-            ),
-            _ => panic!("Unsupported layout transition from ({:?}.", old_layout),
+    // Block compressed formats which the device cannot sample are decoded on the CPU
+    fn from_texture_data(
+        physical_device: &VkPhysicalDevice,
+        texture: TextureData,
+    ) -> TexturePixels {
+        let texture =
+            if texture.is_compressed() && !is_sampleable(physical_device, texture.format, false) {
+                log::warn!(
+                    "Format {:?} is not supported, decompressing",
+                    texture.format
+                );
+                texture.decompress().expect("Unable to decompress texture")
+            } else {
+                texture
+            };
+
+        let pixels = TexturePixels {
+            width: texture.width,
+            height: texture.height,
+            depth: texture.depth,
+            format: texture.format,
+            components: vk::ComponentMapping::default(),
+            dimensions: texture.dimensions,
+            levels: texture.levels,
         };
 
-        let (dst_access_mask, dst_stage) = match new_layout {
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::PipelineStageFlags::TRANSFER,
-            ),
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => (
-                vk::AccessFlags::SHADER_READ,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-            ),
-            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => (
-                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            ),
-            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
-                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            ),
-            _ => panic!("Unsupported layout transition to {:?}).", new_layout),
+        let generate_mips = pixels.levels.len() == 1;
+        if is_sampleable(physical_device, pixels.format, generate_mips) {
+            pixels
+        } else {
+            log::warn!(
+                "Format {:?} is not supported, converting to RGBA",
+                pixels.format
+            );
+            pixels.into_rgba()
+        }
+    }
+
+    fn from_layers(layers: Vec<TexturePixels>, dimensions: ImageDimensions) -> TexturePixels {
+        let first = &layers[0];
+        if layers.iter().any(|layer| {
+            (layer.width, layer.height, layer.format) != (first.width, first.height, first.format)
+        }) {
+            panic!("All layers of a texture need the same size and format");
+        }
+
+        TexturePixels {
+            width: first.width,
+            height: first.height,
+            depth: 1,
+            format: first.format,
+            components: first.components,
+            dimensions,
+            levels: vec![layers
+                .iter()
+                .flat_map(|layer| layer.levels[0].iter().copied())
+                .collect()],
+        }
+    }
+
+    fn with_mipmaps(self, device: &VkDevice, mip_generation: MipGeneration) -> TexturePixels {
+        if self.levels.len() > 1 || self.dimensions == ImageDimensions::D3 {
+            return self;
+        }
+        let options = match mip_generation {
+            MipGeneration::Gpu if supports_blit_mipmaps(device, self.format) => return self,
+            MipGeneration::Gpu => MipOptions::default(),
+            MipGeneration::Cpu(options) => options,
         };
 
-        let aspect_mask = if new_layout == vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL {
-            let mut mask = vk::ImageAspectFlags::DEPTH;
-            if VkImage::has_stencil_component(format) {
-                mask |= vk::ImageAspectFlags::STENCIL;
+        // Each layer gets its own chain, the levels are then put together layer by layer
+        let layer_size = self.levels[0].len() / self.dimensions.layer_count() as usize;
+        let chains: Option<Vec<_>> = self.levels[0]
+            .chunks_exact(layer_size)
+            .map(|layer| generate_mip_chain(layer, self.width, self.height, self.format, options))
+            .collect();
+        match chains {
+            Some(chains) => {
+                let levels = (0..chains[0].len())
+                    .map(|level| chains.iter().map(|chain| chain[level].as_slice()).collect())
+                    .map(|level: Vec<&[u8]>| level.concat())
+                    .collect();
+                TexturePixels { levels, ..self }
             }
-            mask
-        } else {
-            vk::ImageAspectFlags::COLOR
+            None => {
+                log::warn!("Unable to generate mipmaps for format {:?}", self.format);
+                self
+            }
+        }
+    }
+
+    // Fallback for devices without support for the native format of the image
+    fn into_rgba(self) -> TexturePixels {
+        let (channels, wide) = match self.format {
+            vk::Format::R8_UNORM | vk::Format::R8_SRGB => (1, false),
+            vk::Format::R8G8_UNORM | vk::Format::R8G8_SRGB => (2, false),
+            vk::Format::R16_UNORM => (1, true),
+            vk::Format::R16G16_UNORM => (2, true),
+            vk::Format::R16G16B16A16_UNORM => (4, true),
+            _ => return self,
         };
 
-        let barrier = vk::ImageMemoryBarrier::builder()
-            .old_layout(old_layout)
-            .new_layout(new_layout)
-            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .image(image.handle)
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask,
-                base_mip_level: 0,
-                level_count: mip_levels,
-                base_array_layer: 0,
-                layer_count: 1,
+        let component_size = if wide { 2 } else { 1 };
+        let pixel_size = channels * component_size;
+        let levels = self
+            .levels
+            .iter()
+            .map(|level| {
+                let mut data = Vec::with_capacity(level.len() / pixel_size * 4);
+                for pixel in level.chunks_exact(pixel_size) {
+                    // Wide formats keep the most significant byte (little endian)
+                    let channel = |index: usize| pixel[index * component_size + component_size - 1];
+                    let (value, alpha) = match channels {
+                        1 => (channel(0), 255),
+                        2 => (channel(0), channel(1)),
+                        _ => {
+                            data.extend_from_slice(&[
+                                channel(0),
+                                channel(1),
+                                channel(2),
+                                channel(3),
+                            ]);
+                            continue;
+                        }
+                    };
+                    data.extend_from_slice(&[value, value, value, alpha]);
+                }
+                data
             })
-            .src_access_mask(src_access_mask)
-            .dst_access_mask(dst_access_mask)
-            .build();
-        let barriers = [barrier];
+            .collect();
 
-        unsafe {
-            device.handle.cmd_pipeline_barrier(
-                buffer.handle,
-                src_stage,
-                dst_stage,
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &barriers,
-            )
+        let format = match self.format {
+            vk::Format::R8_SRGB | vk::Format::R8G8_SRGB => vk::Format::R8G8B8A8_SRGB,
+            _ => vk::Format::R8G8B8A8_UNORM,
         };
-    });
+
+        TexturePixels {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            format,
+            components: vk::ComponentMapping::default(),
+            dimensions: self.dimensions,
+            levels,
+        }
+    }
+}
+
+// The format is detected from the file contents, with the extension as a fallback for
+// formats without a signature (TGA)
+fn load_image_file(path: &str) -> DynamicImage {
+    let mut buf = Vec::new();
+    let mut file = File::open(path).expect("Unable to open texture file");
+    file.read_to_end(&mut buf)
+        .expect("Unable to read texture file");
+
+    let format = image::guess_format(&buf)
+        .or_else(|_| ImageFormat::from_path(Path::new(path)))
+        .expect("Unknown texture file format");
+
+    image::load_from_memory_with_format(&buf, format).expect("Unable to decode texture")
+}
+
+// Linear RGBA values, HDR and OpenEXR files are read at full precision and other files are
+// converted from their color space
+fn load_float_image_file(path: &str, color_space: TextureColorSpace) -> (u32, u32, Vec<f32>) {
+    if HdrImage::is_hdr_file(path) {
+        let image = HdrImage::load(path).expect("Unable to load texture");
+        return (image.width, image.height, image.pixels);
+    }
+
+    let image = load_image_file(path).into_rgba16();
+    let (width, height) = image.dimensions();
+    let mut data = image.into_raw();
+    if color_space == TextureColorSpace::Srgb {
+        srgb_to_linear_u16(&mut data, 4, true);
+    }
+    let data = data
+        .iter()
+        .map(|&value| value as f32 / u16::MAX as f32)
+        .collect();
+    (width, height, data)
+}
+
+fn supports_blit_mipmaps(device: &VkDevice, format: vk::Format) -> bool {
+    is_sampleable(&device.physical_device, format, true)
+}
+
+// Mipmaps are generated with linear blits so linear filtering has to be supported as well.
+// Images without stored levels also need blits, so that picking their format never leaves
+// them without a way to generate levels on the GPU.
+fn is_sampleable(
+    physical_device: &VkPhysicalDevice,
+    format: vk::Format,
+    generate_mips: bool,
+) -> bool {
+    let mut required = vk::FormatFeatureFlags::SAMPLED_IMAGE
+        | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+        | vk::FormatFeatureFlags::TRANSFER_DST;
+    if generate_mips {
+        required |= vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST;
+    }
+    physical_device
+        .get_format_properties(format)
+        .optimal_tiling_features
+        .contains(required)
+}
+
+fn u16_to_bytes(data: &[u16]) -> Vec<u8> {
+    data.iter().flat_map(|value| value.to_le_bytes()).collect()
 }
 
 fn copy_buffer_to_image(
     device: &VkDevice,
-    command_pool: &Arc<VkCommandPool>,
-    transition_queue: vk::Queue,
+    command_buffer: &VkCommandBuffer,
     buffer: &VkBuffer,
     image: &VkImage,
-    extent: vk::Extent3D,
+    regions: &[vk::BufferImageCopy],
 ) {
-    device.execute_one_time_commands(command_pool, transition_queue, |device, command_buffer| {
-        let region = vk::BufferImageCopy::builder()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(vk::ImageSubresourceLayers {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                base_array_layer: 0,
-                layer_count: 1,
-            })
-            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-            .image_extent(vk::Extent3D {
-                width: extent.width,
-                height: extent.height,
-                depth: 1,
-            })
-            .build();
-        let regions = [region];
-        unsafe {
-            device.handle.cmd_copy_buffer_to_image(
-                command_buffer.handle,
-                buffer.handle,
-                image.handle,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &regions,
-            )
-        }
-    })
+    unsafe {
+        device.handle.cmd_copy_buffer_to_image(
+            command_buffer.handle,
+            buffer.handle,
+            image.handle,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            regions,
+        )
+    }
 }
 
+// Expects every level in the transfer destination layout, leaves them ready for sampling. The
+// barrier which releases a level to the shaders is batched with the one for the next blit.
 fn generate_mipmaps(
     device: &VkDevice,
-    command_pool: &Arc<VkCommandPool>,
-    transfer_queue: vk::Queue,
+    buffer: &VkCommandBuffer,
+    barriers: &mut VkBarrierTracker,
     image: &VkImage,
     extent: vk::Extent3D,
     format: vk::Format,
-    mip_levels: u32,
 ) {
     let format_properties = device.get_format_properties(format);
     if !format_properties
@@ -514,136 +923,106 @@ fn generate_mipmaps(
         panic!("Linear blitting is not supported for format {:?}.", format)
     }
 
-    device.execute_one_time_commands(command_pool, transfer_queue, |device, buffer| {
-        let mut barrier = vk::ImageMemoryBarrier::builder()
-            .image(image.handle)
-            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .subresource_range(vk::ImageSubresourceRange {
+    let mip_levels = image.mip_levels;
+    let layers = 0..image.dimensions.layer_count();
+    let mut mip_width = extent.width as i32;
+    let mut mip_height = extent.height as i32;
+    for level in 1..mip_levels {
+        let next_mip_width = if mip_width > 1 {
+            mip_width / 2
+        } else {
+            mip_width
+        };
+        let next_mip_height = if mip_height > 1 {
+            mip_height / 2
+        } else {
+            mip_height
+        };
+
+        barriers.use_subresources(
+            image,
+            level - 1..level,
+            layers.clone(),
+            ImageAccess::TransferRead,
+        );
+        barriers.flush(device, buffer);
+
+        let blit = vk::ImageBlit::builder()
+            .src_offsets([
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: mip_width,
+                    y: mip_height,
+                    z: 1,
+                },
+            ])
+            .src_subresource(vk::ImageSubresourceLayers {
                 aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level - 1,
                 base_array_layer: 0,
-                layer_count: 1,
-                level_count: 1,
-                ..Default::default()
+                layer_count: image.dimensions.layer_count(),
+            })
+            .dst_offsets([
+                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D {
+                    x: next_mip_width,
+                    y: next_mip_height,
+                    z: 1,
+                },
+            ])
+            .dst_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level,
+                base_array_layer: 0,
+                layer_count: image.dimensions.layer_count(),
             })
             .build();
+        let blits = [blit];
 
-        let mut mip_width = extent.width as i32;
-        let mut mip_height = extent.height as i32;
-        for level in 1..mip_levels {
-            let next_mip_width = if mip_width > 1 {
-                mip_width / 2
-            } else {
-                mip_width
-            };
-            let next_mip_height = if mip_height > 1 {
-                mip_height / 2
-            } else {
-                mip_height
-            };
+        unsafe {
+            device.handle.cmd_blit_image(
+                buffer.handle,
+                image.handle,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image.handle,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &blits,
+                vk::Filter::LINEAR,
+            )
+        };
 
-            barrier.subresource_range.base_mip_level = level - 1;
-            barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-            barrier.new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-            barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-            barrier.dst_access_mask = vk::AccessFlags::TRANSFER_READ;
-            let barriers = [barrier];
-
-            unsafe {
-                device.handle.cmd_pipeline_barrier(
-                    buffer.handle,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::DependencyFlags::empty(),
-                    &[],
-                    &[],
-                    &barriers,
-                )
-            };
+        barriers.use_subresources(
+            image,
+            level - 1..level,
+            layers.clone(),
+            ImageAccess::FragmentShaderRead,
+        );
 
-            let blit = vk::ImageBlit::builder()
-                .src_offsets([
-                    vk::Offset3D { x: 0, y: 0, z: 0 },
-                    vk::Offset3D {
-                        x: mip_width,
-                        y: mip_height,
-                        z: 1,
-                    },
-                ])
-                .src_subresource(vk::ImageSubresourceLayers {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    mip_level: level - 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                })
-                .dst_offsets([
-                    vk::Offset3D { x: 0, y: 0, z: 0 },
-                    vk::Offset3D {
-                        x: next_mip_width,
-                        y: next_mip_height,
-                        z: 1,
-                    },
-                ])
-                .dst_subresource(vk::ImageSubresourceLayers {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    mip_level: level,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                })
-                .build();
-            let blits = [blit];
-
-            unsafe {
-                device.handle.cmd_blit_image(
-                    buffer.handle,
-                    image.handle,
-                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                    image.handle,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    &blits,
-                    vk::Filter::LINEAR,
-                )
-            };
+        mip_width = next_mip_width;
+        mip_height = next_mip_height;
+    }
 
-            barrier.old_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-            barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
-            barrier.src_access_mask = vk::AccessFlags::TRANSFER_READ;
-            barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
-            let barriers = [barrier];
-
-            unsafe {
-                device.handle.cmd_pipeline_barrier(
-                    buffer.handle,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::PipelineStageFlags::FRAGMENT_SHADER,
-                    vk::DependencyFlags::empty(),
-                    &[],
-                    &[],
-                    &barriers,
-                )
-            };
+    barriers.use_subresources(
+        image,
+        mip_levels - 1..mip_levels,
+        layers,
+        ImageAccess::FragmentShaderRead,
+    );
+    barriers.flush(device, buffer);
+}
 
-            mip_width = next_mip_width;
-            mip_height = next_mip_height;
+// Alpha is always stored as a linear value and is expected to be the last channel
+fn srgb_to_linear_u16(data: &mut [u16], channels: usize, has_alpha: bool) {
+    let color_channels = if has_alpha { channels - 1 } else { channels };
+    for pixel in data.chunks_exact_mut(channels) {
+        for value in pixel[..color_channels].iter_mut() {
+            let encoded = *value as f32 / u16::MAX as f32;
+            let linear = if encoded <= 0.04045 {
+                encoded / 12.92
+            } else {
+                ((encoded + 0.055) / 1.055).powf(2.4)
+            };
+            *value = (linear * u16::MAX as f32).round() as u16;
         }
-
-        barrier.subresource_range.base_mip_level = mip_levels - 1;
-        barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-        barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
-        barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-        barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
-        let barriers = [barrier];
-
-        unsafe {
-            device.handle.cmd_pipeline_barrier(
-                buffer.handle,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
-                vk::DependencyFlags::empty(),
-                &[],
-                &[],
-                &barriers,
-            )
-        };
-    });
+    }
 }
@@ -4,14 +4,20 @@ use ash::{extensions::ext::DebugUtils, vk};
 
 use std::ffi::{CStr, CString};
 
-use super::{debug::*, utils};
+use super::{
+    debug::*,
+    settings::{VkSettings, VALIDATION_LAYER},
+    utils,
+};
 
 pub struct VkInstance {
     pub handle: ash::Instance,
+    // False when validation was requested but the layer is not installed
+    pub validation: bool,
 }
 
 impl VkInstance {
-    pub fn new(window: &Window, entry: &ash::Entry, validation: bool) -> VkInstance {
+    pub fn new(window: &Window, entry: &ash::Entry, settings: &VkSettings) -> VkInstance {
         let app_name = CString::new("Vulkan Application").unwrap();
         let engine_name = CString::new("No Engine").unwrap();
         let app_info = vk::ApplicationInfo::builder()
@@ -21,26 +27,53 @@ impl VkInstance {
             .engine_version(vk::make_api_version(0, 0, 0, 1))
             .api_version(vk::make_api_version(0, 1, 2, 0));
 
-        let extensions = enumerate_extensions(window, validation);
+        let layers = find_layers(entry, &settings.layers());
+        let layer_names = utils::as_raw_handles(&layers);
+        let validation = settings.validation
+            && layers
+                .iter()
+                .any(|layer| layer.to_bytes() == VALIDATION_LAYER.as_bytes());
+        if settings.validation && !validation {
+            log::warn!("Validation is disabled");
+        }
+        let validation_features = if validation {
+            settings.validation_features()
+        } else {
+            Vec::new()
+        };
+        log::info!(
+            "Enabling layers {:?} with validation features {:?}",
+            layers,
+            validation_features
+        );
+
+        let mut extensions = enumerate_extensions(window, validation);
+        // Provided by the validation layer
+        if !validation_features.is_empty() {
+            extensions.push(vk::ExtValidationFeaturesFn::name());
+        }
         let extension_names = utils::as_raw_handles(&extensions);
 
+        let mut debug_utils_create_info = populate_debug_messenger_create_info();
+        let mut validation_features_info =
+            vk::ValidationFeaturesEXT::builder().enabled_validation_features(&validation_features);
         let mut instance_create_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
+            .enabled_layer_names(&layer_names)
             .enabled_extension_names(&extension_names);
-
         if validation {
-            check_validation_layer_support(&entry);
-            let validation_layers = get_validation_layers();
-            let validation_layer_names = utils::as_raw_handles(&validation_layers);
-            let mut debug_utils_create_info = populate_debug_messenger_create_info();
-            instance_create_info = instance_create_info
-                .enabled_layer_names(&validation_layer_names)
-                .push_next(&mut debug_utils_create_info);
-
-            build_instance(entry, instance_create_info)
-        } else {
-            build_instance(entry, instance_create_info)
+            instance_create_info = instance_create_info.push_next(&mut debug_utils_create_info);
+        }
+        if !validation_features.is_empty() {
+            instance_create_info = instance_create_info.push_next(&mut validation_features_info);
         }
+
+        let handle = unsafe {
+            entry
+                .create_instance(&instance_create_info, None)
+                .expect("Unable t ocreate Vulkan instance")
+        };
+        VkInstance { handle, validation }
     }
 }
 
@@ -53,15 +86,6 @@ impl Drop for VkInstance {
     }
 }
 
-fn build_instance(entry: &ash::Entry, info: vk::InstanceCreateInfoBuilder) -> VkInstance {
-    let handle = unsafe {
-        entry
-            .create_instance(&info, None)
-            .expect("Unable t ocreate Vulkan instance")
-    };
-    VkInstance { handle }
-}
-
 fn enumerate_extensions(window: &Window, validation: bool) -> Vec<&'static CStr> {
     let window_extensions = ash_window::enumerate_required_extensions(window)
         .expect("Unable to enumerate rrequired window extensions");
@@ -1,13 +1,21 @@
-use std::{ffi::CStr, sync::Arc};
+use std::{
+    ffi::{CStr, CString},
+    sync::Arc,
+};
 
-use ash::{extensions::khr::Swapchain, vk};
+use ash::vk;
 
 use super::{
-    instance::VkInstance, queue_family::VkQueueFamily, surface::VkSurface, utils::coerce_string,
+    instance::VkInstance,
+    queue_family::VkQueueFamily,
+    requirements::VkDeviceRequirements,
+    settings::{DevicePreference, DeviceSelector},
+    surface::VkSurface,
+    utils::coerce_string,
     version::VkVersion,
 };
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DeviceType {
     Cpu,
     IntegratedGpu,
@@ -26,31 +34,98 @@ pub struct VkPhysicalDevice {
 }
 
 impl VkPhysicalDevice {
-    pub fn new(instance: &Arc<VkInstance>, surface: &VkSurface) -> VkPhysicalDevice {
-        let physical_devices = enumerate_devices(&instance.handle);
+    // Picks the best suitable device matching `selector`. Panics listing the available devices
+    // when no suitable device has the requested index or name, vendor and type selectors fall
+    // back to the best suitable device with a warning.
+    pub fn new(
+        instance: &Arc<VkInstance>,
+        surface: &VkSurface,
+        selector: &DeviceSelector,
+        preference: DevicePreference,
+        requirements: &VkDeviceRequirements,
+    ) -> VkPhysicalDevice {
+        let physical_devices = Self::enumerate(instance);
         log::info!(
             "{} device(s) found with vulkan support",
             physical_devices.len()
         );
 
-        let extensions = Self::get_required_device_extensions();
-        let mut best_physical_device: Option<VkPhysicalDevice> = None;
-        let mut best_score = -1;
-        for &handle in &physical_devices {
-            let physical_device = create_physical_device(instance, handle);
+        let mut candidates = Vec::new();
+        let mut available = Vec::new();
+        for (index, physical_device) in physical_devices.into_iter().enumerate() {
             describe_device(&physical_device);
 
-            let score = rate_device_suitability(&physical_device, surface, &extensions);
-            if score > best_score {
-                best_physical_device = Some(physical_device);
-                best_score = score;
+            match rate_device_suitability(&physical_device, surface, requirements, preference) {
+                Ok(score) => {
+                    available.push(format!("{}: {}", index, physical_device.name));
+                    candidates.push((index, score, physical_device));
+                }
+                Err(reason) => {
+                    log::info!("Rejecting device {}: {}", physical_device.name, reason);
+                    available.push(format!("{}: {} ({})", index, physical_device.name, reason));
+                }
+            }
+        }
+
+        let any_selected = candidates
+            .iter()
+            .any(|(index, _, device)| device.matches(*index, selector));
+        // A device asked for by index or name has to be used, vendors and types are preferences
+        let explicit = matches!(selector, DeviceSelector::Index(_) | DeviceSelector::Name(_));
+        if !any_selected && explicit {
+            panic!(
+                "No suitable device matches {:?}, available devices:\n{}",
+                selector,
+                available.join("\n")
+            );
+        }
+        if !any_selected {
+            log::warn!(
+                "No suitable device matches {:?}, choosing by {:?}",
+                selector,
+                preference
+            );
+        }
+
+        // The first of equally rated devices wins
+        let mut best: Option<(i32, VkPhysicalDevice)> = None;
+        for (index, score, device) in candidates {
+            if any_selected && !device.matches(index, selector) {
+                continue;
+            }
+            let better = match &best {
+                Some((best_score, _)) => score > *best_score,
+                None => true,
+            };
+            if better {
+                best = Some((score, device));
+            }
+        }
+
+        match best {
+            Some((_, physical_device)) => {
+                log::info!("Choosing device {}", physical_device.name);
+                physical_device
             }
+            None => panic!("Failed to find a suitable GPU!"),
         }
+    }
 
-        if let Some(physical_device) = best_physical_device {
-            return physical_device;
+    pub fn enumerate(instance: &Arc<VkInstance>) -> Vec<VkPhysicalDevice> {
+        enumerate_devices(&instance.handle)
+            .into_iter()
+            .map(|handle| create_physical_device(instance, handle))
+            .collect()
+    }
+
+    fn matches(&self, index: usize, selector: &DeviceSelector) -> bool {
+        match selector {
+            DeviceSelector::Any => true,
+            DeviceSelector::Index(selected) => index == *selected,
+            DeviceSelector::Name(name) => self.name.to_lowercase().contains(name.as_str()),
+            DeviceSelector::Vendor(vendor_id) => self.get_properties().vendor_id == *vendor_id,
+            DeviceSelector::Type(kind) => self.kind == *kind,
         }
-        panic!("Failed to find a suitable GPU!");
     }
 
     pub fn get_mem_properties(&self) -> vk::PhysicalDeviceMemoryProperties {
@@ -85,8 +160,17 @@ impl VkPhysicalDevice {
         }
     }
 
-    pub fn get_required_device_extensions() -> Vec<&'static CStr> {
-        vec![Swapchain::name()]
+    pub fn get_extension_names(&self) -> Vec<CString> {
+        let extension_props = unsafe {
+            self.instance
+                .handle
+                .enumerate_device_extension_properties(self.handle)
+                .expect("Unable to query device extensions")
+        };
+        extension_props
+            .iter()
+            .map(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) }.to_owned())
+            .collect()
     }
 
     pub fn get_max_usable_sample_count(&self) -> vk::SampleCountFlags {
@@ -171,48 +255,49 @@ fn describe_device(device: &VkPhysicalDevice) {
     );
 }
 
+// Unsuitable devices are rejected with the reason
 fn rate_device_suitability(
     device: &VkPhysicalDevice,
     surface: &VkSurface,
-    extensions: &[&CStr],
-) -> i32 {
-    let mut score = 0i32;
-
+    requirements: &VkDeviceRequirements,
+    preference: DevicePreference,
+) -> Result<i32, String> {
     let queue_families = &device.queue_families;
     let has_graphics_family = has_queue_family(queue_families, |family| {
         family.flags.contains(vk::QueueFlags::GRAPHICS)
     });
     if !has_graphics_family {
-        return -1;
+        return Err("no graphics queue family".to_owned());
     }
     let has_surface_support_family = has_queue_family(queue_families, |family| {
         surface.physical_device_queue_support(device, family.index)
     });
     if !has_surface_support_family {
-        return -1;
+        return Err("no queue family can present to the surface".to_owned());
     }
 
     let surface_caps = surface.get_physical_device_surface_capabilities(device);
-    if surface_caps.formats.is_empty() || surface_caps.present_modes.is_empty() {
-        return -1;
-    }
-
-    let features = device.get_features();
-    if features.sampler_anisotropy == vk::FALSE {
-        return -1;
+    if surface_caps.formats.is_empty() {
+        return Err("no surface formats".to_owned());
     }
-
-    if !check_device_extension_support(device, extensions) {
-        return -1;
+    if surface_caps.present_modes.is_empty() {
+        return Err("no present modes".to_owned());
     }
 
-    match device.kind {
-        DeviceType::DiscreteGpu => score += 100,
-        DeviceType::IntegratedGpu => score += 50,
-        _ => (),
+    let missing = requirements.find_missing(device);
+    if !missing.is_empty() {
+        return Err(format!("missing {}", missing.join(", ")));
     }
 
-    score
+    let score = match (device.kind, preference) {
+        (DeviceType::DiscreteGpu, DevicePreference::HighPerformance) => 100,
+        (DeviceType::IntegratedGpu, DevicePreference::HighPerformance) => 50,
+        (DeviceType::IntegratedGpu, DevicePreference::PowerSaving) => 100,
+        (DeviceType::DiscreteGpu, DevicePreference::PowerSaving) => 50,
+        _ => 0,
+    };
+    // Optional capabilities only decide between devices of the same kind
+    Ok(score + requirements.count_optional(device).min(49) as i32)
 }
 
 fn get_device_type(properties: &vk::PhysicalDeviceProperties) -> DeviceType {
@@ -247,25 +332,3 @@ fn has_queue_family(
         .iter()
         .any(|family| family.queue_count > 0 && predicate(family))
 }
-
-fn check_device_extension_support(device: &VkPhysicalDevice, extensions: &[&CStr]) -> bool {
-    let instance = &device.instance.handle;
-    let extension_props = unsafe {
-        instance
-            .enumerate_device_extension_properties(device.handle)
-            .expect("Unable to query device extensions")
-    };
-
-    for required in extensions {
-        let found = extension_props.iter().any(|ext| {
-            let name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
-            required == &name
-        });
-
-        if !found {
-            return false;
-        }
-    }
-
-    true
-}
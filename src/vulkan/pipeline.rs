@@ -8,6 +8,16 @@ use super::{device::VkDevice, render_pass::VkRenderPass, VkShaderModule};
 
 use memoffset::offset_of;
 
+// What a pipeline draws. Full-screen pipelines draw a triangle from a vertex buffer in clip
+// space, background pipelines draw one without vertex buffers and only cover pixels left empty
+// by the depth buffer.
+#[derive(Clone, Copy, PartialEq)]
+enum Geometry {
+    Vertices,
+    FullScreen,
+    Background,
+}
+
 pub struct VkPipeline {
     device: Arc<VkDevice>,
     pub handle: vk::Pipeline,
@@ -23,15 +33,72 @@ impl VkPipeline {
         fragment_shader_module: &VkShaderModule,
         descriptor_set_layouts: &[vk::DescriptorSetLayout],
         msaa_samples: vk::SampleCountFlags,
+    ) -> VkPipeline {
+        Self::create(
+            device,
+            extent,
+            render_pass,
+            [vertex_shader_module, fragment_shader_module],
+            descriptor_set_layouts,
+            msaa_samples,
+            Geometry::Vertices,
+        )
+    }
+
+    // Draws a triangle covering the whole viewport, see `Mesh::fullscreen_triangle`
+    pub fn new_full_screen(
+        device: &Arc<VkDevice>,
+        extent: vk::Extent2D,
+        render_pass: &VkRenderPass,
+        vertex_shader_module: &VkShaderModule,
+        fragment_shader_module: &VkShaderModule,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    ) -> VkPipeline {
+        Self::create(
+            device,
+            extent,
+            render_pass,
+            [vertex_shader_module, fragment_shader_module],
+            descriptor_set_layouts,
+            vk::SampleCountFlags::TYPE_1,
+            Geometry::FullScreen,
+        )
+    }
+
+    // Full screen triangle on the far plane, drawn after the geometry of a pass with depth
+    pub fn new_background(
+        device: &Arc<VkDevice>,
+        extent: vk::Extent2D,
+        render_pass: &VkRenderPass,
+        vertex_shader_module: &VkShaderModule,
+        fragment_shader_module: &VkShaderModule,
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        msaa_samples: vk::SampleCountFlags,
+    ) -> VkPipeline {
+        Self::create(
+            device,
+            extent,
+            render_pass,
+            [vertex_shader_module, fragment_shader_module],
+            descriptor_set_layouts,
+            msaa_samples,
+            Geometry::Background,
+        )
+    }
+
+    fn create(
+        device: &Arc<VkDevice>,
+        extent: vk::Extent2D,
+        render_pass: &VkRenderPass,
+        shader_modules: [&VkShaderModule; 2],
+        descriptor_set_layouts: &[vk::DescriptorSetLayout],
+        msaa_samples: vk::SampleCountFlags,
+        geometry: Geometry,
     ) -> VkPipeline {
         log::info!("Creating pipeline");
 
-        let vertex_shader_stage_info = vertex_shader_module.create_pipeline_shader_stage();
-        let fragment_shader_stage_info = fragment_shader_module.create_pipeline_shader_stage();
-        let shader_stages = [
-            vertex_shader_stage_info.build(),
-            fragment_shader_stage_info.build(),
-        ];
+        let shader_stages =
+            shader_modules.map(|module| module.create_pipeline_shader_stage().build());
 
         // TODO: Where to put this?
         let vertex_input_binding = create_vertex_input_binding_description();
@@ -44,9 +111,13 @@ impl VkPipeline {
             color_vertex_attribute,
             texture_vertex_attribute,
         ];
-        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(&binding_descriptions)
-            .vertex_attribute_descriptions(&attribute_descriptions);
+        let vertex_input_info = if geometry != Geometry::Background {
+            vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(&binding_descriptions)
+                .vertex_attribute_descriptions(&attribute_descriptions)
+        } else {
+            vk::PipelineVertexInputStateCreateInfo::builder()
+        };
 
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
@@ -73,7 +144,11 @@ impl VkPipeline {
             .rasterizer_discard_enable(false)
             .polygon_mode(vk::PolygonMode::FILL)
             .line_width(1.0f32)
-            .cull_mode(vk::CullModeFlags::BACK)
+            .cull_mode(if geometry == Geometry::Vertices {
+                vk::CullModeFlags::BACK
+            } else {
+                vk::CullModeFlags::NONE
+            })
             .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
             .depth_bias_enable(false)
             .depth_bias_constant_factor(0.0)
@@ -81,9 +156,13 @@ impl VkPipeline {
             .depth_bias_slope_factor(0.0);
 
         let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(vk::CompareOp::LESS)
+            .depth_test_enable(geometry != Geometry::FullScreen)
+            .depth_write_enable(geometry == Geometry::Vertices)
+            .depth_compare_op(if geometry == Geometry::Background {
+                vk::CompareOp::LESS_OR_EQUAL
+            } else {
+                vk::CompareOp::LESS
+            })
             .depth_bounds_test_enable(false)
             .min_depth_bounds(0.0)
             .max_depth_bounds(1.0)
@@ -125,6 +204,7 @@ impl VkPipeline {
                 .create_pipeline_layout(&layout_info, None)
                 .expect("Unable to create pipeline layout")
         };
+        device.track(layout, "pipeline layout");
 
         let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stages)
@@ -147,6 +227,7 @@ impl VkPipeline {
                 .create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
                 .expect("Unable t ocreate graphics pipelines")[0]
         };
+        device.track(handle, "graphics pipeline");
 
         VkPipeline {
             device: Arc::clone(device),
@@ -154,11 +235,18 @@ impl VkPipeline {
             handle,
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+        self.device.set_object_name(self.layout, name);
+    }
 }
 
 impl Drop for VkPipeline {
     fn drop(&mut self) {
         log::debug!("Dropping pipeline");
+        self.device.untrack(self.handle);
+        self.device.untrack(self.layout);
         unsafe {
             self.device.handle.destroy_pipeline(self.handle, None);
             self.device
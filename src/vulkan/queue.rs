@@ -0,0 +1,291 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use ash::{prelude::VkResult, vk};
+
+use super::{
+    physical_device::VkPhysicalDevice,
+    surface::VkSurface,
+    timeline_semaphore::{create_timeline, timeline_value, wait_timeline},
+    tracker,
+};
+
+// Roles may share a queue when the device has no dedicated one, there is one queue per
+// `(family, index)` and submissions to it are serialized. Every submission signals the next
+// value of the queue timeline, so its completion can be checked without a fence.
+pub struct VkQueue {
+    device: ash::Device,
+    handle: Mutex<vk::Queue>,
+    // Destroyed together with the device
+    timeline: vk::Semaphore,
+    submitted: AtomicU64,
+    pub family: u32,
+    pub index: u32,
+}
+
+// A semaphore to wait for or signal, binary semaphores ignore the value
+#[derive(Clone, Copy)]
+pub struct VkSemaphoreValue {
+    pub semaphore: vk::Semaphore,
+    pub value: u64,
+}
+
+pub struct VkQueues {
+    pub graphics: Arc<VkQueue>,
+    pub present: Arc<VkQueue>,
+    // Copies only, falls back to the graphics queue
+    pub transfer: Arc<VkQueue>,
+    // Async compute, falls back to the graphics queue
+    pub compute: Arc<VkQueue>,
+}
+
+// Family and queue index of each role, chosen before the device is created
+pub struct VkQueueLayout {
+    graphics: (u32, u32),
+    present: (u32, u32),
+    transfer: (u32, u32),
+    compute: (u32, u32),
+}
+
+impl VkQueue {
+    // Returns the value of the queue timeline which is reached once the commands have finished
+    pub fn submit(
+        &self,
+        command_buffers: &[vk::CommandBuffer],
+        waits: &[(VkSemaphoreValue, vk::PipelineStageFlags)],
+        signals: &[VkSemaphoreValue],
+    ) -> u64 {
+        let handle = self.lock();
+        // Incremented under the lock so that values are signaled in submission order
+        let value = self.submitted.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let wait_semaphores: Vec<_> = waits.iter().map(|(wait, _)| wait.semaphore).collect();
+        let wait_values: Vec<_> = waits.iter().map(|(wait, _)| wait.value).collect();
+        let wait_stages: Vec<_> = waits.iter().map(|(_, stage)| *stage).collect();
+        let mut signal_semaphores: Vec<_> = signals.iter().map(|signal| signal.semaphore).collect();
+        let mut signal_values: Vec<_> = signals.iter().map(|signal| signal.value).collect();
+        signal_semaphores.push(self.timeline);
+        signal_values.push(value);
+
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let submit_info = vk::SubmitInfo::builder()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_info);
+        unsafe {
+            self.device
+                .queue_submit(*handle, &[submit_info.build()], vk::Fence::null())
+                .expect("Unable to submit queue")
+        };
+        value
+    }
+
+    // Value of the last submission which has finished
+    pub fn completed_value(&self) -> u64 {
+        timeline_value(&self.device, self.timeline)
+    }
+
+    pub fn is_complete(&self, value: u64) -> bool {
+        self.completed_value() >= value
+    }
+
+    pub fn wait_for(&self, value: u64) {
+        wait_timeline(&self.device, self.timeline, value, u64::MAX);
+    }
+
+    pub fn present(
+        &self,
+        extension: &ash::extensions::khr::Swapchain,
+        present_info: &vk::PresentInfoKHR,
+    ) -> VkResult<bool> {
+        let handle = self.lock();
+        unsafe { extension.queue_present(*handle, present_info) }
+    }
+
+    // The queue has to be locked for any other command which uses it
+    pub fn lock(&self) -> MutexGuard<'_, vk::Queue> {
+        self.handle.lock().unwrap()
+    }
+}
+
+impl VkQueues {
+    pub fn new(device: &ash::Device, layout: &VkQueueLayout) -> VkQueues {
+        let mut queues: BTreeMap<(u32, u32), Arc<VkQueue>> = BTreeMap::new();
+        let mut get_queue = |(family, index): (u32, u32)| {
+            let queue = queues.entry((family, index)).or_insert_with(|| {
+                let handle = unsafe { device.get_device_queue(family, index) };
+                let timeline = create_timeline(device, 0);
+                tracker::track(device.handle(), timeline, "queue timeline");
+                Arc::new(VkQueue {
+                    device: device.clone(),
+                    handle: Mutex::new(handle),
+                    timeline,
+                    submitted: AtomicU64::new(0),
+                    family,
+                    index,
+                })
+            });
+            Arc::clone(queue)
+        };
+
+        VkQueues {
+            graphics: get_queue(layout.graphics),
+            present: get_queue(layout.present),
+            transfer: get_queue(layout.transfer),
+            compute: get_queue(layout.compute),
+        }
+    }
+
+    // Distinct families of the given queues, resources used by several families are created
+    // with concurrent sharing so that they never need an ownership transfer
+    pub fn sharing_families(queues: &[&VkQueue]) -> Vec<u32> {
+        let mut families: Vec<_> = queues.iter().map(|queue| queue.family).collect();
+        families.sort_unstable();
+        families.dedup();
+        families
+    }
+
+    // Locks every queue in a fixed order, as needed while waiting for the device to be idle
+    pub fn lock_all(&self) -> Vec<MutexGuard<'_, vk::Queue>> {
+        self.unique().iter().map(|queue| queue.lock()).collect()
+    }
+
+    // Has to be called before the device is destroyed, the queues are unusable afterwards
+    pub fn destroy(&self, device: &ash::Device) {
+        for queue in self.unique() {
+            tracker::untrack(device.handle(), queue.timeline);
+            unsafe { device.destroy_semaphore(queue.timeline, None) };
+        }
+    }
+
+    fn unique(&self) -> Vec<&Arc<VkQueue>> {
+        let mut queues = vec![&self.graphics, &self.present, &self.transfer, &self.compute];
+        queues.sort_by_key(|queue| (queue.family, queue.index));
+        queues.dedup_by_key(|queue| (queue.family, queue.index));
+        queues
+    }
+}
+
+impl VkQueueLayout {
+    // Dedicated transfer and compute families are preferred, those are usually separate
+    // hardware queues which run alongside rendering
+    pub fn new(physical_device: &VkPhysicalDevice, surface: &VkSurface) -> VkQueueLayout {
+        let families = &physical_device.queue_families;
+        let mut allocated = vec![0; families.len()];
+        // Takes the next unused queue of the family, or shares its last queue
+        let mut allocate = |family: u32| {
+            let count = &mut allocated[family as usize];
+            let index = (*count).min(families[family as usize].queue_count - 1);
+            *count += 1;
+            (family, index)
+        };
+        let find_family = |predicate: &dyn Fn(vk::QueueFlags) -> bool| {
+            families
+                .iter()
+                .find(|family| family.queue_count > 0 && predicate(family.flags))
+                .map(|family| family.index)
+        };
+
+        let graphics_family = find_family(&|flags| flags.contains(vk::QueueFlags::GRAPHICS))
+            .expect("Unable to find a graphics queue family");
+        let graphics = allocate(graphics_family);
+
+        let present = if surface.physical_device_queue_support(physical_device, graphics_family) {
+            graphics
+        } else {
+            let present_family = families
+                .iter()
+                .find(|family| {
+                    family.queue_count > 0
+                        && surface.physical_device_queue_support(physical_device, family.index)
+                })
+                .expect("Unable to find a presentation queue family");
+            allocate(present_family.index)
+        };
+
+        let compute = match find_family(&|flags| {
+            flags.contains(vk::QueueFlags::COMPUTE) && !flags.contains(vk::QueueFlags::GRAPHICS)
+        }) {
+            Some(family) => allocate(family),
+            None => graphics,
+        };
+
+        // Graphics and compute families support transfers without reporting them
+        let transfer_only = |flags: vk::QueueFlags| {
+            flags.contains(vk::QueueFlags::TRANSFER)
+                && !flags.intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+        };
+        let transfer = match find_family(&transfer_only).or_else(|| {
+            find_family(&|flags| {
+                flags.intersects(vk::QueueFlags::TRANSFER | vk::QueueFlags::COMPUTE)
+                    && !flags.contains(vk::QueueFlags::GRAPHICS)
+            })
+        }) {
+            Some(family) => allocate(family),
+            None => graphics,
+        };
+
+        let layout = VkQueueLayout {
+            graphics,
+            present,
+            transfer,
+            compute,
+        };
+        log::info!(
+            "Choosing queues (family, index) graphics: {:?}, present: {:?}, transfer: {:?}, compute: {:?}",
+            layout.graphics,
+            layout.present,
+            layout.transfer,
+            layout.compute
+        );
+        layout
+    }
+
+    // Number of queues to create in each family
+    pub fn queue_counts(&self) -> BTreeMap<u32, u32> {
+        let mut counts = BTreeMap::new();
+        for (family, index) in [self.graphics, self.present, self.transfer, self.compute] {
+            let count = counts.entry(family).or_insert(0);
+            *count = (*count).max(index + 1);
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_counts_cover_every_role() {
+        let layout = VkQueueLayout {
+            graphics: (0, 0),
+            present: (0, 0),
+            transfer: (2, 0),
+            compute: (1, 1),
+        };
+        let counts: Vec<_> = layout.queue_counts().into_iter().collect();
+        assert_eq!(counts, vec![(0, 1), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn shared_roles_need_one_queue() {
+        let layout = VkQueueLayout {
+            graphics: (0, 0),
+            present: (0, 0),
+            transfer: (0, 1),
+            compute: (0, 0),
+        };
+        let counts: Vec<_> = layout.queue_counts().into_iter().collect();
+        assert_eq!(counts, vec![(0, 2)]);
+    }
+}
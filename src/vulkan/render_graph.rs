@@ -0,0 +1,874 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{
+    barrier::{ImageAccess, VkBarrierBatch, VkBarrierTracker},
+    command::VkCommandBuffer,
+    deletion_queue::VkDeletionQueue,
+    device::VkDevice,
+    image::VkImage,
+    swap_chain::VkSwapChain,
+    AttachmentDesc, SubpassDesc, VkFramebuffer, VkRenderPass, VkRenderPassBuilder,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphResource(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphPass(usize);
+
+// Attachments and sampled images of a pass. Attachments are cleared when a value is given,
+// loaded when an earlier pass wrote them and discarded otherwise. Each resolve attachment
+// receives the color attachment at the same position.
+#[derive(Clone, Default)]
+pub struct PassDesc {
+    pub colors: Vec<(GraphResource, Option<[f32; 4]>)>,
+    pub depth: Option<(GraphResource, Option<f32>)>,
+    pub resolves: Vec<GraphResource>,
+    // Sampled by the fragment shaders of the pass
+    pub reads: Vec<GraphResource>,
+    // Added to the render pass as they are, such as the self-dependency a pass needs to record
+    // barriers between its draws
+    pub dependencies: Vec<vk::SubpassDependency>,
+}
+
+// Frame made of passes which declare what they read and write. The graph creates the render
+// passes with their load and store ops, allocates the transient images for every swap-chain
+// image and records the barriers between passes. Transient images whose passes do not overlap
+// share memory.
+pub struct VkRenderGraph {
+    device: Arc<VkDevice>,
+    layout: GraphLayout,
+    render_passes: Vec<VkRenderPass>,
+    instances: Vec<GraphInstance>,
+    extent: vk::Extent2D,
+}
+
+// Images and passes of the graph, which do not depend on the device
+#[derive(Default)]
+struct GraphLayout {
+    images: Vec<GraphImage>,
+    passes: Vec<Pass>,
+    swap_chain: Option<GraphResource>,
+}
+
+struct GraphImage {
+    name: String,
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+    // Transient images are created by the graph and their contents do not outlive a frame
+    transient: bool,
+    // Last use before the frame, imported images keep their contents unless it is undefined
+    initial_access: ImageAccess,
+    // Stages which also have to finish before the first use, such as the wait for the
+    // swap-chain image to be acquired
+    initial_stages: vk::PipelineStageFlags,
+    final_access: Option<ImageAccess>,
+}
+
+struct Pass {
+    name: String,
+    desc: PassDesc,
+    // Resources in attachment order followed by the sampled images
+    accesses: Vec<(GraphResource, ImageAccess)>,
+}
+
+// Images and framebuffers used while rendering into one swap-chain image
+struct GraphInstance {
+    device: Arc<VkDevice>,
+    // Indexed by resource
+    images: Vec<(vk::Image, vk::ImageView)>,
+    // Created by the graph and destroyed with the instance
+    transient: Vec<bool>,
+    memory: Vec<vk::DeviceMemory>,
+    // Indexed by pass
+    framebuffers: Vec<VkFramebuffer>,
+    // Recorded before each pass, the last batch after the final pass. The barriers refer to
+    // the images of the instance, so they are built together with them.
+    barriers: Vec<VkBarrierBatch>,
+}
+
+// Memory shared by transient images which are used one after another
+struct MemorySlot {
+    requirements: vk::MemoryRequirements,
+    last_pass: usize,
+    stages: vk::PipelineStageFlags,
+    writes: vk::AccessFlags,
+    // Resources placed in the slot
+    images: Vec<usize>,
+}
+
+// Stages and writes of images which used the same memory earlier in the frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Aliasing {
+    stages: vk::PipelineStageFlags,
+    writes: vk::AccessFlags,
+}
+
+impl VkRenderGraph {
+    pub fn new(device: &Arc<VkDevice>) -> VkRenderGraph {
+        VkRenderGraph {
+            device: Arc::clone(device),
+            layout: GraphLayout::default(),
+            render_passes: Vec::new(),
+            instances: Vec::new(),
+            extent: vk::Extent2D::default(),
+        }
+    }
+
+    // Image with the size of the swap-chain, created and owned by the graph
+    pub fn create_image(
+        &mut self,
+        name: &str,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+    ) -> GraphResource {
+        self.check_not_compiled();
+        self.layout.create_image(name, format, samples)
+    }
+
+    // The frame waits for the image to be acquired before writing color attachments
+    pub fn import_swap_chain(&mut self, name: &str, format: vk::Format) -> GraphResource {
+        self.check_not_compiled();
+        self.layout.import_swap_chain(name, format)
+    }
+
+    // Passes run in the order they are added
+    pub fn add_pass(&mut self, name: &str, desc: PassDesc) -> GraphPass {
+        self.check_not_compiled();
+        self.layout.add_pass(name, desc)
+    }
+
+    // Creates the render passes, which do not depend on the size of the images
+    pub fn compile(&mut self) {
+        self.layout.validate();
+        self.render_passes = (0..self.layout.passes.len())
+            .map(|pass| {
+                let render_pass = self.create_render_pass(pass);
+                render_pass.set_name(&format!("{} render pass", self.layout.passes[pass].name));
+                render_pass
+            })
+            .collect();
+    }
+
+    // Recreates the transient images and framebuffers for every image of the swap-chain. The
+    // previous images are retired, frames in flight may still use them.
+    pub fn resize(&mut self, swap_chain: &VkSwapChain, deletion_queue: &mut VkDeletionQueue) {
+        if self.render_passes.is_empty() {
+            panic!("Render graph has to be compiled before it is resized");
+        }
+        deletion_queue.retire(std::mem::take(&mut self.instances));
+        self.extent = swap_chain.extent;
+
+        log::info!(
+            "Creating {} render graph instances of {}x{}",
+            swap_chain.images.len(),
+            self.extent.width,
+            self.extent.height
+        );
+        self.instances = swap_chain
+            .images
+            .iter()
+            .enumerate()
+            .map(|(instance, image)| self.create_instance(instance, (image.image, image.view)))
+            .collect();
+    }
+
+    pub fn render_pass(&self, pass: GraphPass) -> &VkRenderPass {
+        &self.render_passes[pass.0]
+    }
+
+    pub fn image_view(&self, resource: GraphResource, instance: usize) -> vk::ImageView {
+        self.instances[instance].images[resource.0].1
+    }
+
+    // Records every pass into the command buffer, `record_pass` is called inside the render
+    // pass to record the draws of each pass
+    pub fn record(
+        &self,
+        command_buffer: &VkCommandBuffer,
+        instance: usize,
+        mut record_pass: impl FnMut(GraphPass, &VkCommandBuffer),
+    ) {
+        let device = &self.device;
+        let instance = &self.instances[instance];
+
+        for (index, pass) in self.layout.passes.iter().enumerate() {
+            let _label = device.begin_label(command_buffer, &pass.name);
+            instance.barriers[index].record(device, command_buffer);
+
+            let clear_values = Self::clear_values(&pass.desc);
+            let begin_info = vk::RenderPassBeginInfo::builder()
+                .render_pass(self.render_passes[index].handle)
+                .framebuffer(instance.framebuffers[index].handle)
+                .render_area(vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: instance.framebuffers[index].extent,
+                })
+                .clear_values(&clear_values);
+            unsafe {
+                device.handle.cmd_begin_render_pass(
+                    command_buffer.handle,
+                    &begin_info,
+                    vk::SubpassContents::INLINE,
+                )
+            };
+            record_pass(GraphPass(index), command_buffer);
+            unsafe { device.handle.cmd_end_render_pass(command_buffer.handle) };
+        }
+
+        let final_barriers = &instance.barriers[self.layout.passes.len()];
+        final_barriers.record(device, command_buffer);
+    }
+
+    fn check_not_compiled(&self) {
+        if !self.render_passes.is_empty() {
+            panic!("A compiled render graph cannot be changed");
+        }
+    }
+
+    fn create_render_pass(&self, pass: usize) -> VkRenderPass {
+        let layout = &self.layout;
+        let desc = &layout.passes[pass].desc;
+        let attachment = |resource: GraphResource, access: ImageAccess, clear: bool| {
+            let image = &layout.images[resource.0];
+            let load_op = if clear {
+                vk::AttachmentLoadOp::CLEAR
+            } else if layout.has_contents(resource, pass) {
+                vk::AttachmentLoadOp::LOAD
+            } else {
+                vk::AttachmentLoadOp::DONT_CARE
+            };
+            let store_op = if layout.is_used_after(resource, pass) {
+                vk::AttachmentStoreOp::STORE
+            } else {
+                vk::AttachmentStoreOp::DONT_CARE
+            };
+            // Layouts are changed by the barriers recorded between passes
+            AttachmentDesc {
+                format: image.format,
+                samples: image.samples,
+                load_op,
+                store_op,
+                initial_layout: access.layout(),
+                final_layout: access.layout(),
+            }
+        };
+
+        let mut builder = VkRenderPassBuilder::new();
+        let mut subpass = SubpassDesc::default();
+        for (resource, clear) in &desc.colors {
+            subpass.colors.push(builder.add_attachment(attachment(
+                *resource,
+                ImageAccess::ColorAttachment,
+                clear.is_some(),
+            )));
+        }
+        subpass.depth = desc.depth.map(|(resource, clear)| {
+            builder.add_attachment(attachment(
+                resource,
+                ImageAccess::DepthAttachment,
+                clear.is_some(),
+            ))
+        });
+        for resource in &desc.resolves {
+            subpass.resolves.push(builder.add_attachment(attachment(
+                *resource,
+                ImageAccess::ColorAttachment,
+                false,
+            )));
+        }
+        builder.add_subpass(subpass);
+        for dependency in &desc.dependencies {
+            builder.add_dependency(*dependency);
+        }
+        builder.build(&self.device)
+    }
+
+    fn clear_values(desc: &PassDesc) -> Vec<vk::ClearValue> {
+        let mut clear_values: Vec<_> = desc
+            .colors
+            .iter()
+            .map(|(_, clear)| vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: clear.unwrap_or_default(),
+                },
+            })
+            .collect();
+        if let Some((_, clear)) = desc.depth {
+            clear_values.push(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: clear.unwrap_or(1.0),
+                    stencil: 0,
+                },
+            });
+        }
+        clear_values
+    }
+
+    fn create_instance(
+        &self,
+        instance: usize,
+        swap_chain_image: (vk::Image, vk::ImageView),
+    ) -> GraphInstance {
+        let device = &self.device;
+        let layout = &self.layout;
+        let mut images = vec![(vk::Image::null(), vk::ImageView::null()); layout.images.len()];
+        if let Some(resource) = layout.swap_chain {
+            images[resource.0] = swap_chain_image;
+        }
+
+        for (index, image) in layout.images.iter().enumerate() {
+            if image.transient {
+                images[index].0 = self.create_image_handle(index, instance);
+            }
+        }
+        let (slots, aliasing) = layout.alias_memory(|index| unsafe {
+            device.handle.get_image_memory_requirements(images[index].0)
+        });
+
+        let memory = slots
+            .iter()
+            .map(|slot| {
+                let memory_type_index = device
+                    .find_memory_type(slot.requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL);
+                let alloc_info = vk::MemoryAllocateInfo::builder()
+                    .allocation_size(slot.requirements.size)
+                    .memory_type_index(memory_type_index);
+                let memory = unsafe {
+                    device
+                        .handle
+                        .allocate_memory(&alloc_info, None)
+                        .expect("Unable to allocate render graph memory")
+                };
+                device.track(memory, "render graph memory");
+                for &index in &slot.images {
+                    unsafe {
+                        device
+                            .handle
+                            .bind_image_memory(images[index].0, memory, 0)
+                            .expect("Unable to bind render graph image memory")
+                    };
+                }
+                memory
+            })
+            .collect();
+        if slots.iter().any(|slot| slot.images.len() > 1) {
+            log::debug!(
+                "Render graph instance {} shares memory between images: {:?}",
+                instance,
+                slots
+                    .iter()
+                    .map(|slot| slot.images.len())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        for (index, image) in layout.images.iter().enumerate() {
+            let (handle, view) = &mut images[index];
+            if *handle == vk::Image::null() {
+                panic!("No image given for render graph resource {}", image.name);
+            }
+            if image.transient {
+                *view = VkImage::create_image_view(
+                    device,
+                    *handle,
+                    1,
+                    image.format,
+                    view_aspect(image.format),
+                );
+                device.track(*view, "render graph image view");
+                device.set_object_name(*view, &format!("{} {} view", image.name, instance));
+            }
+        }
+
+        let framebuffers = layout
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let attachments: Vec<_> = pass
+                    .accesses
+                    .iter()
+                    .filter(|(_, access)| access.is_write())
+                    .map(|(resource, _)| images[resource.0].1)
+                    .collect();
+                let framebuffer = VkFramebuffer::new(
+                    device,
+                    &self.render_passes[index],
+                    &attachments,
+                    self.extent,
+                );
+                framebuffer.set_name(&format!("{} framebuffer {}", pass.name, instance));
+                framebuffer
+            })
+            .collect();
+
+        GraphInstance {
+            device: Arc::clone(device),
+            barriers: layout.barriers(&images, &aliasing),
+            images,
+            transient: layout.images.iter().map(|image| image.transient).collect(),
+            memory,
+            framebuffers,
+        }
+    }
+
+    fn create_image_handle(&self, resource: usize, instance: usize) -> vk::Image {
+        let image = &self.layout.images[resource];
+        let mut usage = vk::ImageUsageFlags::empty();
+        for pass in &self.layout.passes {
+            for &(other, access) in &pass.accesses {
+                if other.0 != resource {
+                    continue;
+                }
+                usage |= match access {
+                    ImageAccess::ColorAttachment => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                    ImageAccess::DepthAttachment => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                    _ => vk::ImageUsageFlags::SAMPLED,
+                };
+            }
+        }
+        // Attachments which are never sampled do not have to be backed by memory on tilers
+        if !usage.contains(vk::ImageUsageFlags::SAMPLED) {
+            usage |= vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(image.format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(image.samples);
+        let handle = unsafe {
+            self.device
+                .handle
+                .create_image(&image_info, None)
+                .expect("Unable to create render graph image")
+        };
+        self.device.track(handle, "render graph image");
+        self.device
+            .set_object_name(handle, &format!("{} {}", image.name, instance));
+        handle
+    }
+}
+
+impl GraphLayout {
+    fn create_image(
+        &mut self,
+        name: &str,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+    ) -> GraphResource {
+        self.add_image(GraphImage {
+            name: name.to_owned(),
+            format,
+            samples,
+            transient: true,
+            initial_access: ImageAccess::Undefined,
+            initial_stages: vk::PipelineStageFlags::empty(),
+            final_access: None,
+        })
+    }
+
+    fn import_swap_chain(&mut self, name: &str, format: vk::Format) -> GraphResource {
+        if self.swap_chain.is_some() {
+            panic!("Render graph already has a swap-chain image");
+        }
+        let resource = self.add_image(GraphImage {
+            name: name.to_owned(),
+            format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            transient: false,
+            initial_access: ImageAccess::Undefined,
+            initial_stages: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            final_access: Some(ImageAccess::Present),
+        });
+        self.swap_chain = Some(resource);
+        resource
+    }
+
+    fn add_image(&mut self, image: GraphImage) -> GraphResource {
+        self.images.push(image);
+        GraphResource(self.images.len() - 1)
+    }
+
+    fn add_pass(&mut self, name: &str, desc: PassDesc) -> GraphPass {
+        let mut accesses: Vec<_> = desc
+            .colors
+            .iter()
+            .map(|(resource, _)| (*resource, ImageAccess::ColorAttachment))
+            .collect();
+        if let Some((resource, _)) = desc.depth {
+            accesses.push((resource, ImageAccess::DepthAttachment));
+        }
+        accesses.extend(
+            desc.resolves
+                .iter()
+                .map(|resource| (*resource, ImageAccess::ColorAttachment)),
+        );
+        accesses.extend(desc.reads.iter().map(|resource| {
+            let format = self.images[resource.0].format;
+            if VkImage::is_depth_format(format) {
+                (*resource, ImageAccess::DepthRead)
+            } else {
+                (*resource, ImageAccess::FragmentShaderRead)
+            }
+        }));
+
+        self.passes.push(Pass {
+            name: name.to_owned(),
+            desc,
+            accesses,
+        });
+        GraphPass(self.passes.len() - 1)
+    }
+
+    fn validate(&self) {
+        for (index, image) in self.images.iter().enumerate() {
+            if image.transient && self.lifetime(GraphResource(index)).is_none() {
+                panic!("Render graph image {} is never used", image.name);
+            }
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            let desc = &pass.desc;
+            if !desc.resolves.is_empty() && desc.resolves.len() != desc.colors.len() {
+                panic!("Pass {} needs a resolve attachment per color", pass.name);
+            }
+            for (position, (resource, _)) in pass.accesses.iter().enumerate() {
+                if pass.accesses[..position]
+                    .iter()
+                    .any(|(other, _)| other == resource)
+                {
+                    let image = &self.images[resource.0];
+                    panic!("Pass {} uses {} more than once", pass.name, image.name);
+                }
+            }
+            for resource in &desc.reads {
+                if !self.has_contents(*resource, index) {
+                    let image = &self.images[resource.0];
+                    panic!(
+                        "Pass {} reads {} before it is written",
+                        pass.name, image.name
+                    );
+                }
+            }
+        }
+    }
+
+    fn uses(&self, resource: GraphResource, pass: usize) -> bool {
+        self.passes[pass]
+            .accesses
+            .iter()
+            .any(|(other, _)| *other == resource)
+    }
+
+    fn is_written(&self, resource: GraphResource, pass: usize) -> bool {
+        self.passes[pass]
+            .accesses
+            .iter()
+            .any(|(other, access)| *other == resource && access.is_write())
+    }
+
+    // Whether the resource holds something before the pass runs
+    fn has_contents(&self, resource: GraphResource, pass: usize) -> bool {
+        let image = &self.images[resource.0];
+        (!image.transient && image.initial_access != ImageAccess::Undefined)
+            || (0..pass).any(|earlier| self.is_written(resource, earlier))
+    }
+
+    // Whether the contents are still needed after the pass
+    fn is_used_after(&self, resource: GraphResource, pass: usize) -> bool {
+        let image = &self.images[resource.0];
+        !image.transient || (pass + 1..self.passes.len()).any(|later| self.uses(resource, later))
+    }
+
+    // First and last pass using the resource
+    fn lifetime(&self, resource: GraphResource) -> Option<(usize, usize)> {
+        let first = (0..self.passes.len()).find(|&pass| self.uses(resource, pass))?;
+        let last = (0..self.passes.len()).rfind(|&pass| self.uses(resource, pass))?;
+        Some((first, last))
+    }
+
+    // Places the transient images in order of their first use, reusing the memory of an image
+    // whose last pass is over. Returns the slots and, per resource, what used the memory before.
+    fn alias_memory(
+        &self,
+        requirements: impl Fn(usize) -> vk::MemoryRequirements,
+    ) -> (Vec<MemorySlot>, Vec<Aliasing>) {
+        let mut transients: Vec<_> = (0..self.images.len())
+            .filter(|&index| self.images[index].transient)
+            .filter_map(|index| Some((index, self.lifetime(GraphResource(index))?)))
+            .collect();
+        transients.sort_by_key(|(_, (first, _))| *first);
+
+        let mut slots: Vec<MemorySlot> = Vec::new();
+        let mut aliasing = vec![
+            Aliasing {
+                stages: vk::PipelineStageFlags::empty(),
+                writes: vk::AccessFlags::empty(),
+            };
+            self.images.len()
+        ];
+        for (index, (first_pass, last_pass)) in transients {
+            let requirements = requirements(index);
+            let (stages, writes) = self.passes[first_pass..=last_pass]
+                .iter()
+                .flat_map(|pass| &pass.accesses)
+                .filter(|(resource, _)| resource.0 == index)
+                .fold(
+                    (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty()),
+                    |(stages, writes), (_, access)| {
+                        (stages | access.stages(), writes | access.writes())
+                    },
+                );
+
+            let slot = slots.iter_mut().find(|slot| {
+                slot.last_pass < first_pass
+                    && slot.requirements.memory_type_bits & requirements.memory_type_bits != 0
+            });
+            match slot {
+                Some(slot) => {
+                    aliasing[index] = Aliasing {
+                        stages: slot.stages,
+                        writes: slot.writes,
+                    };
+                    slot.requirements.size = slot.requirements.size.max(requirements.size);
+                    slot.requirements.alignment =
+                        slot.requirements.alignment.max(requirements.alignment);
+                    slot.requirements.memory_type_bits &= requirements.memory_type_bits;
+                    slot.last_pass = last_pass;
+                    slot.stages |= stages;
+                    slot.writes |= writes;
+                    slot.images.push(index);
+                }
+                None => slots.push(MemorySlot {
+                    requirements,
+                    last_pass,
+                    stages,
+                    writes,
+                    images: vec![index],
+                }),
+            }
+        }
+        (slots, aliasing)
+    }
+
+    // Barriers before each pass and after the last one. Aliased images wait for the images
+    // which used their memory before, with a global barrier for the writes of those.
+    fn barriers(
+        &self,
+        images: &[(vk::Image, vk::ImageView)],
+        aliasing: &[Aliasing],
+    ) -> Vec<VkBarrierBatch> {
+        let mut tracker = VkBarrierTracker::new();
+        for (index, image) in self.images.iter().enumerate() {
+            tracker.track_attachment(
+                images[index].0,
+                barrier_aspect(image.format),
+                image.initial_access,
+                image.initial_stages | aliasing[index].stages,
+            );
+        }
+
+        let mut used = vec![false; self.images.len()];
+        let mut batches = Vec::new();
+        for pass in &self.passes {
+            for &(resource, access) in &pass.accesses {
+                let aliased = aliasing[resource.0];
+                if !used[resource.0] && !aliased.writes.is_empty() {
+                    tracker.use_aliased_memory(aliased.stages, aliased.writes, access);
+                }
+                used[resource.0] = true;
+                tracker.use_attachment(images[resource.0].0, access);
+            }
+            batches.push(tracker.take_batch());
+        }
+
+        for (index, image) in self.images.iter().enumerate() {
+            if let Some(final_access) = image.final_access {
+                tracker.use_attachment(images[index].0, final_access);
+            }
+        }
+        batches.push(tracker.take_batch());
+        batches
+    }
+}
+
+impl Drop for GraphInstance {
+    fn drop(&mut self) {
+        let device = &self.device;
+        // Framebuffers reference the views destroyed below
+        self.framebuffers.clear();
+        unsafe {
+            for (&(image, view), &transient) in self.images.iter().zip(&self.transient) {
+                if !transient {
+                    continue;
+                }
+                device.untrack(view);
+                device.untrack(image);
+                device.handle.destroy_image_view(view, None);
+                device.handle.destroy_image(image, None);
+            }
+            for &memory in &self.memory {
+                device.untrack(memory);
+                device.handle.free_memory(memory, None);
+            }
+        }
+    }
+}
+
+impl Drop for VkRenderGraph {
+    fn drop(&mut self) {
+        log::debug!("Dropping render graph");
+    }
+}
+
+// Depth and stencil are transitioned together
+fn barrier_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    if VkImage::has_stencil_component(format) {
+        vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+    } else {
+        view_aspect(format)
+    }
+}
+
+fn view_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    if VkImage::is_depth_format(format) {
+        vk::ImageAspectFlags::DEPTH
+    } else {
+        vk::ImageAspectFlags::COLOR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scene rendering color and depth, a blur reading the color into another image and a
+    // tonemap pass reading the blurred image into the swap-chain
+    fn three_passes() -> (GraphLayout, [GraphResource; 4]) {
+        let samples = vk::SampleCountFlags::TYPE_1;
+        let mut layout = GraphLayout::default();
+        let color = layout.create_image("color", vk::Format::R16G16B16A16_SFLOAT, samples);
+        let depth = layout.create_image("depth", vk::Format::D32_SFLOAT, samples);
+        let blurred = layout.create_image("blurred", vk::Format::R16G16B16A16_SFLOAT, samples);
+        let swap_chain = layout.import_swap_chain("swap-chain", vk::Format::B8G8R8A8_SRGB);
+        layout.add_pass(
+            "scene",
+            PassDesc {
+                colors: vec![(color, Some([0.0; 4]))],
+                depth: Some((depth, Some(1.0))),
+                ..Default::default()
+            },
+        );
+        layout.add_pass(
+            "blur",
+            PassDesc {
+                colors: vec![(blurred, None)],
+                reads: vec![color],
+                ..Default::default()
+            },
+        );
+        layout.add_pass(
+            "tonemap",
+            PassDesc {
+                colors: vec![(swap_chain, None)],
+                reads: vec![blurred],
+                ..Default::default()
+            },
+        );
+        layout.validate();
+        (layout, [color, depth, blurred, swap_chain])
+    }
+
+    fn requirements(size: u64, memory_type_bits: u32) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size,
+            alignment: 256,
+            memory_type_bits,
+        }
+    }
+
+    #[test]
+    fn lifetimes_span_the_passes_using_an_image() {
+        let (layout, [color, depth, blurred, swap_chain]) = three_passes();
+        assert_eq!(layout.lifetime(color), Some((0, 1)));
+        assert_eq!(layout.lifetime(depth), Some((0, 0)));
+        assert_eq!(layout.lifetime(blurred), Some((1, 2)));
+        assert_eq!(layout.lifetime(swap_chain), Some((2, 2)));
+
+        assert!(layout.is_used_after(color, 0));
+        assert!(!layout.is_used_after(color, 1));
+        assert!(!layout.is_used_after(depth, 0));
+        assert!(!layout.has_contents(blurred, 1));
+        assert!(layout.has_contents(blurred, 2));
+    }
+
+    #[test]
+    fn images_share_memory_once_their_lifetimes_are_over() {
+        let (layout, [color, depth, blurred, _]) = three_passes();
+        let sizes = [100, 50, 80];
+        let (slots, aliasing) = layout.alias_memory(|index| requirements(sizes[index], 0b11));
+
+        // The blurred image is first used after the last use of the depth image
+        let images: Vec<_> = slots.iter().map(|slot| slot.images.clone()).collect();
+        assert_eq!(images, [vec![color.0], vec![depth.0, blurred.0]]);
+        assert_eq!(slots[1].requirements.size, 80);
+        assert_eq!(slots[1].last_pass, 2);
+
+        assert_eq!(aliasing[color.0].writes, vk::AccessFlags::empty());
+        assert_eq!(
+            aliasing[blurred.0],
+            Aliasing {
+                stages: ImageAccess::DepthAttachment.stages(),
+                writes: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            }
+        );
+    }
+
+    #[test]
+    fn images_only_share_compatible_memory() {
+        let (layout, [color, depth, blurred, _]) = three_passes();
+        let (slots, aliasing) = layout.alias_memory(|index| {
+            if index == depth.0 {
+                requirements(50, 0b01)
+            } else {
+                requirements(100, 0b10)
+            }
+        });
+
+        let images: Vec<_> = slots.iter().map(|slot| slot.images.clone()).collect();
+        assert_eq!(images, [vec![color.0], vec![depth.0], vec![blurred.0]]);
+        assert_eq!(aliasing[blurred.0].writes, vk::AccessFlags::empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "reads blurred before it is written")]
+    fn reads_need_an_earlier_write() {
+        let mut layout = GraphLayout::default();
+        let samples = vk::SampleCountFlags::TYPE_1;
+        let blurred = layout.create_image("blurred", vk::Format::R8G8B8A8_UNORM, samples);
+        let swap_chain = layout.import_swap_chain("swap-chain", vk::Format::B8G8R8A8_SRGB);
+        layout.add_pass(
+            "tonemap",
+            PassDesc {
+                colors: vec![(swap_chain, None)],
+                reads: vec![blurred],
+                ..Default::default()
+            },
+        );
+        layout.validate();
+    }
+}
@@ -2,129 +2,439 @@ use std::sync::Arc;
 
 use ash::vk;
 
-use super::device::VkDevice;
+use super::{device::VkDevice, image::VkImage};
 
 pub struct VkRenderPass {
     device: Arc<VkDevice>,
     pub handle: vk::RenderPass,
 }
 
-impl VkRenderPass {
-    pub fn new(
-        device: &Arc<VkDevice>,
-        format: vk::Format,
-        depth_format: vk::Format,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> VkRenderPass {
-        let color_attachment_desc = vk::AttachmentDescription::builder()
-            .format(format)
-            .samples(msaa_samples)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL) // For MSAA
-            .build();
-
-        let depth_attachment_desc = vk::AttachmentDescription::builder()
-            .format(depth_format)
-            .samples(msaa_samples)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
-
-        let color_attachment_resolve = vk::AttachmentDescription::builder()
-            .format(format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-            .build();
-
-        let attachment_descs = [
-            color_attachment_desc,
-            depth_attachment_desc,
-            color_attachment_resolve,
-        ];
-
-        let color_attachment_ref = vk::AttachmentReference::builder()
-            .attachment(0)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build();
-
-        let depth_attachment_ref = vk::AttachmentReference::builder()
-            .attachment(1)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-            .build();
-
-        let color_attachment_resolve_ref = vk::AttachmentReference::builder()
-            .attachment(2)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build();
-
-        let color_attachment_refs = [color_attachment_ref];
-        let color_attachment_resolve_refs = [color_attachment_resolve_ref];
-
-        let subpass_desc = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_refs)
-            .depth_stencil_attachment(&depth_attachment_ref)
-            .resolve_attachments(&color_attachment_resolve_refs)
-            .build();
-        let subpass_descs = [subpass_desc];
-
-        let subpass_dep = vk::SubpassDependency::builder()
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(
-                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            )
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_stage_mask(
-                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            )
-            .dst_access_mask(
-                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-            )
-            .build();
-        let subpass_deps = [subpass_dep];
+// Stencil formats use the load and store ops for the stencil aspect as well
+#[derive(Clone, Copy, Debug)]
+pub struct AttachmentDesc {
+    pub format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub initial_layout: vk::ImageLayout,
+    pub final_layout: vk::ImageLayout,
+}
+
+// Attachments of a subpass given by their index in the render pass. There is either no resolve
+// attachment or one per color attachment, `vk::ATTACHMENT_UNUSED` skips a color.
+#[derive(Clone, Debug, Default)]
+pub struct SubpassDesc {
+    pub colors: Vec<u32>,
+    pub resolves: Vec<u32>,
+    // Read by the fragment shader at the same pixel, written by an earlier subpass
+    pub inputs: Vec<u32>,
+    pub depth: Option<u32>,
+    // The depth attachment is only tested, which allows it to be an input at the same time
+    pub depth_read_only: bool,
+    // Attachments which are not used by the subpass but read afterwards
+    pub preserves: Vec<u32>,
+}
+
+// Render pass with any number of attachments and subpasses. Subpasses reading an input
+// attachment depend on the last earlier subpass writing it, other dependencies are added with
+// `add_dependency`.
+#[derive(Clone, Default)]
+pub struct VkRenderPassBuilder {
+    attachments: Vec<AttachmentDesc>,
+    subpasses: Vec<SubpassDesc>,
+    dependencies: Vec<vk::SubpassDependency>,
+}
+
+impl VkRenderPassBuilder {
+    pub fn new() -> VkRenderPassBuilder {
+        VkRenderPassBuilder::default()
+    }
+
+    // Returns the index used to reference the attachment
+    pub fn add_attachment(&mut self, desc: AttachmentDesc) -> u32 {
+        self.attachments.push(desc);
+        self.attachments.len() as u32 - 1
+    }
 
+    // Subpasses run in the order they are added, returns the index used by dependencies
+    pub fn add_subpass(&mut self, desc: SubpassDesc) -> u32 {
+        let attachment_count = self.attachments.len() as u32;
+        let used = desc
+            .colors
+            .iter()
+            .chain(&desc.resolves)
+            .chain(&desc.inputs)
+            .chain(&desc.depth)
+            .chain(&desc.preserves);
+        for &attachment in used {
+            if attachment != vk::ATTACHMENT_UNUSED && attachment >= attachment_count {
+                panic!("Subpass uses unknown attachment {}", attachment);
+            }
+        }
+        if !desc.resolves.is_empty() && desc.resolves.len() != desc.colors.len() {
+            panic!("Subpass needs a resolve attachment per color attachment");
+        }
+        if let Some(depth) = desc.depth {
+            if !desc.depth_read_only && desc.inputs.contains(&depth) {
+                panic!(
+                    "Subpass reads its depth attachment {} while writing it",
+                    depth
+                );
+            }
+        }
+        self.subpasses.push(desc);
+        self.subpasses.len() as u32 - 1
+    }
+
+    // `vk::SUBPASS_EXTERNAL` refers to the commands before or after the render pass
+    pub fn add_dependency(&mut self, dependency: vk::SubpassDependency) -> &mut Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    pub fn build(&self, device: &Arc<VkDevice>) -> VkRenderPass {
+        self.with_create_info(|create_info| VkRenderPass::new_with_info(device, create_info))
+    }
+
+    // The create info points into data which only lives during `f`
+    fn with_create_info<R>(&self, f: impl FnOnce(&vk::RenderPassCreateInfo) -> R) -> R {
+        if self.subpasses.is_empty() {
+            panic!("Render pass needs at least one subpass");
+        }
+
+        let attachment_descs: Vec<_> = self
+            .attachments
+            .iter()
+            .map(|desc| {
+                let (stencil_load_op, stencil_store_op) =
+                    if VkImage::has_stencil_component(desc.format) {
+                        (desc.load_op, desc.store_op)
+                    } else {
+                        (
+                            vk::AttachmentLoadOp::DONT_CARE,
+                            vk::AttachmentStoreOp::DONT_CARE,
+                        )
+                    };
+                vk::AttachmentDescription::builder()
+                    .format(desc.format)
+                    .samples(desc.samples)
+                    .load_op(desc.load_op)
+                    .store_op(desc.store_op)
+                    .stencil_load_op(stencil_load_op)
+                    .stencil_store_op(stencil_store_op)
+                    .initial_layout(desc.initial_layout)
+                    .final_layout(desc.final_layout)
+                    .build()
+            })
+            .collect();
+
+        // The references have to outlive the subpass descriptions pointing to them
+        let reference = |attachment: u32, layout: vk::ImageLayout| vk::AttachmentReference {
+            attachment,
+            layout,
+        };
+        let references: Vec<_> = self
+            .subpasses
+            .iter()
+            .map(|subpass| {
+                let colors: Vec<_> = subpass
+                    .colors
+                    .iter()
+                    .map(|&index| reference(index, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                    .collect();
+                let resolves: Vec<_> = subpass
+                    .resolves
+                    .iter()
+                    .map(|&index| reference(index, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                    .collect();
+                let inputs: Vec<_> = subpass
+                    .inputs
+                    .iter()
+                    .map(|&index| reference(index, self.input_layout(index)))
+                    .collect();
+                let depth_layout = if subpass.depth_read_only {
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                } else {
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                };
+                let depth = subpass.depth.map(|index| reference(index, depth_layout));
+                (colors, resolves, inputs, depth)
+            })
+            .collect();
+        let subpass_descs: Vec<_> = self
+            .subpasses
+            .iter()
+            .zip(&references)
+            .map(|(subpass, (colors, resolves, inputs, depth))| {
+                let mut desc = vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(colors)
+                    .input_attachments(inputs)
+                    .preserve_attachments(&subpass.preserves);
+                if !resolves.is_empty() {
+                    desc = desc.resolve_attachments(resolves);
+                }
+                if let Some(depth) = depth {
+                    desc = desc.depth_stencil_attachment(depth);
+                }
+                desc.build()
+            })
+            .collect();
+
+        let mut dependencies = self.input_dependencies();
+        dependencies.extend_from_slice(&self.dependencies);
         let render_pass_info = vk::RenderPassCreateInfo::builder()
             .attachments(&attachment_descs)
             .subpasses(&subpass_descs)
-            .dependencies(&subpass_deps)
-            .build();
+            .dependencies(&dependencies);
+        f(&render_pass_info)
+    }
 
+    fn input_layout(&self, attachment: u32) -> vk::ImageLayout {
+        if VkImage::is_depth_format(self.attachments[attachment as usize].format) {
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        } else {
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        }
+    }
+
+    // Writes of an input attachment have to be visible to the fragment shaders of the reading
+    // subpass, only at the same pixel
+    fn input_dependencies(&self) -> Vec<vk::SubpassDependency> {
+        let mut dependencies: Vec<vk::SubpassDependency> = Vec::new();
+        for (dst, subpass) in self.subpasses.iter().enumerate() {
+            for &input in &subpass.inputs {
+                let writer = self.subpasses[..dst].iter().rposition(|earlier| {
+                    earlier.colors.contains(&input)
+                        || earlier.resolves.contains(&input)
+                        || (earlier.depth == Some(input) && !earlier.depth_read_only)
+                });
+                let src = match writer {
+                    Some(src) => src,
+                    None => continue,
+                };
+                let (src_stages, src_access) = if self.subpasses[src].depth == Some(input) {
+                    (
+                        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    )
+                } else {
+                    (
+                        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                        vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    )
+                };
+
+                let existing = dependencies.iter_mut().find(|dependency| {
+                    dependency.src_subpass == src as u32 && dependency.dst_subpass == dst as u32
+                });
+                match existing {
+                    Some(dependency) => {
+                        dependency.src_stage_mask |= src_stages;
+                        dependency.src_access_mask |= src_access;
+                    }
+                    None => dependencies.push(
+                        vk::SubpassDependency::builder()
+                            .src_subpass(src as u32)
+                            .dst_subpass(dst as u32)
+                            .src_stage_mask(src_stages)
+                            .src_access_mask(src_access)
+                            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                            .dst_access_mask(vk::AccessFlags::INPUT_ATTACHMENT_READ)
+                            .dependency_flags(vk::DependencyFlags::BY_REGION)
+                            .build(),
+                    ),
+                }
+            }
+        }
+        dependencies
+    }
+}
+
+impl VkRenderPass {
+    // Usually created through `VkRenderPassBuilder`
+    pub fn new_with_info(
+        device: &Arc<VkDevice>,
+        create_info: &vk::RenderPassCreateInfo,
+    ) -> VkRenderPass {
         let handle = unsafe {
             device
                 .handle
-                .create_render_pass(&render_pass_info, None)
+                .create_render_pass(create_info, None)
                 .expect("Unable to create render pass")
         };
+        device.track(handle, "render pass");
 
         VkRenderPass {
             device: Arc::clone(device),
             handle,
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkRenderPass {
     fn drop(&mut self) {
         log::debug!("Dropping render pass");
+        self.device.untrack(self.handle);
         unsafe {
             self.device.handle.destroy_render_pass(self.handle, None);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(format: vk::Format, layout: vk::ImageLayout) -> AttachmentDesc {
+        AttachmentDesc {
+            format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: layout,
+        }
+    }
+
+    // G-buffer pass writing color and depth, followed by a lighting pass reading both as inputs
+    fn deferred() -> VkRenderPassBuilder {
+        let mut builder = VkRenderPassBuilder::new();
+        let albedo = builder.add_attachment(attachment(
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        ));
+        let depth = builder.add_attachment(attachment(
+            vk::Format::D32_SFLOAT,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        ));
+        let lit = builder.add_attachment(attachment(
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        ));
+        builder.add_subpass(SubpassDesc {
+            colors: vec![albedo],
+            depth: Some(depth),
+            ..Default::default()
+        });
+        builder.add_subpass(SubpassDesc {
+            colors: vec![lit],
+            inputs: vec![albedo, depth],
+            depth: Some(depth),
+            depth_read_only: true,
+            ..Default::default()
+        });
+        builder
+    }
+
+    #[test]
+    fn inputs_depend_on_the_subpass_writing_them() {
+        deferred().with_create_info(|create_info| {
+            assert_eq!(create_info.attachment_count, 3);
+            assert_eq!(create_info.subpass_count, 2);
+            let (subpasses, dependencies) = unsafe {
+                (
+                    std::slice::from_raw_parts(create_info.p_subpasses, 2),
+                    std::slice::from_raw_parts(
+                        create_info.p_dependencies,
+                        create_info.dependency_count as usize,
+                    ),
+                )
+            };
+
+            let lighting = &subpasses[1];
+            let inputs = unsafe {
+                std::slice::from_raw_parts(
+                    lighting.p_input_attachments,
+                    lighting.input_attachment_count as usize,
+                )
+            };
+            let input_layouts: Vec<_> = inputs.iter().map(|input| input.layout).collect();
+            assert_eq!(
+                input_layouts,
+                [
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                ]
+            );
+            let depth = unsafe { *lighting.p_depth_stencil_attachment };
+            assert_eq!(
+                depth.layout,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            );
+
+            // Color and depth writes are merged into one dependency
+            assert_eq!(dependencies.len(), 1);
+            let dependency = dependencies[0];
+            assert_eq!((dependency.src_subpass, dependency.dst_subpass), (0, 1));
+            assert_eq!(
+                dependency.src_stage_mask,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+            );
+            assert_eq!(
+                dependency.src_access_mask,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            );
+            assert_eq!(
+                dependency.dst_stage_mask,
+                vk::PipelineStageFlags::FRAGMENT_SHADER
+            );
+            assert_eq!(
+                dependency.dst_access_mask,
+                vk::AccessFlags::INPUT_ATTACHMENT_READ
+            );
+            assert_eq!(dependency.dependency_flags, vk::DependencyFlags::BY_REGION);
+        });
+    }
+
+    #[test]
+    fn added_dependencies_follow_the_derived_ones() {
+        let mut builder = deferred();
+        let external = vk::SubpassDependency {
+            src_subpass: 1,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            dependency_flags: vk::DependencyFlags::empty(),
+        };
+        builder.add_dependency(external);
+        builder.with_create_info(|create_info| {
+            let dependencies = unsafe {
+                std::slice::from_raw_parts(
+                    create_info.p_dependencies,
+                    create_info.dependency_count as usize,
+                )
+            };
+            let subpasses: Vec<_> = dependencies
+                .iter()
+                .map(|dependency| (dependency.src_subpass, dependency.dst_subpass))
+                .collect();
+            assert_eq!(subpasses, [(0, 1), (1, vk::SUBPASS_EXTERNAL)]);
+            assert_eq!(
+                dependencies[1].dst_access_mask,
+                vk::AccessFlags::SHADER_READ
+            );
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "reads its depth attachment 1 while writing it")]
+    fn written_depth_cannot_be_an_input() {
+        let mut builder = deferred();
+        builder.add_subpass(SubpassDesc {
+            inputs: vec![1],
+            depth: Some(1),
+            ..Default::default()
+        });
+    }
+}
@@ -0,0 +1,115 @@
+use std::ffi::{CStr, CString};
+
+use ash::{extensions::khr::Swapchain, vk};
+
+use super::{features::VkFeatures, physical_device::VkPhysicalDevice};
+
+// What the application needs from a device. Devices missing a required feature or extension are
+// rejected, optional ones are enabled when supported and make a device more suitable.
+#[derive(Clone, Default)]
+pub struct VkDeviceRequirements {
+    pub required_features: VkFeatures,
+    pub optional_features: VkFeatures,
+    pub required_extensions: Vec<&'static CStr>,
+    pub optional_extensions: Vec<&'static CStr>,
+}
+
+// The outcome of negotiating the requirements with a device
+pub struct VkDeviceCapabilities {
+    pub features: VkFeatures,
+    pub extensions: Vec<&'static CStr>,
+}
+
+impl VkDeviceRequirements {
+    // Presenting to the window and timeline semaphores, which track queue submissions, are
+    // always required
+    pub fn new() -> VkDeviceRequirements {
+        let mut requirements = VkDeviceRequirements {
+            required_extensions: vec![Swapchain::name()],
+            ..Default::default()
+        };
+        requirements.required_features.v1_2.timeline_semaphore = vk::TRUE;
+        requirements
+    }
+
+    // Names of the required features and extensions the device does not support
+    pub fn find_missing(&self, physical_device: &VkPhysicalDevice) -> Vec<String> {
+        let supported_extensions = physical_device.get_extension_names();
+        let mut missing = self
+            .required_features
+            .missing_from(&VkFeatures::supported(physical_device));
+        missing.extend(
+            self.required_extensions
+                .iter()
+                .filter(|extension| !contains(&supported_extensions, extension))
+                .map(|extension| extension.to_string_lossy().into_owned()),
+        );
+        missing
+    }
+
+    // Everything required plus the optional capabilities the device supports
+    pub fn negotiate(&self, physical_device: &VkPhysicalDevice) -> VkDeviceCapabilities {
+        let supported_features = VkFeatures::supported(physical_device);
+        let supported_extensions = physical_device.get_extension_names();
+
+        let features = self
+            .optional_features
+            .intersection(&supported_features)
+            .union(&self.required_features);
+        let mut extensions = self.required_extensions.clone();
+        for extension in &self.optional_extensions {
+            if contains(&supported_extensions, extension) && !extensions.contains(extension) {
+                extensions.push(extension);
+            }
+        }
+
+        VkDeviceCapabilities {
+            features,
+            extensions,
+        }
+    }
+
+    // Number of optional features and extensions the device supports
+    pub fn count_optional(&self, physical_device: &VkPhysicalDevice) -> usize {
+        let supported_features = VkFeatures::supported(physical_device);
+        let supported_extensions = physical_device.get_extension_names();
+        let extensions = self
+            .optional_extensions
+            .iter()
+            .filter(|extension| contains(&supported_extensions, extension))
+            .count();
+        self.optional_features
+            .intersection(&supported_features)
+            .count()
+            + extensions
+    }
+}
+
+impl VkDeviceCapabilities {
+    pub fn has_extension(&self, name: &CStr) -> bool {
+        self.extensions.contains(&name)
+    }
+}
+
+fn contains(extensions: &[CString], name: &CStr) -> bool {
+    extensions
+        .iter()
+        .any(|extension| extension.as_c_str() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::extensions::khr::Synchronization2;
+
+    use super::*;
+
+    #[test]
+    fn only_negotiated_extensions_are_enabled() {
+        let capabilities = VkDeviceCapabilities {
+            features: VkFeatures::default(),
+            extensions: vec![Swapchain::name()],
+        };
+        assert!(capabilities.has_extension(Swapchain::name()));
+        assert!(!capabilities.has_extension(Synchronization2::name()));
+    }
+}
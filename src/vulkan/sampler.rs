@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use ash::vk;
+
+use super::VkDevice;
+
+// Anisotropy values of 1 or less disable anisotropic filtering, larger values are clamped to
+// the device limit. A compare op turns the sampler into a depth comparison sampler.
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerDesc {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    pub border_color: vk::BorderColor,
+    pub mip_lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub max_anisotropy: f32,
+    pub compare_op: Option<vk::CompareOp>,
+}
+
+pub struct VkSampler {
+    device: Arc<VkDevice>,
+    pub handle: vk::Sampler,
+    pub desc: SamplerDesc,
+}
+
+// Hands out one sampler per distinct description, samplers stay alive as long as the cache
+pub struct VkSamplerCache {
+    device: Arc<VkDevice>,
+    samplers: Mutex<HashMap<SamplerDesc, Arc<VkSampler>>>,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::REPEAT,
+            address_mode_v: vk::SamplerAddressMode::REPEAT,
+            address_mode_w: vk::SamplerAddressMode::REPEAT,
+            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+            mip_lod_bias: 0.0,
+            min_lod: 0.0,
+            max_lod: vk::LOD_CLAMP_NONE,
+            max_anisotropy: f32::MAX,
+            compare_op: None,
+        }
+    }
+}
+
+impl SamplerDesc {
+    // glTF sampler values, https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#samplers
+    const GL_NEAREST: u32 = 9728;
+    const GL_LINEAR: u32 = 9729;
+    const GL_NEAREST_MIPMAP_NEAREST: u32 = 9984;
+    const GL_LINEAR_MIPMAP_NEAREST: u32 = 9985;
+    const GL_NEAREST_MIPMAP_LINEAR: u32 = 9986;
+    const GL_CLAMP_TO_EDGE: u32 = 33071;
+    const GL_MIRRORED_REPEAT: u32 = 33648;
+
+    // Hardware PCF for shadow maps, texels outside of the map are treated as lit
+    pub fn shadow_map() -> SamplerDesc {
+        SamplerDesc {
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_BORDER,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_BORDER,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_BORDER,
+            border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+            max_lod: 0.0,
+            max_anisotropy: 1.0,
+            compare_op: Some(vk::CompareOp::LESS_OR_EQUAL),
+            ..Default::default()
+        }
+    }
+
+    // Filters are optional in glTF, the defaults are used when the asset leaves them out
+    pub fn from_gltf(
+        mag_filter: Option<u32>,
+        min_filter: Option<u32>,
+        wrap_s: u32,
+        wrap_t: u32,
+    ) -> SamplerDesc {
+        // Filters without mipmapping are emulated by clamping the LOD to the base level, as
+        // suggested by the Vulkan specification
+        let base_level = 0.25;
+        let (min_filter, mipmap_mode, max_lod) = match min_filter {
+            Some(Self::GL_NEAREST) => (
+                vk::Filter::NEAREST,
+                vk::SamplerMipmapMode::NEAREST,
+                base_level,
+            ),
+            Some(Self::GL_LINEAR) => (
+                vk::Filter::LINEAR,
+                vk::SamplerMipmapMode::NEAREST,
+                base_level,
+            ),
+            Some(Self::GL_NEAREST_MIPMAP_NEAREST) => (
+                vk::Filter::NEAREST,
+                vk::SamplerMipmapMode::NEAREST,
+                vk::LOD_CLAMP_NONE,
+            ),
+            Some(Self::GL_LINEAR_MIPMAP_NEAREST) => (
+                vk::Filter::LINEAR,
+                vk::SamplerMipmapMode::NEAREST,
+                vk::LOD_CLAMP_NONE,
+            ),
+            Some(Self::GL_NEAREST_MIPMAP_LINEAR) => (
+                vk::Filter::NEAREST,
+                vk::SamplerMipmapMode::LINEAR,
+                vk::LOD_CLAMP_NONE,
+            ),
+            _ => (
+                vk::Filter::LINEAR,
+                vk::SamplerMipmapMode::LINEAR,
+                vk::LOD_CLAMP_NONE,
+            ),
+        };
+
+        SamplerDesc {
+            mag_filter: Self::gltf_filter(mag_filter),
+            min_filter,
+            mipmap_mode,
+            address_mode_u: Self::gltf_address_mode(wrap_s),
+            address_mode_v: Self::gltf_address_mode(wrap_t),
+            max_lod,
+            ..Default::default()
+        }
+    }
+
+    // Texture options of an MTL map statement such as `map_Kd -clamp on texture.png`
+    pub fn from_mtl_options(statement: &str) -> SamplerDesc {
+        let mut tokens = statement.split_whitespace();
+        let mut clamp = false;
+        while let Some(token) = tokens.next() {
+            if token == "-clamp" {
+                clamp = tokens.next() == Some("on");
+            }
+        }
+
+        if clamp {
+            SamplerDesc {
+                address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                ..Default::default()
+            }
+        } else {
+            SamplerDesc::default()
+        }
+    }
+
+    fn gltf_filter(filter: Option<u32>) -> vk::Filter {
+        if filter == Some(Self::GL_NEAREST) {
+            vk::Filter::NEAREST
+        } else {
+            vk::Filter::LINEAR
+        }
+    }
+
+    fn gltf_address_mode(wrap: u32) -> vk::SamplerAddressMode {
+        match wrap {
+            Self::GL_CLAMP_TO_EDGE => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            Self::GL_MIRRORED_REPEAT => vk::SamplerAddressMode::MIRRORED_REPEAT,
+            _ => vk::SamplerAddressMode::REPEAT,
+        }
+    }
+
+    // Floats are compared by their bits so that descriptions can be used as hash map keys
+    fn key(&self) -> impl PartialEq + Hash {
+        (
+            [
+                self.mag_filter.as_raw(),
+                self.min_filter.as_raw(),
+                self.mipmap_mode.as_raw(),
+                self.address_mode_u.as_raw(),
+                self.address_mode_v.as_raw(),
+                self.address_mode_w.as_raw(),
+                self.border_color.as_raw(),
+            ],
+            [
+                self.mip_lod_bias.to_bits(),
+                self.min_lod.to_bits(),
+                self.max_lod.to_bits(),
+                self.max_anisotropy.to_bits(),
+            ],
+            self.compare_op.map(|op| op.as_raw()),
+        )
+    }
+}
+
+impl PartialEq for SamplerDesc {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerDesc {}
+
+impl Hash for SamplerDesc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+impl VkSampler {
+    pub fn new(device: &Arc<VkDevice>, desc: SamplerDesc) -> VkSampler {
+        let max_anisotropy = anisotropy(
+            desc.max_anisotropy,
+            device.get_properties().limits.max_sampler_anisotropy,
+            device.enabled_features().v1_0.sampler_anisotropy == vk::TRUE,
+        );
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(desc.mag_filter)
+            .min_filter(desc.min_filter)
+            .address_mode_u(desc.address_mode_u)
+            .address_mode_v(desc.address_mode_v)
+            .address_mode_w(desc.address_mode_w)
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1.0))
+            .border_color(desc.border_color)
+            .unnormalized_coordinates(false)
+            .compare_enable(desc.compare_op.is_some())
+            .compare_op(desc.compare_op.unwrap_or(vk::CompareOp::ALWAYS))
+            .mipmap_mode(desc.mipmap_mode)
+            .mip_lod_bias(desc.mip_lod_bias)
+            .min_lod(desc.min_lod)
+            .max_lod(desc.max_lod);
+
+        let handle = unsafe { device.handle.create_sampler(&sampler_info, None).unwrap() };
+        device.track(handle, "sampler");
+
+        VkSampler {
+            device: Arc::clone(device),
+            handle,
+            desc,
+        }
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
+}
+
+impl Drop for VkSampler {
+    fn drop(&mut self) {
+        log::debug!("Dropping sampler");
+        self.device.untrack(self.handle);
+        unsafe {
+            self.device.handle.destroy_sampler(self.handle, None);
+        }
+    }
+}
+
+// Anisotropic filtering needs the feature and more than one sample
+fn anisotropy(requested: f32, device_max: f32, enabled: bool) -> Option<f32> {
+    let max_anisotropy = requested.max(1.0).min(device_max);
+    if enabled && max_anisotropy > 1.0 {
+        Some(max_anisotropy)
+    } else {
+        None
+    }
+}
+
+impl VkSamplerCache {
+    pub fn new(device: &Arc<VkDevice>) -> VkSamplerCache {
+        VkSamplerCache {
+            device: Arc::clone(device),
+            samplers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, desc: SamplerDesc) -> Arc<VkSampler> {
+        let mut samplers = self.samplers.lock().unwrap();
+        let sampler = samplers.entry(desc).or_insert_with(|| {
+            log::debug!("Creating sampler {:?}", desc);
+            let sampler = VkSampler::new(&self.device, desc);
+            // Samplers are shared, so they are named after their description
+            sampler.set_name(&format!("{:?}", desc));
+            Arc::new(sampler)
+        });
+        Arc::clone(sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn equal_descriptions_share_a_key() {
+        let mut descs = HashSet::new();
+        descs.insert(SamplerDesc::default());
+        descs.insert(SamplerDesc::default());
+        descs.insert(SamplerDesc {
+            max_anisotropy: f32::MAX,
+            ..Default::default()
+        });
+        assert_eq!(descs.len(), 1);
+
+        descs.insert(SamplerDesc {
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ..Default::default()
+        });
+        descs.insert(SamplerDesc {
+            compare_op: Some(vk::CompareOp::LESS),
+            ..Default::default()
+        });
+        descs.insert(SamplerDesc {
+            max_lod: 0.0,
+            ..Default::default()
+        });
+        assert_eq!(descs.len(), 4);
+    }
+
+    #[test]
+    fn floats_are_compared_by_bits() {
+        let bias = |mip_lod_bias| SamplerDesc {
+            mip_lod_bias,
+            ..Default::default()
+        };
+        // NaN equals itself as a key, while the two zeros are different values
+        assert_eq!(bias(f32::NAN), bias(f32::NAN));
+        assert_ne!(bias(0.0), bias(-0.0));
+
+        let descs: HashSet<SamplerDesc> = [bias(f32::NAN), bias(f32::NAN), bias(0.5), bias(0.5)]
+            .into_iter()
+            .collect();
+        assert_eq!(descs.len(), 2);
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_and_needs_the_feature() {
+        assert_eq!(anisotropy(16.0, 8.0, true), Some(8.0));
+        assert_eq!(anisotropy(4.0, 16.0, true), Some(4.0));
+        assert_eq!(anisotropy(0.0, 16.0, true), None);
+        assert_eq!(anisotropy(f32::NAN, 16.0, true), None);
+        assert_eq!(anisotropy(16.0, 16.0, false), None);
+    }
+
+    #[test]
+    fn gltf_filters_without_mipmaps_clamp_to_the_base_level() {
+        let desc = SamplerDesc::from_gltf(Some(9728), Some(9729), 33071, 33648);
+        assert_eq!(desc.mag_filter, vk::Filter::NEAREST);
+        assert_eq!(desc.min_filter, vk::Filter::LINEAR);
+        assert_eq!(desc.mipmap_mode, vk::SamplerMipmapMode::NEAREST);
+        assert_eq!(desc.max_lod, 0.25);
+        assert_eq!(desc.address_mode_u, vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        assert_eq!(desc.address_mode_v, vk::SamplerAddressMode::MIRRORED_REPEAT);
+
+        let desc = SamplerDesc::from_gltf(None, Some(9986), 10497, 10497);
+        assert_eq!(desc.mag_filter, vk::Filter::LINEAR);
+        assert_eq!(desc.min_filter, vk::Filter::NEAREST);
+        assert_eq!(desc.mipmap_mode, vk::SamplerMipmapMode::LINEAR);
+        assert_eq!(desc.max_lod, vk::LOD_CLAMP_NONE);
+        assert_eq!(desc.address_mode_u, vk::SamplerAddressMode::REPEAT);
+
+        let desc = SamplerDesc::from_gltf(None, None, 10497, 10497);
+        assert_eq!(desc, SamplerDesc::default());
+    }
+
+    #[test]
+    fn mtl_clamp_option() {
+        let clamped = SamplerDesc::from_mtl_options("map_Kd -clamp on texture.png");
+        assert_eq!(
+            clamped.address_mode_u,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE
+        );
+        assert_eq!(
+            clamped.address_mode_w,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE
+        );
+
+        for statement in [
+            "map_Kd -clamp off texture.png",
+            "map_Kd texture.png",
+            "-clamp",
+        ] {
+            let desc = SamplerDesc::from_mtl_options(statement);
+            assert_eq!(desc, SamplerDesc::default());
+        }
+    }
+
+    #[test]
+    fn shadow_maps_compare_and_treat_outside_texels_as_lit() {
+        let desc = SamplerDesc::shadow_map();
+        assert_eq!(desc.compare_op, Some(vk::CompareOp::LESS_OR_EQUAL));
+        assert_eq!(desc.border_color, vk::BorderColor::FLOAT_OPAQUE_WHITE);
+        assert_eq!(desc.address_mode_v, vk::SamplerAddressMode::CLAMP_TO_BORDER);
+        assert_eq!(desc.max_lod, 0.0);
+        assert_ne!(desc, SamplerDesc::default());
+    }
+}
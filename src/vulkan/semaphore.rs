@@ -18,17 +18,23 @@ impl VkSemaphore {
                 .create_semaphore(&create_info, None)
                 .expect("Unable t ocreate a semaphore")
         };
+        device.track(handle, "semaphore");
 
         VkSemaphore {
             device: Arc::clone(device),
             handle,
         }
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkSemaphore {
     fn drop(&mut self) {
         log::debug!("Dropping semaphore");
+        self.device.untrack(self.handle);
         unsafe {
             self.device.handle.destroy_semaphore(self.handle, None);
         }
@@ -1,3 +1,246 @@
+use std::env;
+
+use ash::vk;
+
+use super::physical_device::DeviceType;
+
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+pub const API_DUMP_LAYER: &str = "VK_LAYER_LUNARG_api_dump";
+
+// Which physical device to use, devices which are not suitable are never selected
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceSelector {
+    Any,
+    // Position in the list of enumerated devices
+    Index(usize),
+    // Case insensitive substring of the device name
+    Name(String),
+    Vendor(u32),
+    Type(DeviceType),
+}
+
+// Ranks the suitable devices when the selector leaves a choice
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DevicePreference {
+    HighPerformance,
+    PowerSaving,
+}
+
 pub struct VkSettings {
     pub validation: bool,
+    // Abort on the first validation error, meant for automated runs
+    pub fail_on_validation_error: bool,
+    // Features of the validation layer, see VK_EXT_validation_features
+    pub gpu_assisted_validation: bool,
+    pub best_practices_validation: bool,
+    pub synchronization_validation: bool,
+    // Logs every API call through the api_dump layer
+    pub api_dump: bool,
+    pub extra_layers: Vec<String>,
+    pub device: DeviceSelector,
+    pub device_preference: DevicePreference,
+    // Present through an sRGB swap-chain format, otherwise the shaders encode to sRGB themselves
+    pub srgb_output: bool,
+    // Record barriers through VK_KHR_synchronization2 when the device supports it
+    pub synchronization2: bool,
+}
+
+// Validation is enabled in debug builds only
+impl Default for VkSettings {
+    fn default() -> VkSettings {
+        VkSettings {
+            validation: cfg!(debug_assertions),
+            fail_on_validation_error: false,
+            gpu_assisted_validation: false,
+            best_practices_validation: false,
+            synchronization_validation: false,
+            api_dump: false,
+            extra_layers: Vec::new(),
+            device: DeviceSelector::Any,
+            device_preference: DevicePreference::HighPerformance,
+            srgb_output: true,
+            synchronization2: true,
+        }
+    }
+}
+
+impl VkSettings {
+    // Boolean variables accept 1/0, true/false, on/off and yes/no, `VULKAN_LAYERS` is a comma
+    // separated list of additional layers
+    pub fn with_env_overrides(mut self) -> VkSettings {
+        override_flag("VULKAN_VALIDATION", &mut self.validation);
+        override_flag(
+            "VULKAN_VALIDATION_FAIL_ON_ERROR",
+            &mut self.fail_on_validation_error,
+        );
+        override_flag(
+            "VULKAN_VALIDATION_GPU_ASSISTED",
+            &mut self.gpu_assisted_validation,
+        );
+        override_flag(
+            "VULKAN_VALIDATION_BEST_PRACTICES",
+            &mut self.best_practices_validation,
+        );
+        override_flag(
+            "VULKAN_VALIDATION_SYNCHRONIZATION",
+            &mut self.synchronization_validation,
+        );
+        override_flag("VULKAN_API_DUMP", &mut self.api_dump);
+        override_flag("VULKAN_SRGB_OUTPUT", &mut self.srgb_output);
+        override_flag("VULKAN_SYNCHRONIZATION2", &mut self.synchronization2);
+        if let Ok(device) = env::var("VULKAN_DEVICE") {
+            self.device = DeviceSelector::parse(&device);
+        }
+        if let Ok(preference) = env::var("VULKAN_DEVICE_PREFERENCE") {
+            override_preference(
+                "VULKAN_DEVICE_PREFERENCE",
+                &preference,
+                &mut self.device_preference,
+            );
+        }
+        if let Ok(layers) = env::var("VULKAN_LAYERS") {
+            self.extra_layers = layers
+                .split(',')
+                .map(str::trim)
+                .filter(|layer| !layer.is_empty())
+                .map(str::to_owned)
+                .collect();
+        }
+        self
+    }
+
+    // Command line options take precedence over the environment. Supports `--device <selector>`
+    // and `--device-preference <performance|power-saving>`, other arguments are ignored.
+    pub fn with_args(mut self, args: impl IntoIterator<Item = String>) -> VkSettings {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (arg, None),
+            };
+            if name != "--device" && name != "--device-preference" {
+                continue;
+            }
+            let value = match value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => {
+                    log::warn!("Missing value for {}", name);
+                    continue;
+                }
+            };
+            if name == "--device" {
+                self.device = DeviceSelector::parse(&value);
+            } else {
+                override_preference(&name, &value, &mut self.device_preference);
+            }
+        }
+        self
+    }
+
+    pub fn layers(&self) -> Vec<String> {
+        let mut layers = Vec::new();
+        if self.validation {
+            layers.push(VALIDATION_LAYER.to_owned());
+        }
+        if self.api_dump {
+            layers.push(API_DUMP_LAYER.to_owned());
+        }
+        for layer in &self.extra_layers {
+            if !layers.contains(layer) {
+                layers.push(layer.clone());
+            }
+        }
+        layers
+    }
+
+    pub fn validation_features(&self) -> Vec<vk::ValidationFeatureEnableEXT> {
+        let mut features = Vec::new();
+        if !self.validation {
+            return features;
+        }
+        if self.gpu_assisted_validation {
+            features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        if self.best_practices_validation {
+            features.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+        }
+        if self.synchronization_validation {
+            features.push(vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+        features
+    }
+}
+
+impl DeviceSelector {
+    // Accepts an index, `name:<text>`, `vendor:<nvidia|amd|intel|arm|qualcomm|apple|id>` and
+    // `type:<discrete|integrated|virtual|cpu>`, anything else is matched against the name
+    pub fn parse(value: &str) -> DeviceSelector {
+        let value = value.trim();
+        if let Ok(index) = value.parse() {
+            return DeviceSelector::Index(index);
+        }
+
+        let (kind, argument) = value.split_once(':').unwrap_or(("name", value));
+        let argument = argument.trim().to_lowercase();
+        let selector = match kind.trim().to_lowercase().as_str() {
+            "name" => Some(DeviceSelector::Name(argument)),
+            "vendor" => parse_vendor(&argument).map(DeviceSelector::Vendor),
+            "type" => parse_device_type(&argument).map(DeviceSelector::Type),
+            _ => Some(DeviceSelector::Name(value.to_lowercase())),
+        };
+        selector.unwrap_or_else(|| {
+            log::warn!("Ignoring unknown device selector {}", value);
+            DeviceSelector::Any
+        })
+    }
+}
+
+fn parse_vendor(vendor: &str) -> Option<u32> {
+    match vendor {
+        "nvidia" => Some(0x10de),
+        "amd" => Some(0x1002),
+        "intel" => Some(0x8086),
+        "arm" => Some(0x13b5),
+        "qualcomm" => Some(0x5143),
+        "apple" => Some(0x106b),
+        _ => match vendor.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => vendor.parse().ok(),
+        },
+    }
+}
+
+fn parse_device_type(kind: &str) -> Option<DeviceType> {
+    match kind {
+        "discrete" => Some(DeviceType::DiscreteGpu),
+        "integrated" => Some(DeviceType::IntegratedGpu),
+        "virtual" => Some(DeviceType::VirtualGpu),
+        "cpu" => Some(DeviceType::Cpu),
+        _ => None,
+    }
+}
+
+fn override_preference(name: &str, value: &str, preference: &mut DevicePreference) {
+    match value.trim().to_lowercase().as_str() {
+        "performance" | "high-performance" => *preference = DevicePreference::HighPerformance,
+        "power-saving" | "low-power" => *preference = DevicePreference::PowerSaving,
+        _ => log::warn!(
+            "Ignoring {}={}, expected performance or power-saving",
+            name,
+            value
+        ),
+    }
+}
+
+fn override_flag(name: &str, flag: &mut bool) {
+    let value = match env::var(name) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => *flag = true,
+        "0" | "false" | "off" | "no" => *flag = false,
+        _ => log::warn!("Ignoring {}={}, expected a boolean", name, value),
+    }
 }
@@ -42,13 +42,16 @@ impl VkShaderModule {
                 .create_shader_module(&create_info, None)
                 .expect("Unable to create shader module")
         };
+        device.track(handle, "shader module");
 
-        VkShaderModule {
+        let module = VkShaderModule {
             device: Arc::clone(device),
             handle,
             stage,
             entry_point: CString::new(entry_point).unwrap(),
-        }
+        };
+        module.set_name(path);
+        module
     }
 
     pub fn create_pipeline_shader_stage(&self) -> vk::PipelineShaderStageCreateInfoBuilder {
@@ -57,11 +60,16 @@ impl VkShaderModule {
             .module(self.handle)
             .name(&self.entry_point)
     }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkShaderModule {
     fn drop(&mut self) {
         log::debug!("Dropping shader module");
+        self.device.untrack(self.handle);
         unsafe {
             self.device.handle.destroy_shader_module(self.handle, None);
         }
@@ -3,17 +3,14 @@ use std::sync::Arc;
 use ash::{extensions::khr::Swapchain, prelude::VkResult, vk};
 
 use super::{
-    device::VkDevice, render_pass::VkRenderPass, semaphore::VkSemaphore, surface::VkSurface, utils,
-    VkCommandBuffer, VkCommandPool, VkFence, VkImage, VkTexture,
+    device::VkDevice, semaphore::VkSemaphore, surface::VkSurface, utils, VkCommandBuffer,
+    VkCommandPool, VkImage, VkQueue,
 };
 
 pub struct VkSwapChainImage {
     device: Arc<VkDevice>,
     pub image: vk::Image,
     pub view: vk::ImageView,
-    pub color_image: VkTexture,
-    pub depth_image: VkTexture,
-    pub framebuffer: vk::Framebuffer,
     pub frame: Option<usize>,
     pub command_buffer: VkCommandBuffer,
 }
@@ -21,7 +18,6 @@ pub struct VkSwapChainImage {
 pub struct VkFrame {
     pub available: VkSemaphore,
     pub finished: VkSemaphore,
-    pub in_flight: VkFence,
 }
 
 pub struct VkSwapChain {
@@ -36,6 +32,7 @@ pub struct VkSwapChain {
 }
 
 impl VkSwapChain {
+    // The images of `old_swap_chain` can still be presented until it is dropped
     pub fn new(
         device: &Arc<VkDevice>,
         surface: &VkSurface,
@@ -43,6 +40,7 @@ impl VkSwapChain {
         present_mode: vk::PresentModeKHR,
         image_count: u32,
         dimensions: &[u32; 2],
+        old_swap_chain: Option<&VkSwapChain>,
     ) -> VkSwapChain {
         let surface_caps =
             surface.get_physical_device_surface_capabilities(&device.physical_device);
@@ -66,13 +64,11 @@ impl VkSwapChain {
             .pre_transform(surface_caps.capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
-            .clipped(true);
+            .clipped(true)
+            .old_swapchain(old_swap_chain.map_or(vk::SwapchainKHR::null(), |old| old.handle));
 
-        let queue_family_indices = [
-            device.graphics_queue_family,
-            device.presentation_queue_family,
-        ];
-        if device.graphics_queue_family != device.presentation_queue_family {
+        let queue_family_indices = [device.queues.graphics.family, device.queues.present.family];
+        if device.queues.graphics.family != device.queues.present.family {
             create_info = create_info
                 .image_sharing_mode(vk::SharingMode::CONCURRENT)
                 .queue_family_indices(&queue_family_indices);
@@ -86,6 +82,7 @@ impl VkSwapChain {
                 .create_swapchain(&create_info, None)
                 .expect("Unable to create swap chain")
         };
+        device.track(handle, "swap-chain");
 
         VkSwapChain {
             device: Arc::clone(device),
@@ -111,15 +108,8 @@ impl VkSwapChain {
         (frame + 1) % self.frame_count()
     }
 
-    pub fn initialize_images(
-        &mut self,
-        max_frames: usize,
-        render_pass: &VkRenderPass,
-        depth_format: vk::Format,
-        msaa_samples: vk::SampleCountFlags,
-        command_pool: &Arc<VkCommandPool>,
-        transfer_queue: vk::Queue,
-    ) {
+    // The images are imported into the render graph, which creates their framebuffers
+    pub fn initialize_images(&mut self, max_frames: usize, command_pool: &Arc<VkCommandPool>) {
         log::info!("Creating swap-chain images");
         let images = unsafe {
             self.extension
@@ -127,7 +117,7 @@ impl VkSwapChain {
                 .expect("Unable to get swap chain images")
         };
 
-        for &image in &images {
+        for (index, &image) in images.iter().enumerate() {
             let color_format = self.format.format;
 
             let view = VkImage::create_image_view(
@@ -138,29 +128,13 @@ impl VkSwapChain {
                 vk::ImageAspectFlags::COLOR,
             );
 
-            let color_image =
-                VkImage::create_color_image(&self.device, color_format, self.extent, msaa_samples);
-
-            let depth_image = VkImage::create_depth_image(
-                &self.device,
-                &command_pool,
-                transfer_queue,
-                depth_format,
-                self.extent,
-                msaa_samples,
-            );
-
-            let framebuffer =
-                self.create_frame_buffer(view, render_pass, &depth_image, &color_image);
             let command_buffer = VkCommandBuffer::new(command_pool, true);
+            command_buffer.set_name(&format!("swap-chain image {} commands", index));
 
             let swap_image = VkSwapChainImage {
                 device: Arc::clone(&self.device),
                 image,
                 view,
-                color_image,
-                depth_image,
-                framebuffer,
                 frame: None,
                 command_buffer,
             };
@@ -169,44 +143,21 @@ impl VkSwapChain {
         }
 
         let frame_count = max_frames.min(images.len());
-        for _ in 0..frame_count {
+        for index in 0..frame_count {
             let available = VkSemaphore::new(&self.device);
             let finished = VkSemaphore::new(&self.device);
-            let in_flight = VkFence::new(&self.device);
+            available.set_name(&format!("frame {} image available", index));
+            finished.set_name(&format!("frame {} render finished", index));
 
             let frame = VkFrame {
                 available,
                 finished,
-                in_flight,
             };
 
             self.frames.push(frame);
         }
     }
 
-    pub fn create_frame_buffer(
-        &self,
-        view: vk::ImageView,
-        render_pass: &VkRenderPass,
-        depth_image: &VkTexture,
-        color_image: &VkTexture,
-    ) -> vk::Framebuffer {
-        let attachments = [color_image.view, depth_image.view, view];
-        let framebuffer_info = vk::FramebufferCreateInfo::builder()
-            .render_pass(render_pass.handle)
-            .attachments(&attachments)
-            .width(self.extent.width)
-            .height(self.extent.height)
-            .layers(1)
-            .build();
-        unsafe {
-            self.device
-                .handle
-                .create_framebuffer(&framebuffer_info, None)
-                .expect("Unable to create framebuffer")
-        }
-    }
-
     pub fn acquire_next_image(&self, semaphore: &VkSemaphore) -> VkResult<(u32, bool)> {
         unsafe {
             self.extension.acquire_next_image(
@@ -220,7 +171,7 @@ impl VkSwapChain {
 
     pub fn present_image(
         &self,
-        queue: vk::Queue,
+        queue: &VkQueue,
         index: u32,
         semaphores: &[&VkSemaphore],
     ) -> VkResult<bool> {
@@ -232,7 +183,7 @@ impl VkSwapChain {
             .swapchains(&swapchains)
             .image_indices(&image_indices);
 
-        unsafe { self.extension.queue_present(queue, &present_info) }
+        queue.present(&self.extension, &present_info)
     }
 
     pub fn cleanup_images(&mut self) {
@@ -242,16 +193,16 @@ impl VkSwapChain {
     }
 
     // TODO: Resize method
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
 }
 
 impl Drop for VkSwapChainImage {
     fn drop(&mut self) {
+        self.device.untrack(self.view);
         unsafe { self.device.handle.destroy_image_view(self.view, None) };
-        unsafe {
-            self.device
-                .handle
-                .destroy_framebuffer(self.framebuffer, None)
-        };
     }
 }
 
@@ -259,6 +210,7 @@ impl Drop for VkSwapChain {
     fn drop(&mut self) {
         log::debug!("Dropping swap chain");
         self.cleanup_images();
+        self.device.untrack(self.handle);
         unsafe {
             self.extension.destroy_swapchain(self.handle, None);
         }
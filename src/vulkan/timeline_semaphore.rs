@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{device::VkDevice, utils::AsRawHandle};
+
+// Semaphore with a 64 bit counter which only increases. The GPU and the host can both wait for
+// and signal values, a value is reached once every submission signaling up to it has finished.
+pub struct VkTimelineSemaphore {
+    device: Arc<VkDevice>,
+    pub handle: vk::Semaphore,
+}
+
+impl VkTimelineSemaphore {
+    pub fn new(device: &Arc<VkDevice>, initial_value: u64) -> VkTimelineSemaphore {
+        let handle = create_timeline(&device.handle, initial_value);
+        device.track(handle, "timeline semaphore");
+
+        VkTimelineSemaphore {
+            device: Arc::clone(device),
+            handle,
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        timeline_value(&self.device.handle, self.handle)
+    }
+
+    pub fn wait(&self, value: u64) {
+        wait_timeline(&self.device.handle, self.handle, value, u64::MAX);
+    }
+
+    // Returns false when the value was not reached within `timeout` nanoseconds
+    pub fn wait_timeout(&self, value: u64, timeout: u64) -> bool {
+        wait_timeline(&self.device.handle, self.handle, value, timeout)
+    }
+
+    pub fn signal(&self, value: u64) {
+        signal_timeline(&self.device.handle, self.handle, value);
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
+}
+
+impl Drop for VkTimelineSemaphore {
+    fn drop(&mut self) {
+        log::debug!("Dropping timeline semaphore");
+        self.device.untrack(self.handle);
+        unsafe {
+            self.device.handle.destroy_semaphore(self.handle, None);
+        }
+    }
+}
+
+impl AsRawHandle for &VkTimelineSemaphore {
+    type Handle = vk::Semaphore;
+
+    fn as_raw_handle(&self) -> Self::Handle {
+        self.handle
+    }
+}
+
+pub fn create_timeline(device: &ash::Device, initial_value: u64) -> vk::Semaphore {
+    let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+        .semaphore_type(vk::SemaphoreType::TIMELINE)
+        .initial_value(initial_value);
+    let create_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+    unsafe {
+        device
+            .create_semaphore(&create_info, None)
+            .expect("Unable to create timeline semaphore")
+    }
+}
+
+pub fn timeline_value(device: &ash::Device, semaphore: vk::Semaphore) -> u64 {
+    unsafe {
+        device
+            .get_semaphore_counter_value(semaphore)
+            .expect("Unable to query timeline semaphore")
+    }
+}
+
+pub fn signal_timeline(device: &ash::Device, semaphore: vk::Semaphore, value: u64) {
+    let signal_info = vk::SemaphoreSignalInfo::builder()
+        .semaphore(semaphore)
+        .value(value);
+    unsafe {
+        device
+            .signal_semaphore(&signal_info)
+            .expect("Unable to signal timeline semaphore")
+    };
+}
+
+pub fn wait_timeline(
+    device: &ash::Device,
+    semaphore: vk::Semaphore,
+    value: u64,
+    timeout: u64,
+) -> bool {
+    let semaphores = [semaphore];
+    let values = [value];
+    let wait_info = vk::SemaphoreWaitInfo::builder()
+        .semaphores(&semaphores)
+        .values(&values);
+    match unsafe { device.wait_semaphores(&wait_info, timeout) } {
+        Ok(()) => true,
+        Err(vk::Result::TIMEOUT) => false,
+        Err(error) => panic!("Unable to wait for timeline semaphore: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{c_void, CStr},
+        os::raw::c_char,
+        ptr,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use ash::vk::Handle;
+
+    use super::*;
+
+    // A driver with a single timeline, enough to check how the host side uses it
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    unsafe extern "system" fn signal_semaphore(
+        _device: vk::Device,
+        signal_info: *const vk::SemaphoreSignalInfo,
+    ) -> vk::Result {
+        COUNTER.store((*signal_info).value, Ordering::SeqCst);
+        vk::Result::SUCCESS
+    }
+
+    unsafe extern "system" fn wait_semaphores(
+        _device: vk::Device,
+        wait_info: *const vk::SemaphoreWaitInfo,
+        _timeout: u64,
+    ) -> vk::Result {
+        if COUNTER.load(Ordering::SeqCst) >= *(*wait_info).p_values {
+            vk::Result::SUCCESS
+        } else {
+            vk::Result::TIMEOUT
+        }
+    }
+
+    unsafe extern "system" fn get_semaphore_counter_value(
+        _device: vk::Device,
+        _semaphore: vk::Semaphore,
+        value: *mut u64,
+    ) -> vk::Result {
+        *value = COUNTER.load(Ordering::SeqCst);
+        vk::Result::SUCCESS
+    }
+
+    unsafe extern "system" fn get_device_proc_addr(
+        _device: vk::Device,
+        name: *const c_char,
+    ) -> vk::PFN_vkVoidFunction {
+        let function = match CStr::from_ptr(name).to_bytes() {
+            b"vkSignalSemaphore" => signal_semaphore as *const c_void,
+            b"vkWaitSemaphores" => wait_semaphores as *const c_void,
+            b"vkGetSemaphoreCounterValue" => get_semaphore_counter_value as *const c_void,
+            _ => return None,
+        };
+        Some(std::mem::transmute::<
+            *const c_void,
+            unsafe extern "system" fn(),
+        >(function))
+    }
+
+    fn device() -> ash::Device {
+        let instance_fn = vk::InstanceFnV1_0::load(|name| {
+            if name.to_bytes() == b"vkGetDeviceProcAddr" {
+                get_device_proc_addr as *const c_void
+            } else {
+                ptr::null()
+            }
+        });
+        unsafe { ash::Device::load(&instance_fn, vk::Device::from_raw(1)) }
+    }
+
+    #[test]
+    fn host_signals_release_waits() {
+        let device = device();
+        let semaphore = vk::Semaphore::from_raw(1);
+
+        assert!(!wait_timeline(&device, semaphore, 3, 0));
+        signal_timeline(&device, semaphore, 3);
+        assert_eq!(timeline_value(&device, semaphore), 3);
+        assert!(wait_timeline(&device, semaphore, 2, 0));
+        assert!(wait_timeline(&device, semaphore, 3, 0));
+        assert!(!wait_timeline(&device, semaphore, 4, 0));
+    }
+}
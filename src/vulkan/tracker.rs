@@ -0,0 +1,122 @@
+use std::{
+    backtrace::Backtrace,
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use ash::vk::{self, Handle};
+
+struct TrackedObject {
+    label: String,
+    backtrace: Backtrace,
+}
+
+type DeviceObjects = HashMap<(vk::ObjectType, u64), TrackedObject>;
+
+// Live objects of every live device, keyed by the raw device handle. Only filled in debug builds.
+static DEVICES: Mutex<BTreeMap<u64, DeviceObjects>> = Mutex::new(BTreeMap::new());
+
+pub fn register_device(device: vk::Device) {
+    if cfg!(debug_assertions) {
+        DEVICES
+            .lock()
+            .unwrap()
+            .insert(device.as_raw(), HashMap::new());
+    }
+}
+
+// Reports the objects still alive and forgets the device. Creating or destroying its objects
+// afterwards is reported as an error, other uses of them are not detected.
+pub fn unregister_device(device: vk::Device) {
+    if cfg!(debug_assertions) {
+        report_live_objects(device);
+        DEVICES.lock().unwrap().remove(&device.as_raw());
+    }
+}
+
+pub fn track<H: Handle>(device: vk::Device, handle: H, label: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    let mut devices = DEVICES.lock().unwrap();
+    let objects = match devices.get_mut(&device.as_raw()) {
+        Some(objects) => objects,
+        None => {
+            log::error!("{:?} '{}' created from a destroyed device", H::TYPE, label);
+            return;
+        }
+    };
+    let object = TrackedObject {
+        label: label.to_owned(),
+        backtrace: Backtrace::force_capture(),
+    };
+    objects.insert((H::TYPE, handle.as_raw()), object);
+}
+
+pub fn rename<H: Handle>(device: vk::Device, handle: H, label: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    let mut devices = DEVICES.lock().unwrap();
+    let object = devices
+        .get_mut(&device.as_raw())
+        .and_then(|objects| objects.get_mut(&(H::TYPE, handle.as_raw())));
+    if let Some(object) = object {
+        object.label = label.to_owned();
+    }
+}
+
+pub fn untrack<H: Handle>(device: vk::Device, handle: H) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    let raw_handle = handle.as_raw();
+    let mut devices = DEVICES.lock().unwrap();
+    let objects = match devices.get_mut(&device.as_raw()) {
+        Some(objects) => objects,
+        None => {
+            log::error!(
+                "{:?} {:#x} destroyed after its device was destroyed",
+                H::TYPE,
+                raw_handle
+            );
+            return;
+        }
+    };
+    if objects.remove(&(H::TYPE, raw_handle)).is_none() {
+        log::error!(
+            "{:?} {:#x} destroyed but not alive, it was destroyed twice or never tracked",
+            H::TYPE,
+            raw_handle
+        );
+    }
+}
+
+// Objects still alive are leaked when the device is destroyed
+fn report_live_objects(device: vk::Device) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    let devices = DEVICES.lock().unwrap();
+    let objects = match devices.get(&device.as_raw()) {
+        Some(objects) if !objects.is_empty() => objects,
+        _ => return,
+    };
+    log::error!("{} Vulkan objects are still alive", objects.len());
+
+    let mut objects: Vec<_> = objects.iter().collect();
+    objects.sort_by_key(|(&(object_type, _), object)| (object_type, object.label.as_str()));
+    for ((object_type, raw_handle), object) in objects {
+        log::error!(
+            "{:?} {:#x} '{}' created at:\n{}",
+            object_type,
+            raw_handle,
+            object.label,
+            object.backtrace
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use super::{VkBuffer, VkCommandBuffer, VkQueue};
+
+// Resource whose contents are still being copied by the GPU. The staging buffers and the
+// command buffer are released once the queue has reached the value of the copy.
+pub struct VkPendingUpload<T> {
+    queue: Arc<VkQueue>,
+    value: u64,
+    resource: Option<T>,
+    command_buffer: Option<VkCommandBuffer>,
+    staging_buffers: Vec<VkBuffer>,
+}
+
+impl<T> VkPendingUpload<T> {
+    pub fn new(
+        queue: &Arc<VkQueue>,
+        value: u64,
+        resource: T,
+        command_buffer: VkCommandBuffer,
+        staging_buffers: Vec<VkBuffer>,
+    ) -> VkPendingUpload<T> {
+        VkPendingUpload {
+            queue: Arc::clone(queue),
+            value,
+            resource: Some(resource),
+            command_buffer: Some(command_buffer),
+            staging_buffers,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.queue.is_complete(self.value)
+    }
+
+    // Blocks until the copy has finished
+    pub fn wait(mut self) -> T {
+        self.release();
+        self.resource.take().unwrap()
+    }
+
+    fn release(&mut self) {
+        if self.command_buffer.is_some() {
+            self.queue.wait_for(self.value);
+            self.command_buffer = None;
+            self.staging_buffers.clear();
+        }
+    }
+}
+
+impl<T> Drop for VkPendingUpload<T> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}